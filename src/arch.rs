@@ -1,23 +1,26 @@
 use crate::{
-    error::Result,
+    error::{Error, Result},
     native_panic,
     starknet::{ArrayAbi, Secp256k1Point, Secp256r1Point},
     types::TypeBuilder,
-    utils::libc_malloc,
+    utils::{get_integer_layout, libc_malloc},
     values::Value,
 };
 use bumpalo::Bump;
 use cairo_lang_sierra::{
     extensions::{
+        circuit::CircuitTypeConcrete,
         core::{CoreLibfunc, CoreType, CoreTypeConcrete},
         starknet::{secp256::Secp256PointTypeConcrete, StarkNetTypeConcrete},
     },
     ids::ConcreteTypeId,
     program_registry::ProgramRegistry,
 };
+use starknet_types_core::felt::Felt;
 use std::{
     ffi::c_void,
     ptr::{null, NonNull},
+    slice,
 };
 
 mod aarch64;
@@ -84,8 +87,7 @@ impl AbiArgument for ValueWithInfoWrapper<'_> {
     ) -> Result<()> {
         match (self.value, self.info) {
             (value, CoreTypeConcrete::Box(info)) => {
-                let ptr =
-                    value.to_ptr(self.arena, self.registry, self.type_id, find_dict_overrides)?;
+                let ptr = value.to_ptr(self.arena, self.registry, &info.ty, find_dict_overrides)?;
 
                 let layout = self.registry.get_type(&info.ty)?.layout(self.registry)?;
                 let heap_ptr = unsafe {
@@ -100,12 +102,8 @@ impl AbiArgument for ValueWithInfoWrapper<'_> {
                 if matches!(value, Value::Null) {
                     null::<()>().to_bytes(buffer, find_dict_overrides)?;
                 } else {
-                    let ptr = value.to_ptr(
-                        self.arena,
-                        self.registry,
-                        self.type_id,
-                        find_dict_overrides,
-                    )?;
+                    let ptr =
+                        value.to_ptr(self.arena, self.registry, &info.ty, find_dict_overrides)?;
 
                     let layout = self.registry.get_type(&info.ty)?.layout(self.registry)?;
                     let heap_ptr = unsafe {
@@ -120,7 +118,68 @@ impl AbiArgument for ValueWithInfoWrapper<'_> {
             (value, CoreTypeConcrete::NonZero(info) | CoreTypeConcrete::Snapshot(info)) => self
                 .map(value, &info.ty)?
                 .to_bytes(buffer, find_dict_overrides)?,
+            (value, CoreTypeConcrete::Const(info)) => self
+                .map(value, &info.inner_ty)?
+                .to_bytes(buffer, find_dict_overrides)?,
+            (
+                Value::Struct { .. },
+                CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Sha256StateHandle(_)),
+            ) => {
+                let ptr = self.value.to_ptr(
+                    self.arena,
+                    self.registry,
+                    self.type_id,
+                    find_dict_overrides,
+                )?;
+
+                unsafe { *ptr.cast::<*mut ()>().as_ref() }.to_bytes(buffer, find_dict_overrides)?
+            }
+
+            (value, CoreTypeConcrete::Circuit(info)) => {
+                // Circuit values are passed by value: their 96-bit integers are passed as 128-bit
+                // ones and the 384-bit integers are split like the other big integers.
+                let ptr =
+                    value.to_ptr(self.arena, self.registry, self.type_id, find_dict_overrides)?;
+                let layout = self.info.layout(self.registry)?;
+                let data =
+                    unsafe { slice::from_raw_parts(ptr.cast::<u8>().as_ptr(), layout.size()) };
+
+                let u384_chunks = |data: &[u8]| {
+                    data.chunks_exact(48)
+                        .map(|chunk| {
+                            <[u8; 48]>::try_from(chunk).map_err(|_| Error::IntegerConversion)
+                        })
+                        .collect::<Result<Vec<_>>>()
+                };
+                match info {
+                    CircuitTypeConcrete::CircuitModulus(_)
+                    | CircuitTypeConcrete::CircuitData(_) => {
+                        for chunk in u384_chunks(data)? {
+                            chunk.to_bytes(buffer, find_dict_overrides)?;
+                        }
+                    }
+                    CircuitTypeConcrete::CircuitInputAccumulator(_) => {
+                        let offset = get_integer_layout(64).extend(get_integer_layout(384))?.1;
 
+                        unsafe { ptr.cast::<u64>().read() }
+                            .to_bytes(buffer, find_dict_overrides)?;
+                        for chunk in u384_chunks(&data[offset..])? {
+                            chunk.to_bytes(buffer, find_dict_overrides)?;
+                        }
+                    }
+                    CircuitTypeConcrete::U96Guarantee(_)
+                    | CircuitTypeConcrete::CircuitOutputs(_) => {
+                        for chunk in data.chunks_exact(16) {
+                            u128::from_ne_bytes(
+                                chunk.try_into().map_err(|_| Error::IntegerConversion)?,
+                            )
+                            .to_bytes(buffer, find_dict_overrides)?;
+                        }
+                    }
+                    // The rest are either builtins or zero-sized.
+                    _ => {}
+                }
+            }
             (Value::Array(_), CoreTypeConcrete::Array(_)) => {
                 // TODO: Assert that `info.ty` matches all the values' types.
 
@@ -137,8 +196,18 @@ impl AbiArgument for ValueWithInfoWrapper<'_> {
                 abi.until.to_bytes(buffer, find_dict_overrides)?;
                 abi.capacity.to_bytes(buffer, find_dict_overrides)?;
             }
-            (Value::BoundedInt { .. }, CoreTypeConcrete::BoundedInt(_)) => {
-                native_panic!("todo: implement AbiArgument for Value::BoundedInt case")
+            (Value::BoundedInt { value, .. }, CoreTypeConcrete::BoundedInt(info)) => {
+                // Bounded integers are represented by their offset from the lower bound.
+                let offset = (value - Felt::from(&info.range.lower)).to_biguint();
+                match info.range.offset_bit_width() {
+                    0..=64 => u64::try_from(offset)
+                        .map_err(|_| Error::IntegerConversion)?
+                        .to_bytes(buffer, find_dict_overrides)?,
+                    65..=128 => u128::try_from(offset)
+                        .map_err(|_| Error::IntegerConversion)?
+                        .to_bytes(buffer, find_dict_overrides)?,
+                    _ => Felt::from(offset).to_bytes(buffer, find_dict_overrides)?,
+                }
             }
             (Value::Bytes31(value), CoreTypeConcrete::Bytes31(_)) => {
                 value.to_bytes(buffer, find_dict_overrides)?
@@ -69,3 +69,57 @@ _invoke_trampoline:
     stp     x2,     x3,     [x4]
 
     ret
+
+
+.global _invoke_on_stack
+_invoke_on_stack:
+    // x0 <- callback: extern "C" fn(*mut c_void)
+    // x1 <- data: *mut c_void
+    // x2 <- stack_ptr: *mut u8 (top of the new stack, 16-byte aligned, or null)
+    // x3 <- saved_sp: *mut *mut u8
+
+    stp     x29,    x30,    [sp, #-16]!     // Push all callee-saved registers. If the callback is
+    stp     x27,    x28,    [sp, #-16]!     //   aborted they will be restored from here by the
+    stp     x25,    x26,    [sp, #-16]!     //   landing pad.
+    stp     x23,    x24,    [sp, #-16]!
+    stp     x21,    x22,    [sp, #-16]!
+    stp     x19,    x20,    [sp, #-16]!
+    stp     d14,    d15,    [sp, #-16]!
+    stp     d12,    d13,    [sp, #-16]!
+    stp     d10,    d11,    [sp, #-16]!
+    stp     d8,     d9,     [sp, #-16]!
+
+    mov     x4,     sp
+    str     x4,     [x3]                    // Save the current stack pointer.
+    mov     x19,    x3                      // Keep `saved_sp` in a callee-saved register.
+
+    cbz     x2,     1f                      // If there's no new stack, keep using the current one.
+    mov     sp,     x2                      // Switch to the new stack.
+
+  1:
+    mov     x9,     x0
+    mov     x0,     x1
+    blr     x9                              // Call the callback.
+
+    ldr     x4,     [x19]
+    mov     sp,     x4                      // Switch back to the original stack.
+    mov     x0,     0                       // The callback returned normally.
+    b       2f
+
+.global _invoke_on_stack_abort
+_invoke_on_stack_abort:
+    // Entered from the signal handler with sp set to `saved_sp` and the signal number in x0.
+
+  2:
+    ldp     d8,     d9,     [sp],   16
+    ldp     d10,    d11,    [sp],   16
+    ldp     d12,    d13,    [sp],   16
+    ldp     d14,    d15,    [sp],   16
+    ldp     x19,    x20,    [sp],   16
+    ldp     x21,    x22,    [sp],   16
+    ldp     x23,    x24,    [sp],   16
+    ldp     x25,    x26,    [sp],   16
+    ldp     x27,    x28,    [sp],   16
+    ldp     x29,    x30,    [sp],   16
+
+    ret
@@ -70,3 +70,50 @@ _invoke_trampoline:
     mov     [rcx + 8],  rdx
 
     ret
+
+
+.global _invoke_on_stack
+_invoke_on_stack:
+    # rdi <- callback: extern "C" fn(*mut c_void)
+    # rsi <- data: *mut c_void
+    # rdx <- stack_ptr: *mut u8 (top of the new stack, 16-byte aligned, or null)
+    # rcx <- saved_sp: *mut *mut u8
+
+    push    rbp                     # Push all callee-saved registers. If the callback is aborted
+    push    rbx                     #   they will be restored from here by the landing pad.
+    push    r12
+    push    r13
+    push    r14
+    push    r15
+
+    mov     [rcx],  rsp             # Save the current stack pointer.
+    mov     rbx,    rcx             # Keep `saved_sp` in a callee-saved register.
+
+    sub     rsp,    8               # Align the stack (in case it's not switched).
+    test    rdx,    rdx             # Check if there's a new stack.
+    jz      1f                      # If there isn't, keep using the current one.
+    mov     rsp,    rdx             # Switch to the new stack.
+
+  1:
+    mov     rax,    rdi
+    mov     rdi,    rsi
+    call    rax                     # Call the callback.
+
+    mov     rsp,    [rbx]           # Switch back to the original stack.
+    xor     eax,    eax             # The callback returned normally.
+    jmp     2f
+
+.global _invoke_on_stack_abort
+_invoke_on_stack_abort:
+    # Entered from the signal handler with rsp set to `saved_sp` and the signal number in rdi.
+    mov     rax,    rdi
+
+  2:
+    pop     r15
+    pop     r14
+    pop     r13
+    pop     r12
+    pop     rbx
+    pop     rbp
+
+    ret
@@ -1,7 +1,8 @@
 use anyhow::Context;
 use cairo_lang_compiler::project::check_compiler_path;
 use cairo_native::{
-    context::NativeContext, module_to_object, object_to_shared_lib, utils::cairo_to_sierra,
+    context::NativeContext, module_to_objects, objects_to_shared_lib, options::CompilationOptions,
+    utils::cairo_to_sierra, TargetOptions,
 };
 use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
@@ -32,6 +33,22 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// Number of objects to optimize and emit in parallel.
+    #[arg(long, default_value_t = 1)]
+    codegen_units: usize,
+    /// Link the library with the built-in linker instead of the system's `ld` (glibc-based Linux
+    /// on x86-64 or AArch64 only).
+    #[arg(long)]
+    builtin_linker: bool,
+    /// The target triple to compile for, defaults to the host's.
+    #[arg(long)]
+    target: Option<String>,
+    /// The target CPU, defaults to the host's CPU when compiling for the host.
+    #[arg(long)]
+    target_cpu: Option<String>,
+    /// The target CPU features, like `+avx2,-sse4a`.
+    #[arg(long)]
+    target_features: Option<String>,
     /// The output path for the mlir, if none is passed, out.mlir will be the default.
     output_mlir: Option<PathBuf>,
     /// If a path is passed, a dynamic library will be compiled and saved at that path.
@@ -51,7 +68,20 @@ fn main() -> anyhow::Result<()> {
     // Check if args.path is a file or a directory.
     check_compiler_path(args.single_file, &args.path)?;
 
-    let native_context = NativeContext::new();
+    let target = TargetOptions {
+        triple: args.target,
+        cpu: args.target_cpu,
+        features: args.target_features,
+        ..Default::default()
+    };
+
+    let native_context = NativeContext::new()
+        .with_options(CompilationOptions {
+            codegen_units: args.codegen_units,
+            builtin_linker: args.builtin_linker,
+            ..Default::default()
+        })
+        .with_target(target.clone());
     let sierra_program = cairo_to_sierra(&args.path).unwrap();
 
     // Compile the sierra program into a MLIR module.
@@ -79,9 +109,15 @@ fn main() -> anyhow::Result<()> {
         })
     });
 
-    let object_data = module_to_object(native_module.module(), args.opt_level.into())
-        .context("Failed to convert module to object.")?;
-    object_to_shared_lib(&object_data, &output_lib).context("Failed to write shared library.")?;
+    let objects = module_to_objects(
+        native_module.module(),
+        args.opt_level.into(),
+        Some(&target),
+        native_context.options(),
+    )
+    .context("Failed to convert module to object.")?;
+    objects_to_shared_lib(&objects, &output_lib, native_context.options())
+        .context("Failed to write shared library.")?;
 
     Ok(())
 }
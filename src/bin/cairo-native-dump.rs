@@ -8,8 +8,11 @@ use cairo_lang_starknet::{
     compile::compile_contract_in_prepared_db, inline_macros::selector::SelectorMacro,
     plugin::StarkNetPlugin,
 };
-use cairo_native::context::NativeContext;
-use clap::Parser;
+use cairo_native::{
+    context::NativeContext, module::NativeModule, module_to_assembly, module_to_llvm_ir,
+    module_to_object_with_options, object_to_shared_lib, utils::SHARED_LIBRARY_EXT, OptLevel,
+};
+use clap::{Parser, ValueEnum};
 use melior::ir::operation::OperationPrintingFlags;
 use std::{
     ffi::OsStr,
@@ -34,22 +37,83 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let context = NativeContext::new();
     let program = load_program(Path::new(&args.input), args.starknet)?;
 
-    // Compile the program.
-    let module = context.compile(&program, false, Some(Default::default()))?;
+    // Compile the program, lowering it only if needed.
+    let unlowered_module = if args.emit.contains(&Emit::Mlir) {
+        Some(context.compile_unlowered(&program, false, Some(Default::default()))?)
+    } else {
+        None
+    };
+    let module = if args.emit.iter().any(|x| *x != Emit::Mlir) {
+        Some(context.compile(&program, false, Some(Default::default()))?)
+    } else {
+        None
+    };
+
+    let opt_level = OptLevel::from(args.opt_level);
+    let object = match &module {
+        Some(module) if args.emit.iter().any(|x| matches!(x, Emit::Obj | Emit::So)) => Some(
+            module_to_object_with_options(module.module(), opt_level, None, context.options())?,
+        ),
+        _ => None,
+    };
+
+    for &emit in &args.emit {
+        let lowered_module = || module.as_ref().expect("the module was lowered").module();
+        let output = match emit {
+            Emit::Mlir => {
+                print_module(unlowered_module.as_ref().expect("the module was compiled"))?
+                    .into_bytes()
+            }
+            Emit::MlirLowered => {
+                print_module(module.as_ref().expect("the module was lowered"))?.into_bytes()
+            }
+            Emit::LlvmIr => {
+                module_to_llvm_ir(lowered_module(), opt_level, None, context.options())?
+                    .into_bytes()
+            }
+            Emit::Asm => module_to_assembly(lowered_module(), opt_level, None, context.options())?
+                .into_bytes(),
+            Emit::Obj => object.clone().expect("the object was emitted"),
+            Emit::So => {
+                // The shared library can only be written into a file.
+                object_to_shared_lib(
+                    object.as_ref().expect("the object was emitted"),
+                    &output_path(&args, emit),
+                )?;
+                continue;
+            }
+        };
 
-    // Write the output.
-    let output_str = module
-        .module()
-        .as_operation()
-        .to_string_with_flags(OperationPrintingFlags::new().enable_debug_info(true, false))?;
-    match args.output {
-        CompilerOutput::Stdout => println!("{output_str}"),
-        CompilerOutput::Path(path) => fs::write(path, &output_str)?,
+        // Print to stdout only when it's the only output and it's textual.
+        match args.output {
+            CompilerOutput::Stdout if args.emit.len() == 1 && emit.is_textual() => {
+                println!("{}", String::from_utf8_lossy(&output))
+            }
+            _ => fs::write(output_path(&args, emit), &output)?,
+        }
     }
 
     Ok(())
 }
 
+fn print_module(module: &NativeModule) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(module
+        .module()
+        .as_operation()
+        .to_string_with_flags(OperationPrintingFlags::new().enable_debug_info(true, false))?)
+}
+
+/// The path of an output. When emitting many of them (or printing binary ones), every output is
+/// written next to the given path (or the input) with its own extension.
+fn output_path(args: &CmdLine, emit: Emit) -> PathBuf {
+    match &args.output {
+        CompilerOutput::Path(path) if args.emit.len() == 1 => path.clone(),
+        CompilerOutput::Path(path) => path.with_extension(emit.extension()),
+        CompilerOutput::Stdout => PathBuf::from(args.input.file_stem().unwrap_or_default())
+            .with_extension(emit.extension()),
+    }
+}
+
 fn load_program(path: &Path, is_contract: bool) -> Result<Program, Box<dyn std::error::Error>> {
     Ok(match path.extension().and_then(OsStr::to_str) {
         Some("cairo") if !is_contract => {
@@ -115,6 +179,14 @@ struct CmdLine {
     /// Compile a starknet contract
     #[clap(long)]
     starknet: bool,
+
+    /// The outputs to generate, separated by commas
+    #[clap(long, value_delimiter = ',', default_value = "mlir-lowered")]
+    emit: Vec<Emit>,
+
+    /// Optimization level used by the LLVM outputs, Valid: 0, 1, 2, 3
+    #[clap(short = 'O', long, default_value_t = 0)]
+    opt_level: u8,
 }
 
 #[derive(Clone, Debug)]
@@ -123,6 +195,39 @@ enum CompilerOutput {
     Path(PathBuf),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// The MLIR generated from the Sierra program, before running any pass
+    Mlir,
+    /// The MLIR lowered into the LLVM dialect
+    MlirLowered,
+    /// The LLVM IR, after running the LLVM passes
+    LlvmIr,
+    /// The target's assembly
+    Asm,
+    /// The object file
+    Obj,
+    /// The shared library
+    So,
+}
+
+impl Emit {
+    const fn is_textual(self) -> bool {
+        !matches!(self, Self::Obj | Self::So)
+    }
+
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Mlir => "mlir",
+            Self::MlirLowered => "lowered.mlir",
+            Self::LlvmIr => "ll",
+            Self::Asm => "s",
+            Self::Obj => "o",
+            Self::So => SHARED_LIBRARY_EXT,
+        }
+    }
+}
+
 fn parse_input(input: &str) -> Result<PathBuf, String> {
     Ok(match Path::new(input).extension().and_then(OsStr::to_str) {
         Some("cairo" | "sierra") => input.into(),
@@ -136,17 +241,8 @@ fn parse_input(input: &str) -> Result<PathBuf, String> {
 }
 
 fn parse_output(input: &str) -> Result<CompilerOutput, String> {
-    Ok(if input == "-" {
-        CompilerOutput::Stdout
-    } else {
-        CompilerOutput::Path(match Path::new(input).extension().and_then(OsStr::to_str) {
-            Some("mlir") => input.into(),
-            _ => {
-                return Err(
-                    "Output path expected to be `-` for stdout or have `mlir` as its extension."
-                        .to_string(),
-                )
-            }
-        })
+    Ok(match input {
+        "-" => CompilerOutput::Stdout,
+        _ => CompilerOutput::Path(input.into()),
     })
 }
@@ -5,14 +5,17 @@ use cairo_lang_compiler::{
 use cairo_lang_runner::short_string::as_cairo_short_string;
 use cairo_native::{
     context::NativeContext,
+    dump::DumpConfig,
     executor::{AotNativeExecutor, JitNativeExecutor},
     metadata::gas::{GasMetadata, MetadataComputationConfig},
     starknet_stub::StubSyscallHandler,
+    Value,
 };
 use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use starknet_types_core::felt::Felt;
+use std::{fs, path::PathBuf};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
-use utils::{find_function, result_to_runresult};
+use utils::{find_function, result_to_runresult, unwrap_return_value};
 
 mod utils;
 
@@ -22,6 +25,14 @@ enum RunMode {
     Jit,
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+enum OutputFormat {
+    /// The result as a message.
+    Text,
+    /// The return value, the remaining gas and the builtin stats as JSON.
+    Json,
+}
+
 /// Command line args parser.
 /// Exits with 1 if the compilation or run fails, otherwise 0.
 #[derive(Parser, Debug)]
@@ -44,6 +55,35 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// Dump the intermediate representations (and, in AOT mode, the object and a shared library
+    /// with debug info) into the given directory.
+    #[arg(long)]
+    dump_dir: Option<PathBuf>,
+    /// The arguments of the `main` function, serialized as felts the way Cairo's `Serde` does.
+    #[arg(long, num_args = 1.., allow_negative_numbers = true, value_parser = parse_felt)]
+    args: Vec<Felt>,
+    /// A JSON file with the arguments of the `main` function, as an array of `Value`s.
+    #[arg(long, conflicts_with = "args")]
+    args_file: Option<PathBuf>,
+    /// The format of the output.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+}
+
+/// Parse a felt, either in decimal (possibly negative) or hexadecimal (prefixed by `0x`).
+fn parse_felt(value: &str) -> Result<Felt, String> {
+    let (is_negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value),
+    };
+
+    let felt = match value.starts_with("0x") {
+        true => Felt::from_hex(value),
+        false => Felt::from_dec_str(value),
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(if is_negative { -felt } else { felt })
 }
 
 fn main() -> anyhow::Result<()> {
@@ -69,13 +109,23 @@ fn main() -> anyhow::Result<()> {
     )?
     .program;
 
-    let native_context = NativeContext::new();
+    let mut native_context = NativeContext::new();
+    if let Some(dump_dir) = args.dump_dir {
+        native_context = native_context.with_dump(DumpConfig::new(dump_dir));
+    }
 
     // Compile the sierra program into a MLIR module.
     let native_module = native_context
         .compile(&sierra_program, false, Some(Default::default()))
         .unwrap();
 
+    let func = find_function(&sierra_program, "::main")?;
+    let main_args = match &args.args_file {
+        Some(path) => serde_json::from_str::<Vec<Value>>(&fs::read_to_string(path)?)
+            .with_context(|| format!("invalid arguments in {}", path.display()))?,
+        None => Value::args_from_felts(native_module.program_registry(), &func.id, &args.args)?,
+    };
+
     let native_executor: Box<dyn Fn(_, _, _, &mut StubSyscallHandler) -> _> = match args.run_mode {
         RunMode::Aot => {
             let executor =
@@ -106,23 +156,32 @@ fn main() -> anyhow::Result<()> {
     let gas_metadata =
         GasMetadata::new(&sierra_program, Some(MetadataComputationConfig::default())).unwrap();
 
-    let func = find_function(&sierra_program, "::main")?;
-
     let initial_gas = gas_metadata
         .get_initial_available_gas(&func.id, args.available_gas)
         .with_context(|| "not enough gas to run")?;
 
     let mut syscall_handler = StubSyscallHandler::default();
 
-    let result = native_executor(&func.id, &[], Some(initial_gas), &mut syscall_handler)
-        .with_context(|| "Failed to run the function.")?;
+    let result = native_executor(
+        &func.id,
+        &main_args,
+        Some(initial_gas),
+        &mut syscall_handler,
+    )
+    .with_context(|| "Failed to run the function.")?;
+
+    if let OutputFormat::Json = args.output_format {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
 
     let run_result = result_to_runresult(&result)?;
 
     match run_result {
-        cairo_lang_runner::RunResultValue::Success(values) => {
-            println!("Run completed successfully, returning {values:?}")
-        }
+        cairo_lang_runner::RunResultValue::Success(_) => println!(
+            "Run completed successfully, returning {}",
+            unwrap_return_value(&result.return_value)
+        ),
         cairo_lang_runner::RunResultValue::Panic(values) => {
             print!("Run panicked with [");
             for value in &values {
@@ -9,38 +9,27 @@
 //! - Compiles the program and inserts the compiled program into the cache.
 //! - Executes the program.
 //!
-//! The cache is dropped at the end of the whole execution.
-//!
-//! For documentation on the specific cache used, see `NaiveAotCache`.
+//! The programs are cached on disk using a `DiskProgramCache`, so they're reused if the stress test
+//! is run again. The least recently used ones are evicted once the cache exceeds `--max-cache-size`.
 
 use cairo_lang_sierra::{
     ids::FunctionId,
     program::{GenericArg, Program},
-    program_registry::ProgramRegistry,
 };
 use cairo_lang_starknet::compile::compile_path;
 use cairo_native::{
-    context::NativeContext,
-    executor::AotNativeExecutor,
-    metadata::gas::GasMetadata,
-    module_to_object, object_to_shared_lib,
-    starknet::DummySyscallHandler,
-    utils::{find_entry_point_by_idx, SHARED_LIBRARY_EXT},
-    OptLevel,
+    cache::DiskProgramCache, context::NativeContext, starknet::DummySyscallHandler,
+    utils::find_entry_point_by_idx, OptLevel,
 };
 use clap::Parser;
-use libloading::Library;
 use num_bigint::BigInt;
 use stats_alloc::{Region, StatsAlloc, INSTRUMENTED_SYSTEM};
 use std::{
     alloc::System,
-    collections::HashMap,
-    fmt::{Debug, Display},
-    fs::{self, create_dir_all, read_dir, OpenOptions},
-    hash::Hash,
+    fmt::Debug,
+    fs::{self, read_dir, OpenOptions},
     io,
     path::{Path, PathBuf},
-    sync::Arc,
     time::Instant,
 };
 use tracing::{debug, info, info_span, warn};
@@ -68,6 +57,9 @@ struct StressTestCommand {
     /// Output file for JSON formatted logs
     #[arg(short, long)]
     output: Option<PathBuf>,
+    /// Maximum size of the AOT cache in bytes, unbounded if not specified
+    #[arg(long)]
+    max_cache_size: Option<u64>,
 }
 
 fn main() {
@@ -76,7 +68,7 @@ fn main() {
     set_global_subscriber(&args);
 
     if !directory_is_empty(AOT_CACHE_DIR).expect("failed to open aot cache dir") {
-        warn!("{AOT_CACHE_DIR} directory is not empty")
+        warn!("{AOT_CACHE_DIR} directory is not empty, previously compiled programs will be reused")
     }
 
     // Generate initial program
@@ -93,7 +85,8 @@ fn main() {
 
     // Initialize context and cache
     let native_context = NativeContext::new();
-    let mut cache = NaiveAotCache::new(&native_context);
+    let cache = DiskProgramCache::new(&native_context, AOT_CACHE_DIR, args.max_cache_size)
+        .expect("failed to open aot cache dir");
 
     info!("starting stress test");
 
@@ -103,19 +96,15 @@ fn main() {
         let before_round = Instant::now();
 
         let program = modify_starknet_contract(program.clone(), UNIQUE_CONTRACT_VALUE, round);
-        // TODO: use the program hash instead of round number.
-        let hash = round;
-
-        debug!(hash, "obtained test program");
 
-        if cache.get(&hash).is_some() {
-            panic!("all program keys should be different")
-        }
+        debug!("obtained test program");
 
-        // Compiles and caches the program
+        // Compiles and caches the program, unless it was compiled by a previous run
         let executor = {
             let before_compile = Instant::now();
-            let executor = cache.compile_and_insert(hash, &program, cairo_native::OptLevel::None);
+            let executor = cache
+                .get_or_compile(&program, OptLevel::None)
+                .expect("failed to compile program");
             let elapsed = before_compile.elapsed().as_millis();
             debug!(time = elapsed, "compiled test program");
             executor
@@ -153,7 +142,12 @@ fn main() {
     }
 
     let elapsed = before_stress_test.elapsed().as_millis();
-    info!(time = elapsed, "finished stress test");
+    info!(
+        time = elapsed,
+        cache_hits = cache.hits(),
+        cache_misses = cache.misses(),
+        "finished stress test"
+    );
 }
 
 /// Generate a dummy starknet contract
@@ -234,93 +228,6 @@ fn modify_starknet_contract(mut program: Program, old_value: u32, new_value: u32
     program
 }
 
-/// A naive implementation of an AOT Program Cache
-///
-/// For each contract:
-/// - Compiles it to a shared library and stores it on disk (`AOT_CACHE_DIR`)
-/// - Loads the shared library into an executor and stores it on a hashmap with the given key.
-///
-/// The shared libraries are unloaded when the executor gets drop, AKA: when the cache gets dropped.
-/// The shared libraries on disk are never deleted
-///
-/// Possible improvements include:
-/// - Keeping only some executors on memory, while storing the remaining compiled shared libraries only on disk.
-/// - When restarting the program, reutilize already compiled programs from `AOT_CACHE_DIR`
-struct NaiveAotCache<'a, K>
-where
-    K: PartialEq + Eq + Hash + Display,
-{
-    context: &'a NativeContext,
-    cache: HashMap<K, Arc<AotNativeExecutor>>,
-}
-
-impl<'a, K> NaiveAotCache<'a, K>
-where
-    K: PartialEq + Eq + Hash + Display,
-{
-    pub fn new(context: &'a NativeContext) -> Self {
-        Self {
-            context,
-            cache: Default::default(),
-        }
-    }
-
-    pub fn get(&self, key: &K) -> Option<Arc<AotNativeExecutor>> {
-        self.cache.get(key).cloned()
-    }
-
-    /// Compiles and inserts a given program into the cache
-    ///
-    /// The dynamic library is stored in `AOT_CACHE_DIR` directory
-    pub fn compile_and_insert(
-        &mut self,
-        key: K,
-        program: &Program,
-        opt_level: OptLevel,
-    ) -> Arc<AotNativeExecutor> {
-        let native_module = self
-            .context
-            .compile(program, false, Some(Default::default()))
-            .expect("failed to compile program");
-
-        let registry = ProgramRegistry::new(program).expect("failed to get program registry");
-        let metadata = native_module
-            .metadata()
-            .get::<GasMetadata>()
-            .cloned()
-            .expect("module should have gas metadata");
-
-        let shared_library = {
-            let object_data = module_to_object(native_module.module(), opt_level)
-                .expect("failed to convert MLIR to object");
-
-            let shared_library_dir = Path::new(AOT_CACHE_DIR);
-            create_dir_all(shared_library_dir).expect("failed to create shared library directory");
-            let shared_library_name = format!("lib{key}{SHARED_LIBRARY_EXT}");
-            let shared_library_path = shared_library_dir.join(shared_library_name);
-
-            object_to_shared_lib(&object_data, &shared_library_path)
-                .expect("failed to link object into shared library");
-
-            unsafe {
-                Library::new(shared_library_path).expect("failed to load dynamic shared library")
-            }
-        };
-
-        let executor = AotNativeExecutor::new(
-            shared_library,
-            registry,
-            metadata,
-            native_module.metadata().get().cloned().unwrap_or_default(),
-        );
-        let executor = Arc::new(executor);
-
-        self.cache.insert(key, executor.clone());
-
-        executor
-    }
-}
-
 /// Returns the size of a directory in bytes
 fn directory_get_size(path: impl AsRef<Path>) -> io::Result<u64> {
     let mut dir = read_dir(path)?;
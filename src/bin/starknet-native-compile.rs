@@ -4,8 +4,34 @@ use std::path::PathBuf;
 use cairo_lang_sierra::program::Program;
 use cairo_lang_starknet_classes::compiler_version::VersionId;
 use cairo_lang_starknet_classes::contract_class::ContractClass;
-use cairo_native::executor::AotContractExecutor;
-use clap::Parser;
+use cairo_native::{executor::AotContractExecutor, CpuBaseline, TargetOptions};
+use clap::{Parser, ValueEnum};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Baseline {
+    #[value(name = "x86-64")]
+    X86_64,
+    #[value(name = "x86-64-v2")]
+    X86_64V2,
+    #[value(name = "x86-64-v3")]
+    X86_64V3,
+    #[value(name = "x86-64-v4")]
+    X86_64V4,
+    #[value(name = "armv8-a")]
+    Armv8A,
+}
+
+impl From<Baseline> for CpuBaseline {
+    fn from(value: Baseline) -> Self {
+        match value {
+            Baseline::X86_64 => CpuBaseline::X86_64,
+            Baseline::X86_64V2 => CpuBaseline::X86_64V2,
+            Baseline::X86_64V3 => CpuBaseline::X86_64V3,
+            Baseline::X86_64V4 => CpuBaseline::X86_64V4,
+            Baseline::Armv8A => CpuBaseline::Armv8A,
+        }
+    }
+}
 
 /// Given a Sierra file (as saved in Starknet's contract tree), extracts the sierra_program from
 /// felts into readable Sierra code, compiles it to native, and saves the result to the given output
@@ -18,6 +44,10 @@ struct Args {
     /// Optimization level, Valid: 0, 1, 2, 3. Values higher than 3 are considered as 3.
     #[arg(short = 'O', long, default_value_t = 0)]
     opt_level: u8,
+    /// Compile for a portable CPU baseline instead of the host's CPU, so that the library can be
+    /// shared with other machines.
+    #[arg(long)]
+    portable: Option<Baseline>,
     /// The output file path.
     output: PathBuf,
 }
@@ -27,6 +57,18 @@ fn main() -> anyhow::Result<()> {
     let (contract_class, sierra_program, sierra_version) =
         load_sierra_program_from_file(&args.path)?;
 
+    if let Some(baseline) = args.portable {
+        return AotContractExecutor::compile_into(
+            &sierra_program,
+            &contract_class.entry_points_by_type,
+            sierra_version,
+            args.output,
+            args.opt_level.into(),
+            &TargetOptions::portable(baseline.into())?,
+        )
+        .context("Error compiling Sierra program.");
+    }
+
     AotContractExecutor::new_into(
         &sierra_program,
         &contract_class.entry_points_by_type,
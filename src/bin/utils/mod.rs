@@ -111,6 +111,26 @@ pub fn result_to_runresult(result: &ExecutionResult) -> anyhow::Result<RunResult
     })
 }
 
+/// Return the value returned by a successful run, without the `PanicResult` it's wrapped in when
+/// the function can panic.
+pub fn unwrap_return_value(value: &Value) -> &Value {
+    match value {
+        Value::Enum {
+            tag: 0,
+            value,
+            debug_name: Some(debug_name),
+        } if debug_name.starts_with("core::panics::PanicResult::")
+            || debug_name.starts_with("Enum<ut@core::panics::PanicResult::") =>
+        {
+            match &**value {
+                Value::Struct { fields, .. } if fields.len() == 1 => &fields[0],
+                value => value,
+            }
+        }
+        value => value,
+    }
+}
+
 /// Convert a JIT value to a felt.
 fn jitvalue_to_felt(value: &Value) -> Vec<Felt> {
     let mut felts = Vec::new();
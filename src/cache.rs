@@ -1,8 +1,12 @@
-pub use self::{aot::AotProgramCache, jit::JitProgramCache};
+pub use self::{
+    aot::AotProgramCache, disk::DiskProgramCache, jit::JitProgramCache, shared::SharedProgramCache,
+};
 use std::hash::Hash;
 
 pub mod aot;
+pub mod disk;
 pub mod jit;
+pub mod shared;
 
 #[derive(Debug)]
 pub enum ProgramCache<'a, K>
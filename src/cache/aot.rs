@@ -1,10 +1,6 @@
-use crate::error::{Error, Result};
-use crate::{
-    context::NativeContext, executor::AotNativeExecutor, module::NativeModule,
-    utils::SHARED_LIBRARY_EXT, OptLevel,
-};
+use crate::error::Result;
+use crate::{context::NativeContext, executor::AotNativeExecutor, OptLevel};
 use cairo_lang_sierra::program::Program;
-use libloading::Library;
 use std::{
     collections::HashMap,
     fmt::{self, Debug},
@@ -41,32 +37,10 @@ where
         program: &Program,
         opt_level: OptLevel,
     ) -> Result<Arc<AotNativeExecutor>> {
-        let NativeModule {
-            module,
-            registry,
-            mut metadata,
-        } = self
+        let module = self
             .context
             .compile(program, false, Some(Default::default()))?;
-
-        // Compile module into an object.
-        let object_data = crate::ffi::module_to_object(&module, opt_level)?;
-
-        // Compile object into a shared library.
-        let shared_library_path = tempfile::Builder::new()
-            .prefix("lib")
-            .suffix(SHARED_LIBRARY_EXT)
-            .tempfile()?
-            .into_temp_path();
-        crate::ffi::object_to_shared_lib(&object_data, &shared_library_path)?;
-
-        let shared_library = unsafe { Library::new(shared_library_path)? };
-        let executor = AotNativeExecutor::new(
-            shared_library,
-            registry,
-            metadata.remove().ok_or(Error::MissingMetadata)?,
-            metadata.remove().unwrap_or_default(),
-        );
+        let executor = AotNativeExecutor::from_native_module(module, opt_level)?;
 
         let executor = Arc::new(executor);
         self.cache.insert(key, executor.clone());
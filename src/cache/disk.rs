@@ -0,0 +1,389 @@
+//! # Persistent AOT cache
+//!
+//! Stores the compiled programs on disk, so that they can be reused across restarts and shared
+//! between processes using the same directory.
+//!
+//! Every artifact is addressed by a hash of the Sierra program and everything else affecting the
+//! generated code (the optimization level, the compilation options, the target and the compiler and runtime versions),
+//! therefore an artifact never has to be invalidated. The least recently used ones are deleted
+//! once the cache grows over its byte budget.
+
+use crate::{
+    context::NativeContext,
+    error::Result,
+    executor::AotNativeExecutor,
+    ffi::get_target_triple,
+    metadata::runtime_bindings::RUNTIME_ABI_VERSION,
+    utils::{program_hash, SHARED_LIBRARY_EXT},
+    OptLevel,
+};
+use cairo_lang_sierra::program::Program;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Debug},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+use tracing::debug;
+
+/// The prefix of the files being written, which are ignored until they're complete.
+const TEMP_PREFIX: &str = ".tmp-";
+
+pub struct DiskProgramCache<'a> {
+    context: &'a NativeContext,
+    path: PathBuf,
+    max_size: Option<u64>,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<'a> DiskProgramCache<'a> {
+    /// Open (or create) a cache in the given directory.
+    ///
+    /// When `max_size` is given, the least recently used artifacts are evicted whenever the total
+    /// size of the cache exceeds that many bytes.
+    pub fn new(
+        context: &'a NativeContext,
+        path: impl Into<PathBuf>,
+        max_size: Option<u64>,
+    ) -> Result<Self> {
+        let path = path.into();
+        fs::create_dir_all(&path)?;
+
+        Ok(Self {
+            context,
+            path,
+            max_size,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    /// The directory where the artifacts are stored.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// How many lookups found the program already compiled.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// How many lookups didn't find the program.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Load a previously compiled program, if present.
+    pub fn get(&self, program: &Program, opt_level: OptLevel) -> Result<Option<AotNativeExecutor>> {
+        let key = cache_key(program, opt_level, self.context)?;
+        let executor = self.load(&key);
+
+        match executor {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        Ok(executor)
+    }
+
+    /// Load a previously compiled program, or compile and store it if not present.
+    pub fn get_or_compile(
+        &self,
+        program: &Program,
+        opt_level: OptLevel,
+    ) -> Result<AotNativeExecutor> {
+        let key = cache_key(program, opt_level, self.context)?;
+        if let Some(executor) = self.load(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(executor);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let module = self
+            .context
+            .compile(program, false, Some(Default::default()))?;
+        let executor = AotNativeExecutor::from_native_module(module, opt_level)?;
+
+        self.store(&key, program, &executor)?;
+        self.evict(&key)?;
+
+        Ok(executor)
+    }
+
+    /// The total size of the artifacts, in bytes.
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|x| x.size).sum())
+    }
+
+    fn library_path(&self, key: &str) -> PathBuf {
+        self.path.join(format!("{key}.{SHARED_LIBRARY_EXT}"))
+    }
+
+    fn load(&self, key: &str) -> Option<AotNativeExecutor> {
+        let path = self.library_path(key);
+        if !path.exists() {
+            return None;
+        }
+
+        match AotNativeExecutor::load(&path) {
+            Ok(executor) => {
+                // The modification time is used to track the last use.
+                if let Err(error) = File::options()
+                    .write(true)
+                    .open(&path)
+                    .and_then(|x| x.set_modified(SystemTime::now()))
+                {
+                    debug!(%error, "failed to update the artifact's last use");
+                }
+                Some(executor)
+            }
+            // The artifact may have been evicted (or be incomplete) in the meantime, in which case
+            // it's just recompiled.
+            Err(error) => {
+                debug!(%error, key, "failed to load a cached artifact");
+                None
+            }
+        }
+    }
+
+    /// Save an artifact atomically, so that other processes never see it partially written.
+    fn store(&self, key: &str, program: &Program, executor: &AotNativeExecutor) -> Result<()> {
+        let temp_path = tempfile::Builder::new()
+            .prefix(TEMP_PREFIX)
+            .suffix(&format!(".{SHARED_LIBRARY_EXT}"))
+            .tempfile_in(&self.path)?
+            .into_temp_path();
+
+        let result = executor.save(&temp_path, program).and_then(|()| {
+            // The library is renamed last, since it's what marks the artifact as present.
+            fs::rename(
+                temp_path.with_extension("json"),
+                self.library_path(key).with_extension("json"),
+            )?;
+            fs::rename(&temp_path, self.library_path(key))?;
+            Ok(())
+        });
+
+        if result.is_err() {
+            let _ = fs::remove_file(temp_path.with_extension("json"));
+        }
+        result
+    }
+
+    /// Delete the least recently used artifacts until the cache fits in its budget. The artifact
+    /// identified by `keep` is never deleted.
+    fn evict(&self, keep: &str) -> Result<()> {
+        let Some(max_size) = self.max_size else {
+            return Ok(());
+        };
+
+        let mut entries = self.entries()?;
+        let mut size = entries.iter().map(|x| x.size).sum::<u64>();
+        entries.sort_by_key(|x| x.last_use);
+
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+            if entry.key == keep {
+                continue;
+            }
+
+            debug!(
+                key = %entry.key,
+                size = entry.size,
+                "evicting a cached artifact"
+            );
+            for path in [
+                self.library_path(&entry.key),
+                self.library_path(&entry.key).with_extension("json"),
+            ] {
+                match fs::remove_file(path) {
+                    // Another process may have evicted it already.
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                        return Err(error.into())
+                    }
+                    _ => {}
+                }
+            }
+            size -= entry.size;
+        }
+
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.path)? {
+            let path = dir_entry?.path();
+            let Some(key) = path
+                .file_name()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.strip_suffix(&format!(".{SHARED_LIBRARY_EXT}")))
+                .filter(|x| !x.starts_with(TEMP_PREFIX))
+            else {
+                continue;
+            };
+
+            // Either file may disappear at any point if another process evicts the artifact.
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            let json_size = fs::metadata(path.with_extension("json")).map_or(0, |x| x.len());
+
+            entries.push(Entry {
+                key: key.to_string(),
+                size: metadata.len() + json_size,
+                last_use: metadata.modified()?,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+impl Debug for DiskProgramCache<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DiskProgramCache")
+            .field("path", &self.path)
+            .field("max_size", &self.max_size)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Entry {
+    key: String,
+    size: u64,
+    last_use: SystemTime,
+}
+
+/// The hash which identifies an artifact.
+fn cache_key(program: &Program, opt_level: OptLevel, context: &NativeContext) -> Result<String> {
+    // The triple is always the host's, but the CPU and its features may not be.
+    let target = context.target();
+    let target_cpu = target.target_cpu();
+
+    let mut hasher = Sha256::new();
+    for part in [
+        program_hash(program)?,
+        format!("{opt_level:?}"),
+        serde_json::to_string(context.options())?,
+        env!("CARGO_PKG_VERSION").to_string(),
+        RUNTIME_ABI_VERSION.to_string(),
+        get_target_triple(target),
+        format!("{:?}", target.reloc_model),
+        format!("{:?}", target.code_model),
+        target_cpu.name,
+        target_cpu.features,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::test::load_cairo, values::Value, CpuBaseline, TargetOptions};
+    use starknet_types_core::felt::Felt;
+
+    fn run(executor: &AotNativeExecutor, program: &Program) -> Value {
+        let function_id = &program.funcs.first().expect("should have a function").id;
+        executor
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run")
+            .return_value
+    }
+
+    #[test]
+    fn test_disk_cache_reuse() {
+        let native_context = NativeContext::new();
+        let dir = tempfile::tempdir().unwrap();
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        let cache = DiskProgramCache::new(&native_context, dir.path(), None).unwrap();
+        assert!(cache.get(&program, OptLevel::None).unwrap().is_none());
+        let executor = cache.get_or_compile(&program, OptLevel::None).unwrap();
+        assert_eq!(run(&executor, &program), Value::Felt252(Felt::from(42)));
+        assert_eq!((cache.hits(), cache.misses()), (0, 2));
+
+        // A new cache (as if the process was restarted) reuses the artifact.
+        let cache = DiskProgramCache::new(&native_context, dir.path(), None).unwrap();
+        let executor = cache.get_or_compile(&program, OptLevel::None).unwrap();
+        assert_eq!(run(&executor, &program), Value::Felt252(Felt::from(42)));
+        assert_eq!((cache.hits(), cache.misses()), (1, 0));
+
+        // Other compile options are different artifacts.
+        assert!(cache.get(&program, OptLevel::Default).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_disk_cache_key_target() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+        let baseline = if cfg!(target_arch = "aarch64") {
+            CpuBaseline::Armv8A
+        } else {
+            CpuBaseline::X86_64
+        };
+
+        // Code generated for the host's CPU can't be shared with a portable build.
+        let native_context = NativeContext::new();
+        let portable_context =
+            NativeContext::new().with_target(TargetOptions::portable(baseline).unwrap());
+        assert_ne!(
+            cache_key(&program, OptLevel::None, &native_context).unwrap(),
+            cache_key(&program, OptLevel::None, &portable_context).unwrap()
+        );
+        assert_eq!(
+            cache_key(&program, OptLevel::None, &native_context).unwrap(),
+            cache_key(&program, OptLevel::None, &NativeContext::new()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_disk_cache_eviction() {
+        let native_context = NativeContext::new();
+        let dir = tempfile::tempdir().unwrap();
+
+        let (_, program_a) = load_cairo! {
+            fn run_test() -> felt252 {
+                1
+            }
+        };
+        let (_, program_b) = load_cairo! {
+            fn run_test() -> felt252 {
+                2
+            }
+        };
+
+        // A budget which only fits a single artifact.
+        let cache = DiskProgramCache::new(&native_context, dir.path(), Some(1)).unwrap();
+        cache.get_or_compile(&program_a, OptLevel::None).unwrap();
+        let size = cache.size().unwrap();
+        assert!(size > 0);
+
+        let executor = cache.get_or_compile(&program_b, OptLevel::None).unwrap();
+        assert_eq!(run(&executor, &program_b), Value::Felt252(Felt::from(2)));
+        assert!(cache.get(&program_a, OptLevel::None).unwrap().is_none());
+        assert!(cache.get(&program_b, OptLevel::None).unwrap().is_some());
+    }
+}
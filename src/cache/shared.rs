@@ -0,0 +1,327 @@
+use crate::error::Result;
+use crate::{context::NativeContext, executor::AotNativeExecutor, OptLevel};
+use cairo_lang_sierra::program::Program;
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+};
+
+/// A thread-safe cache of AOT executors.
+///
+/// Programs are compiled the first time they're requested. When many threads request the same
+/// program at once, only one of them compiles it while the others wait for its result.
+///
+/// The cache may be limited to a number of loaded executors, in which case the least recently used
+/// ones are evicted. Evicted executors remain valid while they're still referenced elsewhere.
+pub struct SharedProgramCache<'a, K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    context: &'a NativeContext,
+    opt_level: OptLevel,
+    capacity: Option<usize>,
+
+    state: Mutex<State<K>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct State<K> {
+    entries: HashMap<K, Entry>,
+    /// A counter used to track the last use of every entry.
+    tick: u64,
+}
+
+enum Entry {
+    Ready {
+        executor: Arc<AotNativeExecutor>,
+        last_use: u64,
+    },
+    Compiling(Arc<Compilation>),
+}
+
+/// An in-flight compilation, which other threads can wait for.
+#[derive(Default)]
+struct Compilation {
+    done: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Compilation {
+    fn wait(&self) {
+        let mut done = self.done.lock().unwrap_or_else(|e| e.into_inner());
+        while !*done {
+            done = self.condvar.wait(done).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl<'a, K> SharedProgramCache<'a, K>
+where
+    K: PartialEq + Eq + Hash + Clone,
+{
+    /// Create a cache which compiles the programs using the given optimization level.
+    ///
+    /// When `capacity` is given, at most that many executors are kept loaded.
+    pub fn new(context: &'a NativeContext, opt_level: OptLevel, capacity: Option<usize>) -> Self {
+        Self {
+            context,
+            opt_level,
+            capacity,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                tick: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// How many lookups found the program already compiled, including those which waited for
+    /// another thread to compile it.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// How many lookups had to compile the program.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// The number of loaded executors.
+    pub fn len(&self) -> usize {
+        self.state()
+            .entries
+            .values()
+            .filter(|x| matches!(x, Entry::Ready { .. }))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the executor for `key`, if it's loaded.
+    pub fn get(&self, key: &K) -> Option<Arc<AotNativeExecutor>> {
+        let mut state = self.state();
+        state.tick += 1;
+        let tick = state.tick;
+
+        match state.entries.get_mut(key) {
+            Some(Entry::Ready { executor, last_use }) => {
+                *last_use = tick;
+                Some(executor.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the executor for `key`, compiling `program` if it isn't loaded.
+    ///
+    /// If another thread is already compiling it, waits for it to finish instead. Should that
+    /// compilation fail, the error is returned only to the thread which compiled it while the
+    /// waiting ones try again.
+    pub fn get_or_compile(&self, key: K, program: &Program) -> Result<Arc<AotNativeExecutor>> {
+        let compilation = loop {
+            let compilation = {
+                let mut state = self.state();
+                state.tick += 1;
+                let tick = state.tick;
+
+                match state.entries.get_mut(&key) {
+                    Some(Entry::Ready { executor, last_use }) => {
+                        *last_use = tick;
+                        self.hits.fetch_add(1, Ordering::Relaxed);
+                        return Ok(executor.clone());
+                    }
+                    Some(Entry::Compiling(compilation)) => compilation.clone(),
+                    None => {
+                        let compilation = Arc::<Compilation>::default();
+                        state
+                            .entries
+                            .insert(key.clone(), Entry::Compiling(compilation.clone()));
+                        break compilation;
+                    }
+                }
+            };
+
+            compilation.wait();
+        };
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Wakes up the waiting threads even if the compilation fails or panics.
+        let _guard = CompilationGuard {
+            cache: self,
+            key: &key,
+            compilation,
+        };
+
+        let module = self
+            .context
+            .compile(program, false, Some(Default::default()))?;
+        let executor = Arc::new(AotNativeExecutor::from_native_module(
+            module,
+            self.opt_level,
+        )?);
+
+        let mut state = self.state();
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(
+            key.clone(),
+            Entry::Ready {
+                executor: executor.clone(),
+                last_use: tick,
+            },
+        );
+        self.evict(&mut state);
+
+        Ok(executor)
+    }
+
+    /// Unload the least recently used executors until the cache fits in its capacity.
+    fn evict(&self, state: &mut State<K>) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+
+        let mut ready = state
+            .entries
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                Entry::Ready { last_use, .. } => Some((*last_use, key.clone())),
+                Entry::Compiling(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if ready.len() <= capacity {
+            return;
+        }
+
+        ready.sort_unstable_by_key(|(last_use, _)| *last_use);
+        for (_, key) in &ready[..ready.len() - capacity] {
+            state.entries.remove(key);
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<K>> {
+        // The state is never left inconsistent, so poisoning can be ignored.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<K> Debug for SharedProgramCache<'_, K>
+where
+    K: PartialEq + Eq + Hash,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SharedProgramCache")
+    }
+}
+
+struct CompilationGuard<'c, 'a, K>
+where
+    K: PartialEq + Eq + Hash + Clone,
+{
+    cache: &'c SharedProgramCache<'a, K>,
+    key: &'c K,
+    compilation: Arc<Compilation>,
+}
+
+impl<K> Drop for CompilationGuard<'_, '_, K>
+where
+    K: PartialEq + Eq + Hash + Clone,
+{
+    fn drop(&mut self) {
+        {
+            // If the compilation didn't finish, let the next thread try again.
+            let mut state = self.cache.state();
+            if matches!(
+                state.entries.get(self.key),
+                Some(Entry::Compiling(x)) if Arc::ptr_eq(x, &self.compilation)
+            ) {
+                state.entries.remove(self.key);
+            }
+        }
+
+        *self
+            .compilation
+            .done
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = true;
+        self.compilation.condvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{utils::test::load_cairo, values::Value};
+    use starknet_types_core::felt::Felt;
+    use std::thread;
+
+    #[test]
+    fn test_shared_cache_deduplicates() {
+        let native_context = NativeContext::new();
+        let cache = SharedProgramCache::new(&native_context, OptLevel::None, None);
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        let executors = thread::scope(|s| {
+            let handles = (0..8)
+                .map(|_| s.spawn(|| cache.get_or_compile(0, &program).unwrap()))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|x| x.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // The program was compiled only once.
+        assert_eq!((cache.hits(), cache.misses()), (7, 1));
+        assert!(executors.iter().all(|x| Arc::ptr_eq(x, &executors[0])));
+
+        let function_id = &program.funcs.first().expect("should have a function").id;
+        let res = executors[0]
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run");
+        assert_eq!(res.return_value, Value::Felt252(Felt::from(42)));
+    }
+
+    #[test]
+    fn test_shared_cache_eviction() {
+        let native_context = NativeContext::new();
+        let cache = SharedProgramCache::new(&native_context, OptLevel::None, Some(2));
+
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        cache.get_or_compile(0, &program).unwrap();
+        let evicted = cache.get_or_compile(1, &program).unwrap();
+        // Makes `1` the least recently used.
+        cache.get(&0).unwrap();
+
+        cache.get_or_compile(2, &program).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&0).is_some());
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&2).is_some());
+
+        // Evicted executors can still be used.
+        let function_id = &program.funcs.first().expect("should have a function").id;
+        evicted
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .expect("should run");
+    }
+}
@@ -46,8 +46,10 @@
 
 use crate::{
     debug::libfunc_to_name,
+    dump::{sierra_source, DumpConfig},
     error::{panic::ToNativeAssertError, Error},
     libfuncs::{BranchArg, LibfuncBuilder, LibfuncHelper},
+    limits::{self, ResourceGuard},
     metadata::{
         gas::{GasCost, GasMetadata},
         tail_recursion::TailRecursionMeta,
@@ -130,12 +132,6 @@ pub fn compile(
     di_compile_unit_id: Attribute,
     ignore_debug_names: bool,
 ) -> Result<(), Error> {
-    if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-        if x == "1" || x == "true" {
-            std::fs::write("program.sierra", program.to_string())?;
-        }
-    }
-
     // Sierra programs have the following structure:
     //   1. Type declarations, one per line.
     //   2. Libfunc declarations, one per line.
@@ -145,6 +141,8 @@ pub fn compile(
     let num_types = program.type_declarations.len() + 1;
     let n_libfuncs = program.libfunc_declarations.len() + 1;
     let sierra_stmt_start_offset = num_types + n_libfuncs + 1;
+    // The locations refer to the dumped program when dumping, so that debuggers can find it.
+    let (source_file, source_dir) = sierra_source(metadata.get::<DumpConfig>());
 
     for function in &program.funcs {
         tracing::info!("Compiling function `{}`.", function.id);
@@ -156,9 +154,12 @@ pub fn compile(
             &program.statements,
             metadata,
             di_compile_unit_id,
+            &source_file,
+            &source_dir,
             sierra_stmt_start_offset,
             ignore_debug_names,
         )?;
+        limits::check(metadata.get::<ResourceGuard>())?;
     }
 
     tracing::info!("The program was compiled successfully.");
@@ -182,12 +183,14 @@ fn compile_func(
     statements: &[Statement],
     metadata: &mut MetadataStorage,
     di_compile_unit_id: Attribute,
+    source_file: &str,
+    source_dir: &str,
     sierra_stmt_start_offset: usize,
     ignore_debug_names: bool,
 ) -> Result<(), Error> {
     let fn_location = Location::new(
         context,
-        "program.sierra",
+        source_file,
         sierra_stmt_start_offset + function.entry_point.0,
         0,
     );
@@ -295,8 +298,8 @@ fn compile_func(
         // we are using our own bindings to the C++ API.
         let file_attr = Attribute::from_raw(mlirLLVMDIFileAttrGet(
             context.to_raw(),
-            StringAttribute::new(context, "program.sierra").to_raw(),
-            StringAttribute::new(context, ".").to_raw(),
+            StringAttribute::new(context, source_file).to_raw(),
+            StringAttribute::new(context, source_dir).to_raw(),
         ));
         let compile_unit = {
             Attribute::from_raw(mlirLLVMDICompileUnitAttrGet(
@@ -363,6 +366,7 @@ fn compile_func(
         function,
         statements,
         metadata,
+        source_file,
         sierra_stmt_start_offset,
     )?;
 
@@ -375,7 +379,7 @@ fn compile_func(
                 *ty,
                 Location::new(
                     context,
-                    "program.sierra",
+                    source_file,
                     sierra_stmt_start_offset + function.entry_point.0,
                     0,
                 ),
@@ -393,7 +397,7 @@ fn compile_func(
             let type_info = registry.get_type(&param.ty)?;
             let location = Location::new(
                 context,
-                "program.sierra",
+                source_file,
                 sierra_stmt_start_offset + function.entry_point.0,
                 0,
             );
@@ -433,7 +437,7 @@ fn compile_func(
         {
             Location::new(
                 context,
-                "program.sierra",
+                source_file,
                 sierra_stmt_start_offset + function.entry_point.0,
                 0,
             )
@@ -496,7 +500,7 @@ fn compile_func(
 
                     let location = Location::new(
                         context,
-                        "program.sierra",
+                        source_file,
                         sierra_stmt_start_offset + statement_idx.0,
                         0,
                     );
@@ -642,7 +646,7 @@ fn compile_func(
                         &format!("return(stmt_idx={})", statement_idx),
                         Location::new(
                             context,
-                            "program.sierra",
+                            source_file,
                             sierra_stmt_start_offset + statement_idx.0,
                             0,
                         ),
@@ -666,7 +670,7 @@ fn compile_func(
                                     &format!("return(stmt_idx={}, tail_recursion)", statement_idx),
                                     Location::new(
                                         context,
-                                        "program.sierra",
+                                        source_file,
                                         sierra_stmt_start_offset + statement_idx.0,
                                         0,
                                     ),
@@ -919,7 +923,7 @@ fn compile_func(
             context,
             &[Location::new(
                 context,
-                "program.sierra",
+                source_file,
                 sierra_stmt_start_offset + function.entry_point.0,
                 0,
             )],
@@ -936,7 +940,7 @@ fn compile_func(
         &return_types,
         Location::new(
             context,
-            "program.sierra",
+            source_file,
             sierra_stmt_start_offset + function.entry_point.0,
             0,
         ),
@@ -955,6 +959,7 @@ fn generate_function_structure<'c, 'a>(
     function: &Function,
     statements: &[Statement],
     metadata_storage: &mut MetadataStorage,
+    source_file: &str,
     sierra_stmt_start_offset: usize,
 ) -> Result<(BlockRef<'c, 'a>, BlockStorage<'c, 'a>, bool), Error> {
     let initial_state = edit_state::put_results::<Type>(
@@ -1007,7 +1012,7 @@ fn generate_function_structure<'c, 'a>(
 
                     let location = Location::new(
                         context,
-                        "program.sierra",
+                        source_file,
                         sierra_stmt_start_offset + statement_idx.0,
                         0,
                     );
@@ -1077,7 +1082,7 @@ fn generate_function_structure<'c, 'a>(
 
                     let location = Location::new(
                         context,
-                        "program.sierra",
+                        source_file,
                         sierra_stmt_start_offset + statement_idx.0,
                         0,
                     );
@@ -1106,7 +1111,7 @@ fn generate_function_structure<'c, 'a>(
                 ty?,
                 Location::new(
                     context,
-                    "program.sierra",
+                    source_file,
                     sierra_stmt_start_offset + function.entry_point.0,
                     0,
                 ),
@@ -1142,7 +1147,7 @@ fn generate_function_structure<'c, 'a>(
                                         ty,
                                         Location::new(
                                             context,
-                                            "program.sierra",
+                                            source_file,
                                             sierra_stmt_start_offset + statement_idx.0,
                                             0,
                                         ),
@@ -1,6 +1,8 @@
 use crate::{
+    dump::{sierra_source, DumpConfig, DumpStage},
     error::{panic::ToNativeAssertError, Error},
-    ffi::{get_data_layout_rep, get_target_triple},
+    ffi::{get_data_layout_rep, get_target_triple, TargetOptions},
+    limits::{self, CompilationLimits, ResourceGuard},
     metadata::{
         gas::{GasMetadata, MetadataComputationConfig},
         runtime_bindings::RuntimeBindingsMeta,
@@ -8,10 +10,13 @@ use crate::{
     },
     module::NativeModule,
     native_assert,
-    utils::run_pass_manager,
+    options::CompilationOptions,
+    statistics::CompilationStats,
+    utils::{program_hash, reachable_subprogram, run_pass_manager, ProgramHash},
 };
 use cairo_lang_sierra::{
     extensions::core::{CoreLibfunc, CoreType},
+    ids::FunctionId,
     program::Program,
     program_registry::ProgramRegistry,
 };
@@ -22,9 +27,8 @@ use llvm_sys::target::{
 use melior::{
     dialect::DialectRegistry,
     ir::{
-        attribute::StringAttribute,
-        operation::{OperationBuilder, OperationPrintingFlags},
-        Attribute, AttributeLike, Block, Identifier, Location, Module, Region,
+        attribute::StringAttribute, operation::OperationBuilder, Attribute, AttributeLike, Block,
+        Identifier, Location, Module, Region,
     },
     utility::{register_all_dialects, register_all_llvm_translations, register_all_passes},
     Context,
@@ -41,6 +45,10 @@ use tracing::trace;
 #[derive(Debug, Eq, PartialEq)]
 pub struct NativeContext {
     context: Context,
+    limits: CompilationLimits,
+    options: CompilationOptions,
+    target: TargetOptions,
+    dump: Option<DumpConfig>,
 }
 
 unsafe impl Send for NativeContext {}
@@ -55,13 +63,74 @@ impl Default for NativeContext {
 impl NativeContext {
     pub fn new() -> Self {
         let context = initialize_mlir();
-        Self { context }
+        Self {
+            context,
+            limits: CompilationLimits::default(),
+            options: CompilationOptions::default(),
+            target: TargetOptions::default(),
+            dump: None,
+        }
     }
 
     pub const fn context(&self) -> &Context {
         &self.context
     }
 
+    /// Limit the resources used by the compilations, which will fail with
+    /// [`Error::ResourceLimitExceeded`] when exceeding them.
+    ///
+    /// The limits are also checked when loading the compiled modules into executors, which measure
+    /// their own time and memory. See the [`limits`](crate::limits) module for when they're checked.
+    pub fn with_limits(mut self, limits: CompilationLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub const fn limits(&self) -> &CompilationLimits {
+        &self.limits
+    }
+
+    /// Configure the pass pipelines used by the compilations.
+    ///
+    /// The MLIR passes are run by [`compile`](Self::compile), while the LLVM ones and the codegen
+    /// units are used by the AOT executors (and the caches building them) when generating the code
+    /// (or by [`module_to_objects`](crate::module_to_objects)).
+    pub fn with_options(mut self, options: CompilationOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub const fn options(&self) -> &CompilationOptions {
+        &self.options
+    }
+
+    /// Generate the code for the `target` instead of the host.
+    ///
+    /// The AOT executors generate their objects for the same target. However, the executors refuse
+    /// modules compiled for another triple with [`Error::UnsupportedTarget`]: those must be
+    /// converted into objects (see [`module_to_object`](crate::module_to_object)) and loaded on a
+    /// matching machine instead.
+    pub fn with_target(mut self, target: TargetOptions) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub const fn target(&self) -> &TargetOptions {
+        &self.target
+    }
+
+    /// Dump the compilations' intermediate representations, as configured by the `dump`.
+    ///
+    /// The object is dumped by the AOT executors, when they generate the code.
+    pub fn with_dump(mut self, dump: DumpConfig) -> Self {
+        self.dump = Some(dump);
+        self
+    }
+
+    pub const fn dump(&self) -> Option<&DumpConfig> {
+        self.dump.as_ref()
+    }
+
     /// Compiles a sierra program into MLIR and then lowers to LLVM.
     /// Returns the corresponding NativeModule struct.
     ///
@@ -72,9 +141,51 @@ impl NativeContext {
         program: &Program,
         ignore_debug_names: bool,
         gas_metadata_config: Option<MetadataComputationConfig>,
+    ) -> Result<NativeModule, Error> {
+        let NativeModule {
+            mut module,
+            registry,
+            mut metadata,
+        } = self.compile_unlowered(program, ignore_debug_names, gas_metadata_config)?;
+        let mut stats = metadata
+            .remove::<CompilationStats>()
+            .to_native_assert_error("compilation stats should be present")?;
+
+        if let Some(dump) = metadata.get::<DumpConfig>() {
+            dump.dump_mlir(DumpStage::Mlir, &module)?;
+        }
+
+        trace!("starting mlir passes");
+        let pre_passes_instant = Instant::now();
+        run_pass_manager(&self.context, &mut module, &self.options)?;
+        stats.mlir_passes = pre_passes_instant.elapsed();
+        trace!(time = stats.mlir_passes.as_millis(), "mlir passes finished");
+        limits::check(metadata.get::<ResourceGuard>())?;
+
+        if let Some(dump) = metadata.get::<DumpConfig>() {
+            dump.dump_mlir(DumpStage::MlirLowered, &module)?;
+        }
+
+        stats.record_peak_memory();
+        metadata.insert(stats);
+
+        Ok(NativeModule::new(module, registry, metadata))
+    }
+
+    /// Compiles a sierra program into MLIR, without running any pass on it.
+    ///
+    /// The resulting module is only useful for inspecting the generated code, since it has to be
+    /// lowered into the LLVM dialect (as [`compile`](Self::compile) does) before converting it into
+    /// an object or running it.
+    pub fn compile_unlowered(
+        &self,
+        program: &Program,
+        ignore_debug_names: bool,
+        gas_metadata_config: Option<MetadataComputationConfig>,
     ) -> Result<NativeModule, Error> {
         trace!("starting sierra to mlir compilation");
         let pre_sierra_compilation_instant = Instant::now();
+        let guard = ResourceGuard::new(&self.limits, program)?;
 
         static INITIALIZED: OnceLock<()> = OnceLock::new();
         INITIALIZED.get_or_init(|| unsafe {
@@ -84,12 +195,19 @@ impl NativeContext {
             LLVM_InitializeAllAsmPrinters();
             tracing::debug!("initialized llvm targets");
         });
-        let target_triple = get_target_triple();
+        let target_triple = get_target_triple(&self.target);
 
         let module_region = Region::new();
         module_region.append_block(Block::new(&[]));
 
-        let data_layout_ret = &get_data_layout_rep()?;
+        let data_layout_ret = &get_data_layout_rep(&self.target)?;
+
+        let dump = self
+            .dump
+            .as_ref()
+            .map(|dump| dump.for_program(program))
+            .transpose()?;
+        let (source_file, source_dir) = sierra_source(dump.as_ref());
 
         let di_unit_id = unsafe {
             let id = StringAttribute::new(&self.context, "compile_unit_id").to_raw();
@@ -100,13 +218,13 @@ impl NativeContext {
             "builtin.module",
             Location::fused(
                 &self.context,
-                &[Location::new(&self.context, "program.sierra", 0, 0)],
+                &[Location::new(&self.context, &source_file, 0, 0)],
                 {
                     let file_attr = unsafe {
                         Attribute::from_raw(mlirLLVMDIFileAttrGet(
                             self.context.to_raw(),
-                            StringAttribute::new(&self.context, "program.sierra").to_raw(),
-                            StringAttribute::new(&self.context, "").to_raw(),
+                            StringAttribute::new(&self.context, &source_file).to_raw(),
+                            StringAttribute::new(&self.context, &source_dir).to_raw(),
                         ))
                     };
                     unsafe {
@@ -155,7 +273,7 @@ impl NativeContext {
 
         native_assert!(op.verify(), "module operation should be valid");
 
-        let mut module = Module::from_operation(op)
+        let module = Module::from_operation(op)
             .to_native_assert_error("value should be module operation")?;
 
         let mut metadata = MetadataStorage::new();
@@ -166,6 +284,18 @@ impl NativeContext {
         // Unwrapping here is not necessary since the insertion will only fail if there was
         // already some metadata of the same type.
         metadata.insert(gas_metadata);
+        // Keep checking the limits until the code is generated.
+        metadata.insert(guard);
+        // The LLVM passes are run when generating the code, for the same target.
+        metadata.insert(self.options.clone());
+        metadata.insert(self.target.clone());
+        // Allows the AOT executors to check they're saved along with the right program.
+        metadata.insert(ProgramHash(program_hash(program)?));
+        if let Some(dump) = dump {
+            dump.dump_sierra(program)?;
+            // The object is dumped when generating the code.
+            metadata.insert(dump);
+        }
 
         // Create the Sierra program registry
         let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(program)?;
@@ -180,55 +310,39 @@ impl NativeContext {
             ignore_debug_names,
         )?;
 
-        let sierra_compilation_time = pre_sierra_compilation_instant.elapsed().as_millis();
+        let mut stats = CompilationStats {
+            sierra_to_mlir: pre_sierra_compilation_instant.elapsed(),
+            ..Default::default()
+        };
         trace!(
-            time = sierra_compilation_time,
+            time = stats.sierra_to_mlir.as_millis(),
             "sierra to mlir compilation finished"
         );
+        stats.count_operations(&module);
+        metadata.insert(stats);
 
-        if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-            if x == "1" || x == "true" {
-                std::fs::write("dump-prepass.mlir", module.as_operation().to_string())?;
-                std::fs::write(
-                    "dump-prepass-debug-valid.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-                std::fs::write(
-                    "dump-prepass-debug-pretty.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-            }
-        }
+        Ok(NativeModule::new(module, registry, metadata))
+    }
 
-        trace!("starting mlir passes");
-        let pre_passes_instant = Instant::now();
-        run_pass_manager(&self.context, &mut module)?;
-        let passes_time = pre_passes_instant.elapsed().as_millis();
-        trace!(time = passes_time, "mlir passes finished");
-
-        if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-            if x == "1" || x == "true" {
-                std::fs::write("dump.mlir", module.as_operation().to_string())?;
-                std::fs::write(
-                    "dump-debug-pretty.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-                std::fs::write(
-                    "dump-debug.mlir",
-                    module.as_operation().to_string_with_flags(
-                        OperationPrintingFlags::new().enable_debug_info(true, false),
-                    )?,
-                )?;
-            }
-        }
+    /// Compiles only the parts of a sierra program reachable from the `roots`.
+    ///
+    /// The unreachable functions are not available in the resulting module. See
+    /// [`reachable_subprogram`] for details.
+    pub fn compile_reachable<'a>(
+        &self,
+        program: &Program,
+        roots: impl IntoIterator<Item = &'a FunctionId>,
+        ignore_debug_names: bool,
+        gas_metadata_config: Option<MetadataComputationConfig>,
+    ) -> Result<NativeModule, Error> {
+        let program = reachable_subprogram(program, roots)?;
+        trace!(
+            functions = program.funcs.len(),
+            statements = program.statements.len(),
+            "pruned unreachable code"
+        );
 
-        Ok(NativeModule::new(module, registry, metadata))
+        self.compile(&program, ignore_debug_names, gas_metadata_config)
     }
 }
 
@@ -0,0 +1,243 @@
+//! # Debug dumps
+//!
+//! Writes the intermediate representations of a program's compilation into a directory, to
+//! inspect what was generated for it. The dumps are configured in the
+//! [`NativeContext`](crate::context::NativeContext), which stores the configuration in the module's
+//! metadata so that the AOT executors dump the objects too.
+//!
+//! Every program's files are named after it, therefore many compilations can share a directory
+//! (even from different threads) without overwriting each other's files.
+
+use crate::{
+    error::Result,
+    ffi,
+    utils::{program_hash, SHARED_LIBRARY_EXT},
+};
+use cairo_lang_sierra::program::Program;
+use melior::ir::{
+    attribute::StringAttribute,
+    operation::{OperationLike, OperationPrintingFlags},
+    BlockLike, Module,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// Which compilation stages to dump, and where.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DumpConfig {
+    /// The directory where the files are written. It's created if it doesn't exist.
+    pub output_dir: PathBuf,
+    /// The name of the files, without their extension. Defaults to the program's hash.
+    pub name: Option<String>,
+    /// The stages to dump.
+    pub stages: Vec<DumpStage>,
+    /// Only dump the functions whose names contain any of these strings, or every function when
+    /// empty.
+    ///
+    /// It only applies to the MLIR stages, the others always contain the whole program.
+    pub functions: Vec<String>,
+}
+
+/// A compilation stage which can be dumped.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum DumpStage {
+    /// The Sierra program (`.sierra`).
+    Sierra,
+    /// The MLIR generated from the Sierra program, before running any pass (`.prepass.mlir`).
+    Mlir,
+    /// The MLIR lowered into the LLVM dialect (`.mlir`).
+    MlirLowered,
+    /// The objects (`.o`, or `.<i>.o` for each codegen unit), along with a shared library linked
+    /// from them.
+    ///
+    /// Only dumped by
+    /// [`AotNativeExecutor::from_native_module`](crate::executor::AotNativeExecutor::from_native_module).
+    /// The shared library is linked with the system linker, which unlike the built-in one keeps the
+    /// debug info, and it's the one the executor loads so that debuggers can show the source
+    /// locations.
+    Object,
+}
+
+impl DumpConfig {
+    /// Dump every stage into the given directory.
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            name: None,
+            stages: vec![
+                DumpStage::Sierra,
+                DumpStage::Mlir,
+                DumpStage::MlirLowered,
+                DumpStage::Object,
+            ],
+            functions: Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self, stage: DumpStage) -> bool {
+        self.stages.contains(&stage)
+    }
+
+    /// Return the configuration for a program's compilation, naming it after the program if it
+    /// has no name.
+    pub(crate) fn for_program(&self, program: &Program) -> Result<Self> {
+        let mut config = self.clone();
+        if config.name.is_none() {
+            config.name = Some(program_hash(program)?[..16].to_string());
+        }
+
+        Ok(config)
+    }
+
+    /// The file name and directory of the dumped Sierra program.
+    fn sierra_source(&self) -> (String, String) {
+        let name = self.name.as_deref().unwrap_or("program");
+        let output_dir = std::path::absolute(&self.output_dir).unwrap_or(self.output_dir.clone());
+
+        (format!("{name}.sierra"), output_dir.display().to_string())
+    }
+
+    fn path(&self, extension: &str) -> Result<PathBuf> {
+        fs::create_dir_all(&self.output_dir)?;
+
+        let name = self.name.as_deref().unwrap_or("program");
+        Ok(self.output_dir.join(format!("{name}.{extension}")))
+    }
+
+    pub(crate) fn dump_sierra(&self, program: &Program) -> Result<()> {
+        if self.is_enabled(DumpStage::Sierra) {
+            fs::write(self.path("sierra")?, program.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Dump the module if the `stage` (either of the MLIR ones) is enabled, both with and without
+    /// the locations.
+    pub(crate) fn dump_mlir(&self, stage: DumpStage, module: &Module) -> Result<()> {
+        if !self.is_enabled(stage) {
+            return Ok(());
+        }
+
+        let (path, debug_path) = match stage {
+            DumpStage::Mlir => ("prepass.mlir", "prepass.debug.mlir"),
+            _ => ("mlir", "debug.mlir"),
+        };
+        fs::write(self.path(path)?, self.print_module(module, false)?)?;
+        fs::write(self.path(debug_path)?, self.print_module(module, true)?)?;
+
+        Ok(())
+    }
+
+    /// Print the module, or only the functions which pass the filter.
+    fn print_module(&self, module: &Module, debug_info: bool) -> Result<String> {
+        let flags = || OperationPrintingFlags::new().enable_debug_info(debug_info, false);
+
+        if self.functions.is_empty() {
+            return Ok(module.as_operation().to_string_with_flags(flags())?);
+        }
+
+        let mut output = String::new();
+        let mut op = module.body().first_operation();
+        while let Some(current_op) = op {
+            let name = current_op
+                .attribute("sym_name")
+                .ok()
+                .and_then(|x| StringAttribute::try_from(x).ok())
+                .map(|x| x.value().to_string());
+
+            if name.is_some_and(|name| self.functions.iter().any(|x| name.contains(x.as_str()))) {
+                output.push_str(&current_op.to_string_with_flags(flags())?);
+                output.push('\n');
+            }
+
+            op = current_op.next_in_block();
+        }
+
+        Ok(output)
+    }
+
+    /// Dump the objects and link them into a shared library, returning the library's path.
+    ///
+    /// A single object is dumped as `<name>.o`, while many (one per codegen unit) are numbered as
+    /// `<name>.<i>.o`.
+    pub(crate) fn dump_objects(&self, objects: &[Vec<u8>]) -> Result<Option<PathBuf>> {
+        if !self.is_enabled(DumpStage::Object) {
+            return Ok(None);
+        }
+
+        let object_paths = match objects {
+            [object] => {
+                let object_path = self.path("o")?;
+                fs::write(&object_path, object)?;
+                vec![object_path]
+            }
+            _ => objects
+                .iter()
+                .enumerate()
+                .map(|(i, object)| {
+                    let object_path = self.path(&format!("{i}.o"))?;
+                    fs::write(&object_path, object)?;
+                    Ok(object_path)
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+
+        let library_path = self.path(SHARED_LIBRARY_EXT)?;
+        ffi::link_object_files(&object_paths, &library_path)?;
+
+        Ok(Some(library_path))
+    }
+}
+
+/// The file name and directory of the Sierra source the debug info refers to.
+///
+/// When dumping, it's the dumped program so that debuggers can show the source locations.
+pub(crate) fn sierra_source(dump: Option<&DumpConfig>) -> (String, String) {
+    dump.map(DumpConfig::sierra_source)
+        .unwrap_or_else(|| ("program.sierra".to_string(), String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context::NativeContext, utils::test::load_cairo};
+
+    #[test]
+    fn test_dump_stages() {
+        let (_, program) = load_cairo! {
+            fn run_test(x: felt252) -> felt252 {
+                double(x) + 1
+            }
+
+            fn double(x: felt252) -> felt252 {
+                x * 2
+            }
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let native_context = NativeContext::new().with_dump(DumpConfig {
+            stages: vec![DumpStage::Sierra, DumpStage::MlirLowered],
+            functions: vec!["double".to_string()],
+            ..DumpConfig::new(dir.path())
+        });
+        native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+
+        // The files are named after the program.
+        let name = &program_hash(&program).unwrap()[..16];
+        let sierra = fs::read_to_string(dir.path().join(format!("{name}.sierra"))).unwrap();
+        assert_eq!(sierra, program.to_string());
+
+        let mlir = fs::read_to_string(dir.path().join(format!("{name}.mlir"))).unwrap();
+        assert!(mlir.contains("double"));
+        assert!(!mlir.contains("run_test"));
+        assert!(!dir.path().join(format!("{name}.prepass.mlir")).exists());
+
+        // The locations refer to the dumped Sierra program.
+        let debug_mlir = fs::read_to_string(dir.path().join(format!("{name}.debug.mlir"))).unwrap();
+        assert!(debug_mlir.contains(&format!("{name}.sierra")));
+        assert!(!debug_mlir.contains("program.sierra"));
+    }
+}
@@ -1,12 +1,12 @@
 //! Various error types used thorough the crate.
-use crate::metadata::gas::GasMetadataError;
+use crate::{executor::ContractInfoVersion, ffi::CpuBaseline, metadata::gas::GasMetadataError};
 use cairo_lang_sierra::extensions::modules::utils::Range;
 use cairo_lang_sierra::{
     edit_state::EditStateError, ids::ConcreteTypeId, program_registry::ProgramRegistryError,
 };
 use num_bigint::BigInt;
 use panic::NativeAssertError;
-use std::{alloc::LayoutError, num::TryFromIntError};
+use std::{alloc::LayoutError, num::TryFromIntError, time::Duration};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -28,6 +28,27 @@ pub enum Error {
     #[error("a syscall handler was expected but was not provided")]
     MissingSyscallHandler,
 
+    #[error("signature mismatch: {0}")]
+    SignatureMismatch(String),
+
+    #[error("values of type '{0}' can't be passed to or returned from a function")]
+    UnsupportedValueType(String),
+
+    #[error(transparent)]
+    ValueMismatch(#[from] ValueMismatchError),
+
+    #[error(
+        "argument {index}{}: expected a value of type '{}', found {}",
+        error.path, error.expected, error.found
+    )]
+    InvalidArgument {
+        index: usize,
+        error: ValueMismatchError,
+    },
+
+    #[error("expected {expected} arguments but {found} were given")]
+    TooManyArguments { expected: usize, found: usize },
+
     #[error(transparent)]
     LayoutErrorPolyfill(#[from] crate::utils::LayoutError),
 
@@ -43,6 +64,9 @@ pub enum Error {
     #[error("missing metadata")]
     MissingMetadata,
 
+    #[error("the program is not the one the executor was compiled from")]
+    ProgramMismatch,
+
     #[error(transparent)]
     SierraAssert(#[from] SierraAssertError),
 
@@ -64,6 +88,27 @@ pub enum Error {
     #[error("ld link error: {0}")]
     LinkError(String),
 
+    #[error(
+        "the host can't run code compiled for cpu `{cpu}`, missing features: {}",
+        missing_features.join(",")
+    )]
+    UnsupportedCpu {
+        cpu: String,
+        missing_features: Vec<String>,
+    },
+
+    #[error("code compiled for `{0}` can't be run on the host")]
+    UnsupportedTarget(String),
+
+    #[error("the {baseline:?} CPU baseline can't be used for target `{triple}`")]
+    CpuBaselineMismatch {
+        baseline: CpuBaseline,
+        triple: String,
+    },
+
+    #[error(transparent)]
+    IncompatibleContract(#[from] IncompatibleContractError),
+
     #[error("cairo const data mismatch")]
     ConstDataMismatch,
 
@@ -79,6 +124,21 @@ pub enum Error {
     #[error("selector not found in the AotContractExecutor mappings")]
     SelectorNotFound,
 
+    #[error("the execution stack overflowed")]
+    StackOverflow,
+
+    #[error("native fault (signal {signal}) while executing function f{function_id}")]
+    NativeFault { signal: i32, function_id: u64 },
+
+    #[error("the compilation was cancelled")]
+    CompilationCancelled,
+
+    #[error("the compilation panicked: {0}")]
+    CompilationPanicked(String),
+
+    #[error("compilation limit exceeded: {0}")]
+    ResourceLimitExceeded(#[from] ResourceLimitError),
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 
@@ -117,6 +177,51 @@ pub enum SierraAssertError {
     ImpossibleCircuit,
 }
 
+/// The reasons a compiled contract (or program) can't be loaded, which mean it has to be
+/// recompiled.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum IncompatibleContractError {
+    /// The library predates the [`BuildInfo`](crate::executor::BuildInfo) (for example, a
+    /// [`ContractInfoVersion::V0`] sidecar), therefore there's no way to tell whether it's
+    /// compatible.
+    #[error("compiled without build info (contract info version {0:?}), it must be recompiled")]
+    MissingBuildInfo(ContractInfoVersion),
+    #[error("compiled by cairo-native {found}, expected {expected}")]
+    NativeVersion { expected: String, found: String },
+    #[error("compiled against runtime ABI version {found}, expected {expected}")]
+    RuntimeAbiVersion { expected: u32, found: u32 },
+    #[error("compiled for target {found}, expected {expected}")]
+    TargetTriple { expected: String, found: String },
+}
+
+/// A [`Value`](crate::values::Value) which doesn't match the Sierra type it's used as.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("value{path}: expected a value of type '{expected}', found {found}")]
+pub struct ValueMismatchError {
+    /// Where the mismatch is within the checked value: `.N` is a struct's member, `[N]` an array's
+    /// element (or a dictionary's entry) and `#N` the payload of an enum's variant. Members and
+    /// variants are qualified by their type's name when known, like `.Option#0`.
+    pub path: String,
+    /// The expected Sierra type.
+    pub expected: String,
+    /// The Sierra type of the value which was found instead, or a description of it when the
+    /// value may be of many types.
+    pub found: String,
+}
+
+/// A [`CompilationLimits`](crate::limits::CompilationLimits) limit exceeded by a compilation.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ResourceLimitError {
+    #[error("took longer than {limit:?}")]
+    Time { limit: Duration },
+    #[error("{found} statements, the limit is {limit}")]
+    Statements { limit: usize, found: usize },
+    #[error("{found} functions, the limit is {limit}")]
+    Functions { limit: usize, found: usize },
+    #[error("used {used} bytes of memory, the limit is {limit}")]
+    Memory { limit: u64, used: u64 },
+}
+
 #[derive(Error, Debug)]
 pub enum CompilerError {
     #[error("BoundedInt value is out of range: {:?} not within [{:?}, {:?})", value, range.0, range.1)]
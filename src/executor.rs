@@ -1,39 +1,53 @@
 //! # Executors
 //!
 //! This module provides methods to execute the programs, either via JIT or compiled ahead
-//! of time. It also provides a cache to avoid recompiling previously compiled programs.
-
-pub use self::{aot::AotNativeExecutor, contract::AotContractExecutor, jit::JitNativeExecutor};
+//! of time. It also provides a cache to avoid recompiling previously compiled programs, and a
+//! service to compile contracts in the background.
+
+pub use self::{
+    aot::AotNativeExecutor,
+    contract::{AotContractExecutor, BuildInfo, ContractInfoVersion, NativeContractInfo},
+    jit::JitNativeExecutor,
+    service::{CompilationHandle, CompilationService},
+    typed::{ReturnReader, TypedArguments, TypedExecutionResult, TypedReturns, TypedValue},
+};
+use self::{
+    contract::BuiltinType,
+    guard::GuardConfig,
+    plan::{InvocationPlan, ParamSlot},
+};
 use crate::{
     arch::{AbiArgument, ValueWithInfoWrapper},
     error::{panic::ToNativeAssertError, Error},
     execution_result::{BuiltinStats, ExecutionResult},
     native_panic,
     starknet::{handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler},
-    types::TypeBuilder,
+    types::{circuit, TypeBuilder},
     utils::{libc_free, BuiltinCosts, RangeExt},
     values::Value,
 };
 use bumpalo::Bump;
 use cairo_lang_sierra::{
     extensions::{
-        circuit::CircuitTypeConcrete,
         core::{CoreLibfunc, CoreType, CoreTypeConcrete},
         starknet::StarkNetTypeConcrete,
         ConcreteType,
     },
-    ids::ConcreteTypeId,
-    program::FunctionSignature,
+    ids::{ConcreteTypeId, FunctionId},
     program_registry::ProgramRegistry,
 };
 use libc::c_void;
 use num_bigint::BigInt;
 use num_traits::One;
-use std::{alloc::Layout, arch::global_asm, ptr::NonNull};
+use std::{arch::global_asm, ptr::NonNull};
 
 mod aot;
 mod contract;
+mod guard;
 mod jit;
+mod plan;
+mod service;
+mod typed;
 
 #[cfg(target_arch = "aarch64")]
 global_asm!(include_str!("arch/aarch64.s"));
@@ -63,14 +77,17 @@ extern "C" {
 /// constructs the function call in place.
 ///
 /// To pass the arguments, they are stored in a arena.
+///
+/// The invocation is protected as configured by `guard_config`, which may turn stack overflows
+/// and native faults into errors instead of crashing.
 #[allow(clippy::too_many_arguments)]
 fn invoke_dynamic(
     registry: &ProgramRegistry<CoreType, CoreLibfunc>,
-    function_ptr: *const c_void,
-    function_signature: &FunctionSignature,
+    function_id: &FunctionId,
+    plan: &InvocationPlan,
     args: &[Value],
     gas: u64,
-    mut syscall_handler: Option<impl StarknetSyscallHandler>,
+    syscall_handler: Option<impl StarknetSyscallHandler>,
     find_dict_overrides: impl Copy
         + Fn(
             &ConcreteTypeId,
@@ -78,58 +95,111 @@ fn invoke_dynamic(
             Option<extern "C" fn(*mut c_void, *mut c_void)>,
             Option<extern "C" fn(*mut c_void)>,
         ),
+    guard_config: GuardConfig,
 ) -> Result<ExecutionResult, Error> {
-    tracing::info!("Invoking function with signature: {function_signature:?}.");
+    tracing::info!("Invoking function {function_id}.");
+
+    // Check the arguments before anything is allocated for them.
+    let param_types = plan.params.iter().filter_map(|param| match param {
+        ParamSlot::Value(type_id) => Some(type_id),
+        ParamSlot::Builtin(_) => None,
+    });
+    let num_params = param_types.clone().count();
+    if args.len() > num_params {
+        return Err(Error::TooManyArguments {
+            expected: num_params,
+            found: args.len(),
+        });
+    }
+    for (index, type_id) in param_types.enumerate() {
+        args.get(index)
+            .ok_or_else(|| Error::make_missing_parameter(type_id))?
+            .check(registry, type_id)
+            .map_err(|e| match e {
+                Error::ValueMismatch(error) => Error::InvalidArgument { index, error },
+                e => e,
+            })?;
+    }
+
+    // Arguments are allocated in the arena, which must outlive the invocation.
     let arena = Bump::new();
-    let mut invoke_data = Vec::<u8>::new();
+    let (remaining_gas, builtin_stats, return_value) = invoke_with_plan(
+        function_id,
+        plan,
+        gas,
+        syscall_handler,
+        guard_config,
+        |index, type_id, invoke_data| {
+            ValueWithInfoWrapper {
+                value: args
+                    .get(index)
+                    .to_native_assert_error("entrypoint argument is missing")?,
+                type_id,
+                info: registry.get_type(type_id)?,
 
-    // Generate return pointer (if necessary).
-    //
-    // Generated when either:
-    //   - There are more than one non-zst return values.
-    //     - All builtins except GasBuiltin and Starknet are ZST.
-    //     - The unit struct is a ZST.
-    //   - The return argument is complex.
-    let mut ret_types_iter = function_signature
-        .ret_types
-        .iter()
-        .filter_map(|id| {
-            let type_info = match registry.get_type(id) {
-                Ok(x) => x,
-                Err(e) => return Some(Err(e.into())),
-            };
-            let is_zst = match type_info.is_zst(registry) {
-                Ok(x) => x,
-                Err(e) => return Some(Err(e)),
-            };
+                arena: &arena,
+                registry,
+            }
+            .to_bytes(invoke_data, find_dict_overrides)
+        },
+        |return_ptr, ret_registers| {
+            Ok(plan
+                .return_type
+                .as_ref()
+                .map(|ret_type| parse_result(ret_type, registry, return_ptr, ret_registers))
+                .transpose()?
+                .unwrap_or_else(|| Value::Struct {
+                    fields: vec![],
+                    debug_name: None,
+                }))
+        },
+    )?;
 
-            Ok((!(type_info.is_builtin() && is_zst)).then_some(id)).transpose()
-        })
-        .collect::<Result<Vec<_>, _>>()?
-        .into_iter()
-        .peekable();
-
-    let num_return_args = ret_types_iter.clone().count();
-    let mut return_ptr = if num_return_args > 1
-        || ret_types_iter
-            .peek()
-            .map(|id| registry.get_type(id)?.is_complex(registry))
-            .transpose()?
-            == Some(true)
-    {
-        let layout = ret_types_iter.try_fold(Layout::new::<()>(), |layout, id| {
-            let type_info = registry.get_type(id)?;
-            Result::<_, Error>::Ok(layout.extend(type_info.layout(registry)?)?.0)
-        })?;
-
-        let return_ptr = arena.alloc_layout(layout).cast::<()>();
-        return_ptr
-            .as_ptr()
-            .to_bytes(&mut invoke_data, |_| unreachable!())?;
+    Ok(ExecutionResult {
+        remaining_gas,
+        return_value,
+        builtin_stats,
+    })
+}
+
+/// The registers the trampoline stores small return values in.
+#[cfg(target_arch = "x86_64")]
+type ReturnRegisters = [u64; 2];
+#[cfg(target_arch = "aarch64")]
+type ReturnRegisters = [u64; 4];
 
-        Some(return_ptr)
-    } else {
-        None
+/// Internal method.
+///
+/// The part of an invocation shared by `invoke_dynamic` and `invoke_typed`: sets up the return
+/// pointer, the syscall handler and the builtin costs, calls the trampoline under the guard and
+/// reads the returned builtins.
+///
+/// The non-builtin arguments are serialized by `write_argument`, which receives their index among
+/// them and their type. The return value is decoded by `read_return` from what's left after the
+/// builtins, either behind the return pointer or in the registers.
+fn invoke_with_plan<T>(
+    function_id: &FunctionId,
+    plan: &InvocationPlan,
+    gas: u64,
+    mut syscall_handler: Option<impl StarknetSyscallHandler>,
+    guard_config: GuardConfig,
+    mut write_argument: impl FnMut(usize, &ConcreteTypeId, &mut Vec<u8>) -> Result<(), Error>,
+    read_return: impl FnOnce(Option<NonNull<()>>, ReturnRegisters) -> Result<T, Error>,
+) -> Result<(Option<u64>, BuiltinStats, T), Error> {
+    let arena = Bump::new();
+    let mut invoke_data = Vec::<u8>::new();
+
+    // Generate return pointer (if necessary).
+    let mut return_ptr = match plan.return_layout {
+        Some(layout) => {
+            let return_ptr = arena.alloc_layout(layout).cast::<()>();
+            return_ptr
+                .as_ptr()
+                .to_bytes(&mut invoke_data, |_| unreachable!())?;
+
+            Some(return_ptr)
+        }
+        None => None,
     };
 
     // The Cairo compiler doesn't specify that the cheatcode syscall needs the syscall handler,
@@ -159,78 +229,46 @@ fn invoke_dynamic(
         crate::runtime::cairo_native__set_costs_builtin(builtin_costs.cast());
 
     // Generate argument list.
-    let mut iter = args.iter();
-    for item in function_signature.param_types.iter().filter_map(|type_id| {
-        let type_info = match registry.get_type(type_id) {
-            Ok(x) => x,
-            Err(e) => return Some(Err(e.into())),
-        };
-        match type_info.is_zst(registry) {
-            Ok(x) => (!x).then_some(Ok((type_id, type_info))),
-            Err(e) => Some(Err(e)),
-        }
-    }) {
-        let (type_id, type_info) = item?;
-
+    let mut arg_index = 0;
+    for param in &plan.params {
         // Process gas requirements and syscall handler.
-        match type_info {
-            CoreTypeConcrete::GasBuiltin(_) => {
+        match param {
+            ParamSlot::Builtin(BuiltinType::Gas) => {
                 gas.to_bytes(&mut invoke_data, |_| unreachable!())?
             }
-            CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_)) => {
+            ParamSlot::Builtin(BuiltinType::System) => {
                 let syscall_handler = syscall_handler
                     .as_mut()
-                    .to_native_assert_error("syscall handler should be available")?;
+                    .ok_or(Error::MissingSyscallHandler)?;
 
                 (syscall_handler as *mut StarknetSyscallHandlerCallbacks<_>)
                     .to_bytes(&mut invoke_data, |_| unreachable!())?;
             }
-            CoreTypeConcrete::BuiltinCosts(_) => {
+            ParamSlot::Builtin(BuiltinType::BuiltinCosts) => {
                 builtin_costs.to_bytes(&mut invoke_data, |_| unreachable!())?;
             }
-            type_info if type_info.is_builtin() => {
-                0u64.to_bytes(&mut invoke_data, |_| unreachable!())?
+            ParamSlot::Builtin(_) => 0u64.to_bytes(&mut invoke_data, |_| unreachable!())?,
+            ParamSlot::Value(type_id) => {
+                write_argument(arg_index, type_id, &mut invoke_data)?;
+                arg_index += 1;
             }
-            type_info => ValueWithInfoWrapper {
-                value: iter
-                    .next()
-                    .to_native_assert_error("entrypoint argument is missing")?,
-                type_id,
-                info: type_info,
-
-                arena: &arena,
-                registry,
-            }
-            .to_bytes(&mut invoke_data, find_dict_overrides)?,
         }
     }
 
-    // Pad invoke data to the 16 byte boundary avoid segfaults.
-    #[cfg(target_arch = "aarch64")]
-    const REGISTER_BYTES: usize = 64;
-    #[cfg(target_arch = "x86_64")]
-    const REGISTER_BYTES: usize = 48;
-    if invoke_data.len() > REGISTER_BYTES {
-        invoke_data.resize(
-            REGISTER_BYTES + (invoke_data.len() - REGISTER_BYTES).next_multiple_of(16),
-            0,
-        );
-    }
+    pad_invoke_data(&mut invoke_data);
 
     // Invoke the trampoline.
-    #[cfg(target_arch = "x86_64")]
-    let mut ret_registers = [0; 2];
-    #[cfg(target_arch = "aarch64")]
-    let mut ret_registers = [0; 4];
+    let mut ret_registers = ReturnRegisters::default();
 
-    unsafe {
+    let invoke = || unsafe {
         invoke_trampoline(
-            function_ptr,
+            plan.function_ptr,
             invoke_data.as_ptr().cast(),
             invoke_data.len() >> 3,
             ret_registers.as_mut_ptr(),
         );
-    }
+    };
+    let invoke_result = guard::run_guarded(guard_config, plan.fault_scope, function_id.id, invoke);
 
     // If the syscall handler was changed, then reset the previous one.
     // It's only necessary to restore the pointer if it's been modified i.e. if previous_syscall_handler is Some(...)
@@ -239,103 +277,16 @@ fn invoke_dynamic(
         crate::starknet::SYSCALL_HANDLER_VTABLE.set(previous_syscall_handler);
     }
 
-    // Parse final gas.
-    unsafe fn read_value<T>(ptr: &mut NonNull<()>) -> &T {
-        let align_offset = ptr
-            .cast::<u8>()
-            .as_ptr()
-            .align_offset(std::mem::align_of::<T>());
-        let value_ptr = ptr.cast::<u8>().as_ptr().add(align_offset).cast::<T>();
-
-        *ptr = NonNull::new_unchecked(value_ptr.add(1)).cast();
-        &*value_ptr
-    }
-
-    let mut remaining_gas = None;
-    let mut builtin_stats = BuiltinStats::default();
-    for type_id in &function_signature.ret_types {
-        let type_info = registry.get_type(type_id)?;
-        match type_info {
-            CoreTypeConcrete::GasBuiltin(_) => {
-                remaining_gas = Some(match &mut return_ptr {
-                    Some(return_ptr) => unsafe { *read_value::<u64>(return_ptr) },
-                    None => {
-                        // If there's no return ptr then the function only returned the gas. We don't
-                        // need to bother with the syscall handler builtin.
-                        ret_registers[0]
-                    }
-                });
-            }
-            CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_)) => {
-                if let Some(return_ptr) = &mut return_ptr {
-                    unsafe {
-                        let ptr = return_ptr.cast::<*mut ()>();
-                        *return_ptr = NonNull::new_unchecked(ptr.as_ptr().add(1)).cast();
-                    }
-                }
-            }
-            _ if type_info.is_builtin() => {
-                if !type_info.is_zst(registry)? {
-                    if let CoreTypeConcrete::BuiltinCosts(_) = type_info {
-                        // todo: should we use this value?
-                        let _value = match &mut return_ptr {
-                            Some(return_ptr) => unsafe { *read_value::<*mut u64>(return_ptr) },
-                            None => ret_registers[0] as *mut u64,
-                        };
-                    } else {
-                        let value = match &mut return_ptr {
-                            Some(return_ptr) => unsafe { *read_value::<u64>(return_ptr) },
-                            None => ret_registers[0],
-                        } as usize;
-
-                        match type_info {
-                            CoreTypeConcrete::Bitwise(_) => builtin_stats.bitwise = value,
-                            CoreTypeConcrete::EcOp(_) => builtin_stats.ec_op = value,
-                            CoreTypeConcrete::RangeCheck(_) => builtin_stats.range_check = value,
-                            CoreTypeConcrete::Pedersen(_) => builtin_stats.pedersen = value,
-                            CoreTypeConcrete::Poseidon(_) => builtin_stats.poseidon = value,
-                            CoreTypeConcrete::SegmentArena(_) => {
-                                builtin_stats.segment_arena = value
-                            }
-                            CoreTypeConcrete::RangeCheck96(_) => {
-                                builtin_stats.range_check_96 = value
-                            }
-                            CoreTypeConcrete::Circuit(CircuitTypeConcrete::AddMod(_)) => {
-                                builtin_stats.circuit_add = value
-                            }
-                            CoreTypeConcrete::Circuit(CircuitTypeConcrete::MulMod(_)) => {
-                                builtin_stats.circuit_mul = value
-                            }
-                            _ => native_panic!("given type should be a builtin: {type_id:?}"),
-                        }
-                    }
-                }
-            }
-            _ => break,
-        }
+    if let Err(e) = invoke_result {
+        // The builtin costs are not freed since the aborted program may still reference them.
+        crate::runtime::cairo_native__set_costs_builtin(old_builtincosts_ptr);
+        return Err(e);
     }
 
-    // Parse return values.
-    let return_value = function_signature
-        .ret_types
-        .last()
-        .and_then(|ret_type| {
-            let type_info = match registry.get_type(ret_type) {
-                Ok(x) => x,
-                Err(e) => return Some(Err(e.into())),
-            };
-
-            if type_info.is_builtin() {
-                None
-            } else {
-                Some(parse_result(ret_type, registry, return_ptr, ret_registers))
-            }
-        })
-        .transpose()?
-        .unwrap_or_else(|| Value::Struct {
-            fields: vec![],
-            debug_name: None,
-        });
+    // Parse final gas and return values.
+    let (remaining_gas, builtin_stats) =
+        read_return_builtins(plan, &mut return_ptr, ret_registers[0]);
+    let return_value = read_return(return_ptr, ret_registers);
 
     // Restore the old ptr and get back our builtincost box and free it.
     let our_builtincosts_ptr =
@@ -350,11 +301,83 @@ fn invoke_dynamic(
     #[cfg(feature = "with-mem-tracing")]
     crate::utils::mem_tracing::report_stats();
 
-    Ok(ExecutionResult {
-        remaining_gas,
-        return_value,
-        builtin_stats,
-    })
+    Ok((remaining_gas, builtin_stats, return_value?))
+}
+
+/// Pad invoke data to the 16 byte boundary avoid segfaults.
+fn pad_invoke_data(invoke_data: &mut Vec<u8>) {
+    #[cfg(target_arch = "aarch64")]
+    const REGISTER_BYTES: usize = 64;
+    #[cfg(target_arch = "x86_64")]
+    const REGISTER_BYTES: usize = 48;
+    if invoke_data.len() > REGISTER_BYTES {
+        invoke_data.resize(
+            REGISTER_BYTES + (invoke_data.len() - REGISTER_BYTES).next_multiple_of(16),
+            0,
+        );
+    }
+}
+
+/// Reads a value of type `T` from the (aligned) return pointer, and advances it past the value.
+unsafe fn read_value<T>(ptr: &mut NonNull<()>) -> &T {
+    let align_offset = ptr
+        .cast::<u8>()
+        .as_ptr()
+        .align_offset(std::mem::align_of::<T>());
+    let value_ptr = ptr.cast::<u8>().as_ptr().add(align_offset).cast::<T>();
+
+    *ptr = NonNull::new_unchecked(value_ptr.add(1)).cast();
+    &*value_ptr
+}
+
+/// Reads the builtins returned before the return value, returning the remaining gas (if any) and
+/// the final builtin counters.
+///
+/// When there's no return pointer, the only possible non-ZST builtin is the gas, which is returned
+/// in the first register.
+fn read_return_builtins(
+    plan: &InvocationPlan,
+    return_ptr: &mut Option<NonNull<()>>,
+    first_register: u64,
+) -> (Option<u64>, BuiltinStats) {
+    let mut remaining_gas = None;
+    let mut builtin_stats = BuiltinStats::default();
+    for builtin in &plan.return_builtins {
+        let value = match return_ptr {
+            Some(return_ptr) => match builtin {
+                // Pointers are read to advance the return pointer, but their value is of no use.
+                BuiltinType::System | BuiltinType::BuiltinCosts => unsafe {
+                    *read_value::<*mut ()>(return_ptr) as u64
+                },
+                _ => unsafe { *read_value::<u64>(return_ptr) },
+            },
+            None => first_register,
+        };
+
+        match builtin {
+            BuiltinType::Gas => remaining_gas = Some(value),
+            BuiltinType::System | BuiltinType::BuiltinCosts => {}
+            builtin => record_builtin_stat(&mut builtin_stats, builtin, value as usize),
+        }
+    }
+
+    (remaining_gas, builtin_stats)
+}
+
+/// Stores the final value of a builtin counter into the stats.
+fn record_builtin_stat(builtin_stats: &mut BuiltinStats, builtin: &BuiltinType, value: usize) {
+    match builtin {
+        BuiltinType::Bitwise => builtin_stats.bitwise = value,
+        BuiltinType::EcOp => builtin_stats.ec_op = value,
+        BuiltinType::RangeCheck => builtin_stats.range_check = value,
+        BuiltinType::SegmentArena => builtin_stats.segment_arena = value,
+        BuiltinType::Poseidon => builtin_stats.poseidon = value,
+        BuiltinType::Pedersen => builtin_stats.pedersen = value,
+        BuiltinType::RangeCheck96 => builtin_stats.range_check_96 = value,
+        BuiltinType::CircuitAdd => builtin_stats.circuit_add = value,
+        BuiltinType::CircuitMul => builtin_stats.circuit_mul = value,
+        BuiltinType::Gas | BuiltinType::System | BuiltinType::BuiltinCosts => {}
+    }
 }
 
 /// Parses the result by reading from the return ptr the given type.
@@ -362,8 +385,7 @@ fn parse_result(
     type_id: &ConcreteTypeId,
     registry: &ProgramRegistry<CoreType, CoreLibfunc>,
     mut return_ptr: Option<NonNull<()>>,
-    #[cfg(target_arch = "x86_64")] mut ret_registers: [u64; 2],
-    #[cfg(target_arch = "aarch64")] mut ret_registers: [u64; 4],
+    mut ret_registers: ReturnRegisters,
 ) -> Result<Value, Error> {
     let type_info = registry.get_type(type_id)?;
 
@@ -495,11 +517,11 @@ fn parse_result(
             None => Ok(Value::Sint32(ret_registers[0] as i32)),
         },
         CoreTypeConcrete::Sint64(_) => match return_ptr {
-            Some(return_ptr) => Ok(Value::Uint64(unsafe { *return_ptr.cast().as_ref() })),
+            Some(return_ptr) => Ok(Value::Sint64(unsafe { *return_ptr.cast().as_ref() })),
             None => Ok(Value::Sint64(ret_registers[0] as i64)),
         },
         CoreTypeConcrete::Sint128(_) => match return_ptr {
-            Some(return_ptr) => Ok(Value::Uint128(unsafe { *return_ptr.cast().as_ref() })),
+            Some(return_ptr) => Ok(Value::Sint128(unsafe { *return_ptr.cast().as_ref() })),
             None => Ok(Value::Sint128(
                 ((ret_registers[1] as i128) << 64) | ret_registers[0] as i128,
             )),
@@ -613,11 +635,49 @@ fn parse_result(
         CoreTypeConcrete::Snapshot(info) => {
             parse_result(&info.ty, registry, return_ptr, ret_registers)
         }
+        CoreTypeConcrete::Const(info) => {
+            parse_result(&info.inner_ty, registry, return_ptr, ret_registers)
+        }
+
+        CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Secp256Point(_)) => Ok(Value::from_ptr(
+            return_ptr.to_native_assert_error("return pointer should be valid")?,
+            type_id,
+            registry,
+            true,
+        )?),
+        CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Sha256StateHandle(_)) => unsafe {
+            let ptr = return_ptr
+                .unwrap_or_else(|| NonNull::new_unchecked((&raw mut ret_registers[0]) as *mut ()));
+            Ok(Value::from_ptr(ptr, type_id, registry, true)?)
+        },
+        CoreTypeConcrete::Circuit(info) => match return_ptr {
+            Some(return_ptr) => Ok(Value::from_ptr(return_ptr, type_id, registry, true)?),
+            None if circuit::is_zst(info) => Ok(Value::Struct {
+                fields: Vec::new(),
+                debug_name: Some(type_info.info().long_id.to_string()),
+            }),
+            None => {
+                // Only the values which fit in the return registers (like `U96Guarantee`) are
+                // returned without a return pointer.
+                if type_info.layout(registry)?.size() > size_of_val(&ret_registers) {
+                    return Err(Error::ParseAttributeError);
+                }
+
+                let ptr = unsafe { NonNull::new_unchecked(ret_registers.as_mut_ptr().cast()) };
+                Ok(Value::from_ptr(ptr, type_id, registry, true)?)
+            }
+        },
+        CoreTypeConcrete::Uint128MulGuarantee(_) | CoreTypeConcrete::Coupon(_) => {
+            Ok(Value::Struct {
+                fields: Vec::new(),
+                debug_name: Some(type_info.info().long_id.to_string()),
+            })
+        }
+        CoreTypeConcrete::Uninitialized(_) => Ok(Value::Null),
 
         // Builtins are handled before the call to parse_result
         // and should not be reached here.
         CoreTypeConcrete::Bitwise(_)
-        | CoreTypeConcrete::Const(_)
         | CoreTypeConcrete::EcOp(_)
         | CoreTypeConcrete::GasBuiltin(_)
         | CoreTypeConcrete::BuiltinCosts(_)
@@ -625,18 +685,15 @@ fn parse_result(
         | CoreTypeConcrete::Pedersen(_)
         | CoreTypeConcrete::Poseidon(_)
         | CoreTypeConcrete::SegmentArena(_)
+        | CoreTypeConcrete::RangeCheck96(_)
         | CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_)) => {
             native_panic!("builtins should have been handled before")
         }
 
-        CoreTypeConcrete::Felt252DictEntry(_)
-        | CoreTypeConcrete::Span(_)
-        | CoreTypeConcrete::Uninitialized(_)
-        | CoreTypeConcrete::Coupon(_)
-        | CoreTypeConcrete::StarkNet(_)
-        | CoreTypeConcrete::Uint128MulGuarantee(_)
-        | CoreTypeConcrete::Circuit(_)
-        | CoreTypeConcrete::RangeCheck96(_) => native_panic!("not yet implemented as results"),
+        // Spans can't be lowered into MLIR, therefore they never reach an entry point.
+        CoreTypeConcrete::Span(_) => Err(Error::UnsupportedValueType(type_id.to_string())),
+
+        CoreTypeConcrete::Felt252DictEntry(_) => native_panic!("not yet implemented as results"),
         // 2.9.0
         CoreTypeConcrete::IntRange(_) => native_panic!("not yet implemented as results"),
     }
@@ -693,6 +750,20 @@ mod tests {
         program
     }
 
+    #[fixture]
+    fn recursive_program() -> Program {
+        let (_, program) = load_cairo! {
+            fn run_test(n: felt252) -> felt252 {
+                if n == 0 {
+                    0
+                } else {
+                    1 + run_test(n - 1)
+                }
+            }
+        };
+        program
+    }
+
     #[rstest]
     fn test_invoke_dynamic_aot_native_executor(program: Program) {
         let native_context = NativeContext::new();
@@ -729,6 +800,50 @@ mod tests {
         assert_eq!(result.return_value, Value::Felt252(Felt::from(42)));
     }
 
+    #[rstest]
+    fn test_invoke_dynamic_stack_overflow(recursive_program: Program) {
+        let native_context = NativeContext::new();
+        let module = native_context
+            .compile(&recursive_program, false, Some(Default::default()))
+            .expect("failed to compile context");
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::default())
+            .unwrap()
+            .with_stack_size(64 * 1024);
+
+        let entrypoint_function_id = &recursive_program
+            .funcs
+            .first()
+            .expect("should have a function")
+            .id;
+
+        let result = executor.invoke_dynamic(
+            entrypoint_function_id,
+            &[Value::Felt252(Felt::from(10_000_000))],
+            Some(u64::MAX),
+        );
+        assert!(matches!(result, Err(Error::StackOverflow)));
+
+        // The executor should still be usable after an overflow.
+        let result = executor
+            .invoke_dynamic(
+                entrypoint_function_id,
+                &[Value::Felt252(Felt::from(10))],
+                Some(u64::MAX),
+            )
+            .unwrap();
+        assert_eq!(
+            result.return_value,
+            Value::Enum {
+                tag: 0,
+                value: Box::new(Value::Struct {
+                    fields: vec![Value::Felt252(Felt::from(10))],
+                    debug_name: None,
+                }),
+                debug_name: None,
+            }
+        );
+    }
+
     #[rstest]
     fn test_invoke_contract_dynamic_aot(starknet_program: Program) {
         let native_context = NativeContext::new();
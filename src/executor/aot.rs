@@ -1,27 +1,46 @@
 use crate::{
+    dump::DumpConfig,
     error::Error,
     execution_result::{ContractExecutionResult, ExecutionResult},
+    executor::{
+        contract::BuildInfo,
+        guard::GuardConfig,
+        plan::{InvocationPlan, PlanCache},
+        typed::{invoke_typed, TypedArguments, TypedExecutionResult, TypedPlan, TypedReturns},
+    },
+    ffi::{TargetCpu, TargetOptions},
+    limits::{self, ResourceGuard},
     metadata::{
         felt252_dict::Felt252DictOverrides, gas::GasMetadata, runtime_bindings::setup_runtime,
     },
     module::NativeModule,
+    options::CompilationOptions,
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
-    utils::generate_function_name,
+    statistics::CompilationStats,
+    utils::{generate_function_name, program_hash, ProgramHash},
     values::Value,
-    OptLevel,
+    EphemeralSharedLib, OptLevel,
 };
 use cairo_lang_sierra::{
     extensions::core::{CoreLibfunc, CoreType},
     ids::{ConcreteTypeId, FunctionId},
-    program::FunctionSignature,
+    program::{FunctionSignature, Program},
     program_registry::ProgramRegistry,
 };
 use educe::Educe;
 use libc::c_void;
 use libloading::Library;
+use serde::{Deserialize, Serialize};
 use starknet_types_core::felt::Felt;
-use std::{io, mem::transmute};
-use tempfile::NamedTempFile;
+use std::{
+    any::TypeId,
+    collections::BTreeMap,
+    fs,
+    mem::transmute,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
 
 #[derive(Educe)]
 #[educe(Debug)]
@@ -33,6 +52,38 @@ pub struct AotNativeExecutor {
 
     gas_metadata: GasMetadata,
     dict_overrides: Felt252DictOverrides,
+
+    guard_config: GuardConfig,
+    #[educe(Debug(ignore))]
+    plans: PlanCache<FunctionId, InvocationPlan>,
+    #[educe(Debug(ignore))]
+    typed_plans: PlanCache<(FunctionId, TypeId), TypedPlan>,
+
+    /// The path to the shared library, required to save the executor.
+    library_path: Option<PathBuf>,
+    /// How the shared library was built, required to save the executor.
+    build: Option<(BuildInfo, TargetCpu)>,
+    /// Keeps the in-memory shared library alive, when not loaded from a file.
+    shared_lib: Option<EphemeralSharedLib>,
+    /// Only available when compiled by [`from_native_module`](Self::from_native_module).
+    compilation_stats: Option<CompilationStats>,
+}
+
+/// Everything required to load an [`AotNativeExecutor`] besides the shared library itself.
+///
+/// Saved as a JSON file alongside the shared library (ex. `program.json` for `program.so`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeProgramInfo {
+    pub program: Program,
+    /// The initial gas cost of each function, as returned by
+    /// [`GasMetadata::initial_required_gas_for_entry_points`].
+    pub function_costs: BTreeMap<u64, BTreeMap<u64, u64>>,
+    pub dup_overrides: BTreeMap<u64, String>,
+    pub drop_overrides: BTreeMap<u64, String>,
+    /// How the shared library was built.
+    pub build: BuildInfo,
+    /// The CPU the shared library was compiled for.
+    pub cpu: TargetCpu,
 }
 
 unsafe impl Send for AotNativeExecutor {}
@@ -50,6 +101,13 @@ impl AotNativeExecutor {
             registry,
             gas_metadata,
             dict_overrides,
+            guard_config: GuardConfig::default(),
+            plans: PlanCache::default(),
+            typed_plans: PlanCache::default(),
+            library_path: None,
+            build: None,
+            shared_lib: None,
+            compilation_stats: None,
         };
 
         setup_runtime(|name| executor.find_symbol_ptr(name));
@@ -65,20 +123,159 @@ impl AotNativeExecutor {
             mut metadata,
         } = module;
 
-        let library_path = NamedTempFile::new()?
-            .into_temp_path()
-            .keep()
-            .map_err(io::Error::from)?;
-
-        let object_data = crate::module_to_object(&module, opt_level)?;
-        crate::object_to_shared_lib(&object_data, &library_path)?;
+        let mut stats = metadata.remove::<CompilationStats>().unwrap_or_default();
+        // The time and memory used to generate the code are limited on their own.
+        let guard = metadata
+            .remove::<ResourceGuard>()
+            .map(ResourceGuard::restart);
+        let options = metadata.remove::<CompilationOptions>().unwrap_or_default();
+        let target = metadata.remove::<TargetOptions>().unwrap_or_default();
+        target.check_host()?;
+        let build = metadata
+            .remove::<ProgramHash>()
+            .map(|ProgramHash(hash)| BuildInfo::new(hash, opt_level, &target))
+            .transpose()?
+            .map(|build| (build, target.target_cpu()));
+        let objects = crate::ffi::module_to_objects_with_stats(
+            &module,
+            opt_level,
+            Some(&target),
+            &options,
+            &mut stats,
+            guard.as_ref(),
+        )?;
+
+        // When dumping the objects, the library linked from them is loaded instead to keep the
+        // debug info.
+        let pre_linking_instant = Instant::now();
+        let dumped_library = match metadata.remove::<DumpConfig>() {
+            Some(dump) => dump.dump_objects(&objects)?,
+            None => None,
+        };
+        let (shared_lib, library_path) = match dumped_library {
+            Some(library_path) => (None, library_path),
+            None => {
+                let shared_lib = crate::objects_to_ephemeral_shared_lib(&objects, &options)?;
+                let library_path = shared_lib.path();
+                (Some(shared_lib), library_path)
+            }
+        };
+        stats.linking = pre_linking_instant.elapsed();
+        limits::check(guard.as_ref())?;
+        stats.record_peak_memory();
 
-        Ok(Self::new(
+        let mut executor = Self::new(
             unsafe { Library::new(&library_path)? },
             registry,
             metadata.remove().ok_or(Error::MissingMetadata)?,
             metadata.remove().unwrap_or_default(),
-        ))
+        );
+        executor.library_path = Some(library_path);
+        executor.build = build;
+        executor.shared_lib = shared_lib;
+        executor.compilation_stats = Some(stats);
+
+        Ok(executor)
+    }
+
+    /// The statistics of the program's compilation, if it was compiled by this executor.
+    pub fn compilation_stats(&self) -> Option<&CompilationStats> {
+        self.compilation_stats.as_ref()
+    }
+
+    /// Save the compiled program into the given path, so that it can be reloaded later on using
+    /// [`load`](Self::load).
+    ///
+    /// The Sierra `program` and its metadata are saved alongside the shared library. If the given
+    /// path is "program.so", then "program.json" will be saved at the same location.
+    ///
+    /// The `program` must be the one the executor was compiled from (for modules compiled by
+    /// [`NativeContext::compile_reachable`](crate::context::NativeContext::compile_reachable),
+    /// the reachable subprogram), otherwise [`Error::ProgramMismatch`] is returned.
+    ///
+    /// Only executors created by [`from_native_module`](Self::from_native_module) or
+    /// [`load`](Self::load) can be saved.
+    pub fn save(&self, path: impl AsRef<Path>, program: &Program) -> Result<(), Error> {
+        let path = path.as_ref();
+        let (Some(library_path), Some((build, cpu))) = (&self.library_path, &self.build) else {
+            return Err(Error::MissingMetadata);
+        };
+        if program_hash(program)? != build.program_hash {
+            return Err(Error::ProgramMismatch);
+        }
+
+        let (dup_overrides, drop_overrides) = self.dict_overrides.symbols();
+        let program_info = NativeProgramInfo {
+            program: program.clone(),
+            function_costs: self.gas_metadata.initial_required_gas_for_entry_points()?,
+            dup_overrides,
+            drop_overrides,
+            build: build.clone(),
+            cpu: cpu.clone(),
+        };
+
+        if library_path != path {
+            fs::copy(library_path, path)?;
+        }
+        fs::write(
+            path.with_extension("json"),
+            serde_json::to_string(&program_info)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Load a program previously saved using [`save`](Self::save).
+    ///
+    /// The given path is the shared library's, the Sierra program and its metadata are read from
+    /// the JSON file alongside it.
+    ///
+    /// Libraries built by a different version of cairo-native or for another platform are refused
+    /// with [`Error::IncompatibleContract`], and the ones compiled for CPU features the host
+    /// doesn't support with [`Error::UnsupportedCpu`]. They should be recompiled.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        let program_info: NativeProgramInfo =
+            serde_json::from_str(&fs::read_to_string(path.with_extension("json"))?)?;
+        program_info.build.check_compatibility()?;
+        program_info.cpu.check_host()?;
+        let library = unsafe { Library::new(path)? };
+
+        let mut executor = Self::new(
+            library,
+            ProgramRegistry::new(&program_info.program)?,
+            GasMetadata::from_function_costs(&program_info.function_costs)?,
+            Felt252DictOverrides::from_symbols(
+                program_info.dup_overrides,
+                program_info.drop_overrides,
+            ),
+        );
+        executor.library_path = Some(path.to_path_buf());
+        executor.build = Some((program_info.build, program_info.cpu));
+
+        Ok(executor)
+    }
+
+    /// Run the programs on a dedicated stack of the given size, protected by a guard region.
+    ///
+    /// Overflowing the stack will make the invocation return [`Error::StackOverflow`] instead of
+    /// crashing the process. Overflows raised elsewhere, like in the runtime or the syscall
+    /// handler, still crash it since their frames can't be abandoned safely.
+    pub fn with_stack_size(mut self, stack_size: usize) -> Self {
+        self.guard_config.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Catch the faults (invalid memory accesses, traps...) raised by the programs'
+    /// compiled code. Faults raised elsewhere, like in the runtime or the syscall handler, still
+    /// reach the process' signal handlers.
+    ///
+    /// A fault will make the invocation return [`Error::NativeFault`] instead of crashing the
+    /// process. Anything allocated by the aborted execution is leaked.
+    pub fn with_fault_isolation(mut self) -> Self {
+        self.guard_config.catch_faults = true;
+        self
     }
 
     pub fn invoke_dynamic(
@@ -94,12 +291,13 @@ impl AotNativeExecutor {
 
         super::invoke_dynamic(
             &self.registry,
-            self.find_function_ptr(function_id)?,
-            self.extract_signature(function_id)?,
+            function_id,
+            &self.plan(function_id)?,
             args,
             available_gas,
             Option::<DummySyscallHandler>::None,
             self.build_find_dict_overrides(),
+            self.guard_config,
         )
     }
 
@@ -117,12 +315,65 @@ impl AotNativeExecutor {
 
         super::invoke_dynamic(
             &self.registry,
-            self.find_function_ptr(function_id)?,
-            self.extract_signature(function_id)?,
+            function_id,
+            &self.plan(function_id)?,
             args,
             available_gas,
             Some(syscall_handler),
             self.build_find_dict_overrides(),
+            self.guard_config,
+        )
+    }
+
+    /// Invoke a function using statically typed arguments and return values instead of
+    /// [`Value`]s.
+    ///
+    /// Only the non-builtin arguments and return values are given, as tuples. Their types are
+    /// checked against the function's signature before invoking it.
+    pub fn invoke<A: TypedArguments, R: TypedReturns>(
+        &self,
+        function_id: &FunctionId,
+        args: A,
+        gas: Option<u64>,
+    ) -> Result<TypedExecutionResult<R>, Error> {
+        let available_gas = self
+            .gas_metadata
+            .get_initial_available_gas(function_id, gas)
+            .map_err(crate::error::Error::GasMetadataError)?;
+
+        let (plan, typed_plan) = self.typed_plan::<A, R>(function_id)?;
+        invoke_typed(
+            function_id,
+            &plan,
+            &typed_plan,
+            &args,
+            available_gas,
+            Option::<DummySyscallHandler>::None,
+            self.guard_config,
+        )
+    }
+
+    pub fn invoke_with_syscall_handler<A: TypedArguments, R: TypedReturns>(
+        &self,
+        function_id: &FunctionId,
+        args: A,
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<TypedExecutionResult<R>, Error> {
+        let available_gas = self
+            .gas_metadata
+            .get_initial_available_gas(function_id, gas)
+            .map_err(crate::error::Error::GasMetadataError)?;
+
+        let (plan, typed_plan) = self.typed_plan::<A, R>(function_id)?;
+        invoke_typed(
+            function_id,
+            &plan,
+            &typed_plan,
+            &args,
+            available_gas,
+            Some(syscall_handler),
+            self.guard_config,
         )
     }
 
@@ -140,8 +391,8 @@ impl AotNativeExecutor {
 
         ContractExecutionResult::from_execution_result(super::invoke_dynamic(
             &self.registry,
-            self.find_function_ptr(function_id)?,
-            self.extract_signature(function_id)?,
+            function_id,
+            &self.plan(function_id)?,
             &[Value::Struct {
                 fields: vec![Value::Array(
                     args.iter().cloned().map(Value::Felt252).collect(),
@@ -151,6 +402,7 @@ impl AotNativeExecutor {
             available_gas,
             Some(syscall_handler),
             self.build_find_dict_overrides(),
+            self.guard_config,
         )?)
     }
 
@@ -177,6 +429,33 @@ impl AotNativeExecutor {
         }
     }
 
+    /// Return the invocation plan of the function, building it on its first invocation.
+    fn plan(&self, function_id: &FunctionId) -> Result<Arc<InvocationPlan>, Error> {
+        self.plans.get_or_try_insert_with(function_id, || {
+            InvocationPlan::new(
+                &self.registry,
+                self.find_function_ptr(function_id)?,
+                self.extract_signature(function_id)?,
+            )
+        })
+    }
+
+    /// Return the invocation plan of the function along with the outcome of checking its
+    /// signature against `A` and `R`, building them on their first use.
+    fn typed_plan<A: TypedArguments, R: TypedReturns>(
+        &self,
+        function_id: &FunctionId,
+    ) -> Result<(Arc<InvocationPlan>, Arc<TypedPlan>), Error> {
+        let plan = self.plan(function_id)?;
+        let typed_plan = self
+            .typed_plans
+            .get_or_try_insert_with(&(function_id.clone(), TypeId::of::<(A, R)>()), || {
+                TypedPlan::new::<A, R>(&self.registry, &plan)
+            })?;
+
+        Ok((plan, typed_plan))
+    }
+
     fn extract_signature(&self, function_id: &FunctionId) -> Result<&FunctionSignature, Error> {
         Ok(&self.registry.get_function(function_id)?.signature)
     }
@@ -211,6 +490,7 @@ mod tests {
     use super::*;
     use crate::{
         context::NativeContext,
+        error::IncompatibleContractError,
         starknet_stub::StubSyscallHandler,
         utils::test::{load_cairo, load_starknet},
     };
@@ -346,4 +626,71 @@ mod tests {
 
         assert_eq!(result.return_values, vec![Felt::from(42)]);
     }
+
+    #[rstest]
+    fn test_save_and_load(program: Program) {
+        let native_context = NativeContext::new();
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .expect("failed to compile context");
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::Default).unwrap();
+
+        // The second function in the program is `get_block_hash`.
+        let entrypoint_function_id = &program.funcs.get(1).expect("should have a function").id;
+        let expected_result = executor
+            .invoke_dynamic_with_syscall_handler(
+                entrypoint_function_id,
+                &[],
+                Some(u64::MAX),
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("program.so");
+        executor.save(&path, &program).unwrap();
+        assert!(path.with_extension("json").exists());
+
+        let executor = AotNativeExecutor::load(&path).unwrap();
+        let result = executor
+            .invoke_dynamic_with_syscall_handler(
+                entrypoint_function_id,
+                &[],
+                Some(u64::MAX),
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert_eq!(result, expected_result);
+
+        // A loaded executor can be saved again, but only along with its program.
+        executor
+            .save(temp_dir.path().join("copy.so"), &program)
+            .unwrap();
+        let (_, other_program) = load_cairo! {
+            fn run_test() -> felt252 {
+                24
+            }
+        };
+        assert!(matches!(
+            executor.save(temp_dir.path().join("other.so"), &other_program),
+            Err(Error::ProgramMismatch)
+        ));
+
+        // Libraries built by other versions are refused.
+        let mut program_info: NativeProgramInfo =
+            serde_json::from_str(&fs::read_to_string(path.with_extension("json")).unwrap())
+                .unwrap();
+        program_info.build.native_version = "0.0.0".to_string();
+        fs::write(
+            path.with_extension("json"),
+            serde_json::to_string(&program_info).unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            AotNativeExecutor::load(&path),
+            Err(Error::IncompatibleContract(
+                IncompatibleContractError::NativeVersion { .. }
+            ))
+        ));
+    }
 }
@@ -34,18 +34,30 @@
 use crate::{
     arch::AbiArgument,
     context::NativeContext,
-    error::{panic::ToNativeAssertError, Error, Result},
+    error::{panic::ToNativeAssertError, Error, IncompatibleContractError, Result},
     execution_result::{BuiltinStats, ContractExecutionResult},
-    executor::invoke_trampoline,
-    metadata::{gas::MetadataComputationConfig, runtime_bindings::setup_runtime},
+    executor::{
+        guard::{run_guarded, FaultScope, GuardConfig},
+        invoke_trampoline, pad_invoke_data,
+        plan::PlanCache,
+        read_value, record_builtin_stat,
+    },
+    ffi::{get_target_triple, normalize_target_triple},
+    limits::{CompilationLimits, ResourceGuard},
+    metadata::{
+        gas::MetadataComputationConfig,
+        runtime_bindings::{setup_runtime, RUNTIME_ABI_VERSION},
+    },
     module::NativeModule,
+    options::CompilationOptions,
     starknet::{handler::StarknetSyscallHandlerCallbacks, StarknetSyscallHandler},
+    statistics::CompilationStats,
     types::TypeBuilder,
     utils::{
         decode_error_message, generate_function_name, get_integer_layout, libc_free, libc_malloc,
-        BuiltinCosts,
+        program_hash, BuiltinCosts,
     },
-    OptLevel,
+    EphemeralSharedLib, OptLevel, TargetCpu, TargetOptions,
 };
 use bumpalo::Bump;
 use cairo_lang_sierra::{
@@ -75,8 +87,8 @@ use std::{
     path::{Path, PathBuf},
     ptr::NonNull,
     sync::Arc,
+    time::Instant,
 };
-use tempfile::NamedTempFile;
 
 /// Please look at the [module level docs](self).
 #[derive(Educe, Clone)]
@@ -86,17 +98,116 @@ pub struct AotContractExecutor {
     library: Arc<Library>,
     path: PathBuf,
     contract_info: NativeContractInfo,
+    guard_config: GuardConfig,
+    #[educe(Debug(ignore))]
+    plans: PlanCache<Felt, EntryPointPlan>,
+    /// Keeps the in-memory shared library alive, when not loaded from a file.
+    shared_lib: Option<Arc<EphemeralSharedLib>>,
+    /// Only available when compiled by this executor, not when loaded from a file.
+    compilation_stats: Option<CompilationStats>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct NativeContractInfo {
     pub version: ContractInfoVersion,
     pub entry_points: BTreeMap<Felt, EntryPointInfo>,
+    /// The CPU the library was compiled for. Missing in libraries built by older versions.
+    #[serde(default)]
+    pub cpu: Option<TargetCpu>,
+    /// How the library was built. Present since [`ContractInfoVersion::V1`].
+    #[serde(default)]
+    pub build: Option<BuildInfo>,
+}
+
+impl NativeContractInfo {
+    /// Check whether the library can be loaded by this version of cairo-native on this host.
+    ///
+    /// Libraries without a [`BuildInfo`], like the ones saved with a [`ContractInfoVersion::V0`]
+    /// sidecar, are refused with [`IncompatibleContractError::MissingBuildInfo`]: nothing tells
+    /// which compiler and runtime ABI they were built with, so they must be recompiled.
+    pub fn check_compatibility(&self) -> std::result::Result<(), IncompatibleContractError> {
+        match &self.build {
+            Some(build) => build.check_compatibility(),
+            None => Err(IncompatibleContractError::MissingBuildInfo(
+                self.version.clone(),
+            )),
+        }
+    }
+
+    /// Whether the library was compiled from the given program.
+    pub fn is_compiled_from(&self, program: &Program) -> Result<bool> {
+        let program_hash = program_hash(program)?;
+        Ok(self
+            .build
+            .as_ref()
+            .is_some_and(|x| x.program_hash == program_hash))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub enum ContractInfoVersion {
+    /// Only the entry points. Libraries saved with it can't be loaded anymore, since there's no
+    /// way to check they're compatible (see [`NativeContractInfo::check_compatibility`]).
     V0,
+    /// Adds the [`BuildInfo`].
+    V1,
+}
+
+/// Information about how a library was built, used to detect stale or incompatible artifacts.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The version of cairo-native which compiled the library.
+    pub native_version: String,
+    /// The hash of the Sierra program, as returned by [`program_hash`].
+    pub program_hash: String,
+    pub opt_level: OptLevel,
+    /// The normalized target triple.
+    pub target_triple: String,
+    /// The [`RUNTIME_ABI_VERSION`] the library was compiled against.
+    pub runtime_abi_version: u32,
+}
+
+impl BuildInfo {
+    /// Describe a library being compiled by this version of cairo-native.
+    pub(crate) fn new(
+        program_hash: String,
+        opt_level: OptLevel,
+        target: &TargetOptions,
+    ) -> Result<Self> {
+        Ok(Self {
+            native_version: env!("CARGO_PKG_VERSION").to_string(),
+            program_hash,
+            opt_level,
+            target_triple: normalize_target_triple(&get_target_triple(target))?,
+            runtime_abi_version: RUNTIME_ABI_VERSION,
+        })
+    }
+
+    /// Check whether the library can be loaded by this version of cairo-native on this host.
+    pub fn check_compatibility(&self) -> std::result::Result<(), IncompatibleContractError> {
+        if self.native_version != env!("CARGO_PKG_VERSION") {
+            return Err(IncompatibleContractError::NativeVersion {
+                expected: env!("CARGO_PKG_VERSION").to_string(),
+                found: self.native_version.clone(),
+            });
+        }
+        if self.runtime_abi_version != RUNTIME_ABI_VERSION {
+            return Err(IncompatibleContractError::RuntimeAbiVersion {
+                expected: RUNTIME_ABI_VERSION,
+                found: self.runtime_abi_version,
+            });
+        }
+
+        let host_triple = get_target_triple(&TargetOptions::default());
+        if normalize_target_triple(&self.target_triple).ok().as_ref() != Some(&host_triple) {
+            return Err(IncompatibleContractError::TargetTriple {
+                expected: host_triple,
+                found: self.target_triple.clone(),
+            });
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -125,32 +236,124 @@ impl BuiltinType {
     pub const fn size_in_bytes(&self) -> usize {
         size_of::<u64>()
     }
+
+    /// Return the builtin represented by the given type, if any.
+    pub(crate) fn from_type_info(type_info: &CoreTypeConcrete) -> Option<Self> {
+        Some(match type_info {
+            CoreTypeConcrete::Bitwise(_) => BuiltinType::Bitwise,
+            CoreTypeConcrete::EcOp(_) => BuiltinType::EcOp,
+            CoreTypeConcrete::RangeCheck(_) => BuiltinType::RangeCheck,
+            CoreTypeConcrete::Pedersen(_) => BuiltinType::Pedersen,
+            CoreTypeConcrete::Poseidon(_) => BuiltinType::Poseidon,
+            CoreTypeConcrete::BuiltinCosts(_) => BuiltinType::BuiltinCosts,
+            CoreTypeConcrete::SegmentArena(_) => BuiltinType::SegmentArena,
+            CoreTypeConcrete::RangeCheck96(_) => BuiltinType::RangeCheck96,
+            CoreTypeConcrete::Circuit(CircuitTypeConcrete::AddMod(_)) => BuiltinType::CircuitAdd,
+            CoreTypeConcrete::Circuit(CircuitTypeConcrete::MulMod(_)) => BuiltinType::CircuitMul,
+            CoreTypeConcrete::GasBuiltin(_) => BuiltinType::Gas,
+            CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_)) => BuiltinType::System,
+            _ => return None,
+        })
+    }
 }
 
+/// The precomputed information required to run an entry point, built the first time it's run.
+#[derive(Debug)]
+struct EntryPointPlan {
+    function_id: FunctionId,
+    function_ptr: *const c_void,
+    fault_scope: FaultScope,
+    builtins: Vec<BuiltinType>,
+    return_layout: Layout,
+}
+
+unsafe impl Send for EntryPointPlan {}
+unsafe impl Sync for EntryPointPlan {}
+
 impl AotContractExecutor {
-    /// Compile and load a program using a temporary shared library.
+    /// Compile and load a program using an ephemeral shared library.
+    ///
+    /// On Linux the shared library is kept in memory, without touching the filesystem.
     pub fn new(
         program: &Program,
         entry_points: &ContractEntryPoints,
         sierra_version: VersionId,
         opt_level: OptLevel,
     ) -> Result<Self> {
-        let output_path = NamedTempFile::new()?
-            .into_temp_path()
-            .keep()
-            .to_native_assert_error("can only fail on windows")?;
+        Self::new_with_limits(
+            program,
+            entry_points,
+            sierra_version,
+            opt_level,
+            &CompilationLimits::default(),
+        )
+    }
+
+    /// Like [`new`](Self::new), but refuses to compile programs exceeding the given `limits` with
+    /// [`Error::ResourceLimitExceeded`].
+    pub fn new_with_limits(
+        program: &Program,
+        entry_points: &ContractEntryPoints,
+        sierra_version: VersionId,
+        opt_level: OptLevel,
+        limits: &CompilationLimits,
+    ) -> Result<Self> {
+        Self::new_with_options(
+            program,
+            entry_points,
+            sierra_version,
+            opt_level,
+            limits,
+            &CompilationOptions::default(),
+            &TargetOptions::default(),
+        )
+    }
 
-        let executor = Self::new_into(
+    /// Like [`new_with_limits`](Self::new_with_limits), but compiling and linking with the given
+    /// `options` (for example, to split the code into many codegen units or to use the built-in
+    /// linker) for the given `target`.
+    ///
+    /// The code is loaded right away, therefore the `target` must be the host's triple (but it may
+    /// use a portable CPU, see [`TargetOptions::portable`]).
+    pub fn new_with_options(
+        program: &Program,
+        entry_points: &ContractEntryPoints,
+        sierra_version: VersionId,
+        opt_level: OptLevel,
+        limits: &CompilationLimits,
+        options: &CompilationOptions,
+        target: &TargetOptions,
+    ) -> Result<Self> {
+        target.check_host()?;
+        let (objects, contract_info, mut stats) = Self::compile(
             program,
             entry_points,
             sierra_version,
-            output_path,
             opt_level,
-        )?
-        .to_native_assert_error("temporary contract path collision")?;
+            Some(target),
+            limits,
+            options,
+        )?;
+
+        let pre_linking_instant = Instant::now();
+        let shared_lib = crate::objects_to_ephemeral_shared_lib(&objects, options)?;
+        stats.linking = pre_linking_instant.elapsed();
+        stats.record_peak_memory();
+        let path = shared_lib.path();
+        let library = Arc::new(unsafe { Library::new(&path)? });
+
+        let executor = Self {
+            library,
+            path,
+            contract_info,
+            guard_config: GuardConfig::default(),
+            plans: PlanCache::default(),
+            shared_lib: Some(Arc::new(shared_lib)),
+            compilation_stats: Some(stats),
+        };
+
+        setup_runtime(|x| executor.find_symbol_ptr(x));
 
-        fs::remove_file(&executor.path)?;
-        fs::remove_file(executor.path.with_extension("json"))?;
         Ok(executor)
     }
 
@@ -173,7 +376,86 @@ impl AotContractExecutor {
             None => return Ok(None),
         };
 
-        let context = NativeContext::new();
+        let (objects, contract_info, mut stats) = Self::compile(
+            program,
+            entry_points,
+            sierra_version,
+            opt_level,
+            None,
+            &CompilationLimits::default(),
+            &CompilationOptions::default(),
+        )?;
+
+        // Build the shared library into the lockfile, to avoid using a tmp file.
+        let pre_linking_instant = Instant::now();
+        crate::objects_to_shared_lib(&objects, &lock_file.0, &CompilationOptions::default())?;
+        stats.linking = pre_linking_instant.elapsed();
+        stats.record_peak_memory();
+
+        // Write the contract info.
+        fs::write(
+            output_path.with_extension("json"),
+            serde_json::to_string(&contract_info)?,
+        )?;
+
+        // Atomically move the built shared library to the correct path. This will avoid data races
+        // when loading contracts.
+        lock_file.rename(&output_path)?;
+
+        Ok(Self::from_path(output_path)?.map(|executor| Self {
+            compilation_stats: Some(stats),
+            ..executor
+        }))
+    }
+
+    /// Compile a program into a shared library for the given target, without loading it.
+    ///
+    /// The contract info is saved alongside the shared library, so that it can be loaded using
+    /// `AotContractExecutor::from_path` on a machine matching the `target`.
+    pub fn compile_into(
+        program: &Program,
+        entry_points: &ContractEntryPoints,
+        sierra_version: VersionId,
+        output_path: impl Into<PathBuf>,
+        opt_level: OptLevel,
+        target: &TargetOptions,
+    ) -> Result<()> {
+        let output_path = output_path.into();
+
+        let (objects, contract_info, _) = Self::compile(
+            program,
+            entry_points,
+            sierra_version,
+            opt_level,
+            Some(target),
+            &CompilationLimits::default(),
+            &CompilationOptions::default(),
+        )?;
+
+        crate::objects_to_shared_lib(&objects, &output_path, &CompilationOptions::default())?;
+        fs::write(
+            output_path.with_extension("json"),
+            serde_json::to_string(&contract_info)?,
+        )?;
+
+        Ok(())
+    }
+
+    /// Compile the program into objects, and generate the contract info required to run it.
+    fn compile(
+        program: &Program,
+        entry_points: &ContractEntryPoints,
+        sierra_version: VersionId,
+        opt_level: OptLevel,
+        target: Option<&TargetOptions>,
+        limits: &CompilationLimits,
+        options: &CompilationOptions,
+    ) -> Result<(Vec<Vec<u8>>, NativeContractInfo, CompilationStats)> {
+        let target = target.cloned().unwrap_or_default();
+        let context = NativeContext::new()
+            .with_limits(limits.clone())
+            .with_options(options.clone())
+            .with_target(target.clone());
 
         let no_eq_solver = match sierra_version.major.cmp(&1) {
             Ordering::Less => false,
@@ -183,7 +465,9 @@ impl AotContractExecutor {
 
         // Compile the Sierra program.
         let NativeModule {
-            module, registry, ..
+            module,
+            registry,
+            mut metadata,
         } = context.compile(
             program,
             true,
@@ -223,27 +507,7 @@ impl AotContractExecutor {
                 .map(|x| registry.get_type(&x.ty).unwrap())
                 .take_while(|ty| ty.is_builtin())
                 .filter(|ty| !ty.is_zst(&registry).unwrap())
-                .map(|ty| match ty {
-                    CoreTypeConcrete::Bitwise(_) => BuiltinType::Bitwise,
-                    CoreTypeConcrete::EcOp(_) => BuiltinType::EcOp,
-                    CoreTypeConcrete::RangeCheck(_) => BuiltinType::RangeCheck,
-                    CoreTypeConcrete::Pedersen(_) => BuiltinType::Pedersen,
-                    CoreTypeConcrete::Poseidon(_) => BuiltinType::Poseidon,
-                    CoreTypeConcrete::BuiltinCosts(_) => BuiltinType::BuiltinCosts,
-                    CoreTypeConcrete::SegmentArena(_) => BuiltinType::SegmentArena,
-                    CoreTypeConcrete::RangeCheck96(_) => BuiltinType::RangeCheck96,
-                    CoreTypeConcrete::Circuit(CircuitTypeConcrete::AddMod(_)) => {
-                        BuiltinType::CircuitAdd
-                    }
-                    CoreTypeConcrete::Circuit(CircuitTypeConcrete::MulMod(_)) => {
-                        BuiltinType::CircuitMul
-                    }
-                    CoreTypeConcrete::GasBuiltin(_) => BuiltinType::Gas,
-                    CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::System(_)) => {
-                        BuiltinType::System
-                    }
-                    _ => unreachable!("not a builtin"),
-                })
+                .map(|ty| BuiltinType::from_type_info(ty).expect("not a builtin"))
                 .collect();
 
             Ok((
@@ -256,25 +520,26 @@ impl AotContractExecutor {
         })
         .collect::<Result<BTreeMap<_, _>>>()?;
 
-        let object_data = crate::module_to_object(&module, opt_level)?;
-
-        // Build the shared library into the lockfile, to avoid using a tmp file.
-        crate::object_to_shared_lib(&object_data, &lock_file.0)?;
-
-        // Write the contract info.
-        fs::write(
-            output_path.with_extension("json"),
-            serde_json::to_string(&NativeContractInfo {
-                version: ContractInfoVersion::V0,
-                entry_points: entry_point_mappings,
-            })?,
+        let mut stats = metadata.remove::<CompilationStats>().unwrap_or_default();
+        let objects = crate::ffi::module_to_objects_with_stats(
+            &module,
+            opt_level,
+            Some(&target),
+            &metadata.remove::<CompilationOptions>().unwrap_or_default(),
+            &mut stats,
+            metadata.get::<ResourceGuard>(),
         )?;
 
-        // Atomically move the built shared library to the correct path. This will avoid data races
-        // when loading contracts.
-        lock_file.rename(&output_path)?;
-
-        Self::from_path(output_path)
+        Ok((
+            objects,
+            NativeContractInfo {
+                version: ContractInfoVersion::V1,
+                entry_points: entry_point_mappings,
+                cpu: Some(target.target_cpu()),
+                build: Some(BuildInfo::new(program_hash(program)?, opt_level, &target)?),
+            },
+            stats,
+        ))
     }
 
     /// Load a program from a shared library.
@@ -282,19 +547,32 @@ impl AotContractExecutor {
     /// This function will check for the existence of a lockfile. If found, it'll return `Ok(None)`.
     /// When this happens, the user should wait until the lock is released, then try loading it
     /// again.
+    ///
+    /// Libraries built by a different version of cairo-native or for another platform, as well as
+    /// the ones saved without build info by older versions, are refused with
+    /// [`Error::IncompatibleContract`], and the ones compiled for CPU features the host doesn't
+    /// support with [`Error::UnsupportedCpu`]. They should be recompiled.
     pub fn from_path(path: impl Into<PathBuf>) -> Result<Option<Self>> {
         let path = path.into();
 
         // Note: Library should load first, otherwise there could theoretically be a race condition.
         //   See the `new_into` function's code for details.
         let library = Arc::new(unsafe { Library::new(&path)? });
-        let contract_info =
+        let contract_info: NativeContractInfo =
             serde_json::from_str(&fs::read_to_string(path.with_extension("json"))?)?;
+        contract_info.check_compatibility()?;
+        if let Some(cpu) = &contract_info.cpu {
+            cpu.check_host()?;
+        }
 
         let executor = Self {
             library,
             path,
             contract_info,
+            guard_config: GuardConfig::default(),
+            plans: PlanCache::default(),
+            shared_lib: None,
+            compilation_stats: None,
         };
 
         setup_runtime(|x| executor.find_symbol_ptr(x));
@@ -302,6 +580,37 @@ impl AotContractExecutor {
         Ok(Some(executor))
     }
 
+    /// The statistics of the contract's compilation, if it was compiled by this executor.
+    pub fn compilation_stats(&self) -> Option<&CompilationStats> {
+        self.compilation_stats.as_ref()
+    }
+
+    /// The information saved alongside the shared library.
+    pub fn contract_info(&self) -> &NativeContractInfo {
+        &self.contract_info
+    }
+
+    /// Run the entry points on a dedicated stack of the given size, protected by a guard region.
+    ///
+    /// Overflowing the stack will make [`run`](Self::run) return [`Error::StackOverflow`] instead
+    /// of crashing the process. Overflows raised elsewhere, like in the runtime or the syscall
+    /// handler, still crash it since their frames can't be abandoned safely.
+    pub fn with_stack_size(mut self, stack_size: usize) -> Self {
+        self.guard_config.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Catch the faults (invalid memory accesses, traps...) raised by the entry points'
+    /// compiled code. Faults raised elsewhere, like in the runtime or the syscall handler, still
+    /// reach the process' signal handlers.
+    ///
+    /// A fault will make [`run`](Self::run) return [`Error::NativeFault`] instead of crashing the
+    /// process. Anything allocated by the aborted execution is leaked.
+    pub fn with_fault_isolation(mut self) -> Self {
+        self.guard_config.catch_faults = true;
+        self
+    }
+
     /// Runs the entry point by the given selector.
     ///
     /// - selector: The selector of the entry point to run.
@@ -322,16 +631,28 @@ impl AotContractExecutor {
         let arena = Bump::new();
         let mut invoke_data = Vec::<u8>::new();
 
-        let function_id = FunctionId {
-            id: self
+        let plan = self.plans.get_or_try_insert_with(&selector, || {
+            let entry_point = self
                 .contract_info
                 .entry_points
                 .get(&selector)
-                .ok_or(Error::SelectorNotFound)?
-                .function_id,
-            debug_name: None,
-        };
-        let function_ptr = self.find_function_ptr(&function_id, true)?;
+                .ok_or(Error::SelectorNotFound)?;
+            let function_id = FunctionId::new(entry_point.function_id);
+
+            //  it can vary from contract to contract thats why we need to store/ load it.
+            let builtins_size: usize = entry_point.builtins.iter().map(|x| x.size_in_bytes()).sum();
+
+            let function_ptr = self.find_function_ptr(&function_id, true)?;
+            Ok(EntryPointPlan {
+                function_ptr,
+                fault_scope: FaultScope::of(function_ptr),
+                function_id,
+                builtins: entry_point.builtins.clone(),
+                // There is always a return ptr because contracts always return more than 1 thing
+                // (builtin counters, syscall, enum).
+                return_layout: Layout::from_size_align(128 + builtins_size, 16)?,
+            })
+        })?;
 
         let builtin_costs: [u64; 7] = builtin_costs.unwrap_or_default().into();
 
@@ -339,18 +660,7 @@ impl AotContractExecutor {
         let old_builtincosts_ptr =
             crate::runtime::cairo_native__set_costs_builtin(builtin_costs.as_ptr());
 
-        //  it can vary from contract to contract thats why we need to store/ load it.
-        let builtins_size: usize = self.contract_info.entry_points[&selector]
-            .builtins
-            .iter()
-            .map(|x| x.size_in_bytes())
-            .sum();
-
-        // There is always a return ptr because contracts always return more than 1 thing (builtin counters, syscall, enum)
-        let return_ptr = arena.alloc_layout(unsafe {
-            // 56 = size of enum
-            Layout::from_size_align_unchecked(128 + builtins_size, 16)
-        });
+        let return_ptr = arena.alloc_layout(plan.return_layout);
 
         return_ptr
             .as_ptr()
@@ -358,7 +668,7 @@ impl AotContractExecutor {
 
         let mut syscall_handler = StarknetSyscallHandlerCallbacks::new(&mut syscall_handler);
 
-        for b in &self.contract_info.entry_points[&selector].builtins {
+        for b in &plan.builtins {
             match b {
                 BuiltinType::Gas => {
                     gas.to_bytes(&mut invoke_data, |_| unreachable!())?;
@@ -426,17 +736,7 @@ impl AotContractExecutor {
             };
         }
 
-        // Pad invoke data to the 16 byte boundary avoid segfaults.
-        #[cfg(target_arch = "aarch64")]
-        const REGISTER_BYTES: usize = 64;
-        #[cfg(target_arch = "x86_64")]
-        const REGISTER_BYTES: usize = 48;
-        if invoke_data.len() > REGISTER_BYTES {
-            invoke_data.resize(
-                REGISTER_BYTES + (invoke_data.len() - REGISTER_BYTES).next_multiple_of(16),
-                0,
-            );
-        }
+        pad_invoke_data(&mut invoke_data);
 
         // Invoke the trampoline.
         #[cfg(target_arch = "x86_64")]
@@ -444,33 +744,33 @@ impl AotContractExecutor {
         #[cfg(target_arch = "aarch64")]
         let mut ret_registers = [0; 4];
 
-        unsafe {
+        let invoke = || unsafe {
             invoke_trampoline(
-                function_ptr,
+                plan.function_ptr,
                 invoke_data.as_ptr().cast(),
                 invoke_data.len() >> 3,
                 ret_registers.as_mut_ptr(),
             );
-        }
-
-        // Parse final gas.
-        unsafe fn read_value<T>(ptr: &mut NonNull<()>) -> &T {
-            let align_offset = ptr
-                .cast::<u8>()
-                .as_ptr()
-                .align_offset(std::mem::align_of::<T>());
-            let value_ptr = ptr.cast::<u8>().as_ptr().add(align_offset).cast::<T>();
+        };
+        let invoke_result = run_guarded(
+            self.guard_config,
+            plan.fault_scope,
+            plan.function_id.id,
+            invoke,
+        );
 
-            *ptr = NonNull::new_unchecked(value_ptr.add(1)).cast();
-            &*value_ptr
+        if let Err(e) = invoke_result {
+            crate::runtime::cairo_native__set_costs_builtin(old_builtincosts_ptr);
+            return Err(e);
         }
 
+        // Parse final gas.
         let mut remaining_gas = 0;
         let mut builtin_stats = BuiltinStats::default();
 
         let return_ptr = &mut return_ptr.cast();
 
-        for b in &self.contract_info.entry_points[&selector].builtins {
+        for b in &plan.builtins {
             match b {
                 BuiltinType::Gas => {
                     remaining_gas = unsafe { *read_value::<u64>(return_ptr) };
@@ -484,21 +784,7 @@ impl AotContractExecutor {
                 }
                 x => {
                     let value = unsafe { *read_value::<u64>(return_ptr) } as usize;
-
-                    match x {
-                        BuiltinType::Bitwise => builtin_stats.bitwise = value,
-                        BuiltinType::EcOp => builtin_stats.ec_op = value,
-                        BuiltinType::RangeCheck => builtin_stats.range_check = value,
-                        BuiltinType::SegmentArena => builtin_stats.segment_arena = value,
-                        BuiltinType::Poseidon => builtin_stats.poseidon = value,
-                        BuiltinType::Pedersen => builtin_stats.pedersen = value,
-                        BuiltinType::RangeCheck96 => builtin_stats.range_check_96 = value,
-                        BuiltinType::CircuitAdd => builtin_stats.circuit_add = value,
-                        BuiltinType::CircuitMul => builtin_stats.circuit_mul = value,
-                        BuiltinType::Gas => {}
-                        BuiltinType::System => {}
-                        BuiltinType::BuiltinCosts => {}
-                    }
+                    record_builtin_stat(&mut builtin_stats, x, value);
                 }
             }
         }
@@ -653,7 +939,9 @@ impl Drop for LockFile {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{starknet_stub::StubSyscallHandler, utils::test::load_starknet_contract};
+    use crate::{
+        starknet_stub::StubSyscallHandler, utils::test::load_starknet_contract, CpuBaseline,
+    };
     use cairo_lang_starknet_classes::contract_class::{
         version_id_from_serialized_sierra_program, ContractClass,
     };
@@ -900,4 +1188,124 @@ mod tests {
 
         assert_eq!(result.return_values, vec![]);
     }
+
+    #[rstest]
+    fn test_contract_executor_portable(starknet_program: ContractClass) {
+        let baseline = if cfg!(target_arch = "aarch64") {
+            CpuBaseline::Armv8A
+        } else {
+            CpuBaseline::X86_64
+        };
+
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&starknet_program.sierra_program).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contract.so");
+        AotContractExecutor::compile_into(
+            &starknet_program.extract_sierra_program().unwrap(),
+            &starknet_program.entry_points_by_type,
+            sierra_version,
+            &path,
+            OptLevel::Default,
+            &TargetOptions::portable(baseline).unwrap(),
+        )
+        .unwrap();
+
+        let executor = AotContractExecutor::from_path(&path).unwrap().unwrap();
+        assert_eq!(
+            executor.contract_info.cpu.as_ref().unwrap().name,
+            baseline.cpu_name()
+        );
+
+        let selector = starknet_program
+            .entry_points_by_type
+            .external
+            .last()
+            .unwrap()
+            .selector
+            .clone();
+        let result = executor
+            .run(
+                Felt::from(&selector),
+                &[2.into()],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap();
+        assert_eq!(result.return_values, vec![Felt::from(2), Felt::from(4)]);
+
+        // Pretend the library requires a feature the host doesn't have.
+        let mut contract_info = executor.contract_info.clone();
+        contract_info.cpu.as_mut().unwrap().features = "+not-a-real-feature".to_string();
+        fs::write(
+            path.with_extension("json"),
+            serde_json::to_string(&contract_info).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            AotContractExecutor::from_path(&path),
+            Err(Error::UnsupportedCpu { missing_features, .. })
+                if missing_features == ["not-a-real-feature"]
+        ));
+    }
+
+    #[rstest]
+    fn test_contract_executor_incompatible(starknet_program: ContractClass) {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&starknet_program.sierra_program).unwrap();
+        let program = starknet_program.extract_sierra_program().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contract.so");
+        let executor = AotContractExecutor::new_into(
+            &program,
+            &starknet_program.entry_points_by_type,
+            sierra_version,
+            &path,
+            OptLevel::None,
+        )
+        .unwrap()
+        .unwrap();
+
+        let contract_info = executor.contract_info().clone();
+        assert_eq!(contract_info.version, ContractInfoVersion::V1);
+        assert_eq!(
+            contract_info.build.as_ref().unwrap().opt_level,
+            OptLevel::None
+        );
+        assert!(contract_info.is_compiled_from(&program).unwrap());
+
+        let write_info = |contract_info: &NativeContractInfo| {
+            fs::write(
+                path.with_extension("json"),
+                serde_json::to_string(contract_info).unwrap(),
+            )
+            .unwrap();
+        };
+
+        let mut stale_info = contract_info.clone();
+        stale_info.build.as_mut().unwrap().native_version = "0.0.0".to_string();
+        write_info(&stale_info);
+        assert!(matches!(
+            AotContractExecutor::from_path(&path),
+            Err(Error::IncompatibleContract(
+                IncompatibleContractError::NativeVersion { .. }
+            ))
+        ));
+
+        let mut old_info = contract_info.clone();
+        old_info.version = ContractInfoVersion::V0;
+        old_info.build = None;
+        write_info(&old_info);
+        assert!(matches!(
+            AotContractExecutor::from_path(&path),
+            Err(Error::IncompatibleContract(
+                IncompatibleContractError::MissingBuildInfo(ContractInfoVersion::V0)
+            ))
+        ));
+
+        write_info(&contract_info);
+        assert!(AotContractExecutor::from_path(&path).unwrap().is_some());
+    }
 }
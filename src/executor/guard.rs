@@ -0,0 +1,787 @@
+//! # Guarded executions
+//!
+//! Native code may bring the whole process down when something goes wrong. For example, deeply
+//! recursive Cairo programs may exhaust the native stack of the thread that runs them, and bugs
+//! in the generated code (or in the runtime) may dereference invalid pointers or trap.
+//!
+//! To avoid that, executors can be configured (see [`GuardConfig`]) to run their programs:
+//!   - On a dedicated stack with a guard region at its end, which turns stack overflows into
+//!     [`Error::StackOverflow`].
+//!   - With fault isolation enabled, which turns the synchronous signals raised by the program's
+//!     code (`SIGSEGV`, `SIGBUS`, `SIGILL`, `SIGFPE` and `SIGTRAP`) into [`Error::NativeFault`].
+//!
+//! Only faults raised from the program's code are caught (see [`FaultScope`]). Faults raised
+//! anywhere else on the thread, like the runtime helpers, the syscall handler or the Rust code
+//! they call into, are bugs of the process itself and are forwarded to the previous handler. The
+//! same applies to stack overflows: aborting Rust frames without unwinding them would leak their
+//! locks, borrows and allocations, so overflows outside the program's code crash the process with
+//! the default action of the signal.
+//!
+//! `SIGABRT` is never caught: the generated code traps instead of calling `abort()`, therefore
+//! aborts always come from the runtime or from Rust code, and are raised from within the C library
+//! rather than from the program's code anyway.
+//!
+//! In both cases a signal handler redirects execution back into [`run_guarded`], which then
+//! reports the error.
+//!
+//! When an execution is aborted, the aborted frames are abandoned as they are: nothing they have
+//! allocated is freed and no destructors are run.
+
+use crate::error::{Error, Result};
+use libc::{c_int, c_void, siginfo_t};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::CStr,
+    io,
+    ops::Range,
+    ptr::{null_mut, NonNull},
+    sync::OnceLock,
+};
+
+extern "C" {
+    /// Switch to the stack at `stack_ptr` (if not null) and call `callback(data)` from there.
+    ///
+    /// Returns zero if the callback returned normally, or the number of the signal that aborted
+    /// it otherwise.
+    #[cfg_attr(not(target_os = "macos"), link_name = "_invoke_on_stack")]
+    fn invoke_on_stack(
+        callback: extern "C" fn(*mut c_void),
+        data: *mut c_void,
+        stack_ptr: *mut u8,
+        saved_sp: *mut *mut u8,
+    ) -> c_int;
+
+    /// Landing pad for aborted callbacks. Never called directly: the signal handler jumps into it.
+    #[cfg_attr(not(target_os = "macos"), link_name = "_invoke_on_stack_abort")]
+    fn invoke_on_stack_abort();
+}
+
+/// Minimum size of the guard region at the end of every execution stack.
+///
+/// The code generated by LLVM doesn't probe the stack, therefore a single function with a large
+/// frame could skip over a single guard page.
+const MIN_GUARD_SIZE: usize = 64 * 1024;
+
+/// Size of the alternate signal stack, in case the thread doesn't have one already.
+const ALT_STACK_SIZE: usize = 64 * 1024;
+
+/// Signals that may be raised when accessing the guard region.
+const GUARD_SIGNALS: [c_int; 2] = [libc::SIGSEGV, libc::SIGBUS];
+
+/// Signals caught when fault isolation is enabled, in addition to [`GUARD_SIGNALS`].
+const FAULT_SIGNALS: [c_int; 3] = [libc::SIGILL, libc::SIGFPE, libc::SIGTRAP];
+
+/// How to protect the process from the programs run by an executor.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct GuardConfig {
+    /// Run on a dedicated stack of this size.
+    pub stack_size: Option<usize>,
+    /// Catch synchronous faults raised by the execution.
+    pub catch_faults: bool,
+}
+
+impl GuardConfig {
+    pub const fn is_enabled(&self) -> bool {
+        self.stack_size.is_some() || self.catch_faults
+    }
+}
+
+/// The code whose faults are caught when fault isolation is enabled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum FaultScope {
+    /// The executable segments of the shared library the program was loaded from.
+    Library { start: usize, end: usize },
+    /// Code which doesn't belong to any object known to the dynamic linker, which is where the JIT
+    /// engine places the programs it compiles.
+    Unregistered,
+}
+
+impl FaultScope {
+    /// Return the scope of the code that contains the given function.
+    pub fn of(function_ptr: *const c_void) -> Self {
+        let base = unsafe {
+            let mut info = std::mem::zeroed::<libc::Dl_info>();
+            if libc::dladdr(function_ptr, &mut info) == 0 || info.dli_fbase.is_null() {
+                return Self::Unregistered;
+            }
+
+            info.dli_fbase as usize
+        };
+
+        match unsafe { text_range(base) } {
+            Some(Range { start, end }) => Self::Library { start, end },
+            None => Self::Unregistered,
+        }
+    }
+}
+
+/// A [`FaultScope`] resolved into address ranges before invoking the program, so that the signal
+/// handler only has to compare addresses (the dynamic linker's functions aren't async-signal-safe).
+struct ResolvedScope {
+    scope: FaultScope,
+    /// The executable segments of every loaded object, only required by
+    /// [`FaultScope::Unregistered`].
+    loaded_code: Vec<Range<usize>>,
+}
+
+impl ResolvedScope {
+    fn new(scope: FaultScope) -> Self {
+        Self {
+            scope,
+            loaded_code: match scope {
+                FaultScope::Library { .. } => Vec::new(),
+                FaultScope::Unregistered => loaded_code_ranges(),
+            },
+        }
+    }
+
+    /// Whether the code at `pc` is within the scope.
+    fn contains(&self, pc: usize) -> bool {
+        match self.scope {
+            FaultScope::Library { start, end } => (start..end).contains(&pc),
+            FaultScope::Unregistered => !self.loaded_code.iter().any(|x| x.contains(&pc)),
+        }
+    }
+}
+
+/// Return the ranges of the executable segments of every object known to the dynamic linker.
+#[cfg(target_os = "linux")]
+fn loaded_code_ranges() -> Vec<Range<usize>> {
+    unsafe extern "C" fn visit(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        data: *mut c_void,
+    ) -> c_int {
+        let (info, ranges) = (&*info, &mut *data.cast::<Vec<Range<usize>>>());
+        if info.dlpi_phdr.is_null() {
+            return 0;
+        }
+
+        let program_headers = std::slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum.into());
+        ranges.extend(
+            program_headers
+                .iter()
+                .filter(|x| x.p_type == libc::PT_LOAD && x.p_flags & libc::PF_X != 0)
+                .map(|x| {
+                    let start = info.dlpi_addr as usize + x.p_vaddr as usize;
+                    start..start + x.p_memsz as usize
+                }),
+        );
+
+        0
+    }
+
+    let mut ranges = Vec::new();
+    unsafe {
+        libc::dl_iterate_phdr(Some(visit), (&raw mut ranges).cast());
+    }
+
+    ranges
+}
+
+/// Return the ranges of the `__TEXT` segments of every image known to the dynamic linker.
+#[cfg(target_os = "macos")]
+fn loaded_code_ranges() -> Vec<Range<usize>> {
+    (0..unsafe { libc::_dyld_image_count() })
+        .filter_map(|index| unsafe {
+            let header = libc::_dyld_get_image_header(index);
+            if header.is_null() {
+                return None;
+            }
+
+            text_range(header as usize)
+        })
+        .collect()
+}
+
+/// Return the range of the executable segments of the ELF object loaded at `base`.
+#[cfg(target_os = "linux")]
+unsafe fn text_range(base: usize) -> Option<Range<usize>> {
+    let header = &*(base as *const libc::Elf64_Ehdr);
+    if header.e_ident[..4] != *b"\x7fELF" {
+        return None;
+    }
+
+    let program_headers = std::slice::from_raw_parts(
+        (base + header.e_phoff as usize) as *const libc::Elf64_Phdr,
+        header.e_phnum.into(),
+    );
+    let segments = program_headers.iter().filter(|x| x.p_type == libc::PT_LOAD);
+
+    // The base is where the first segment is mapped, which is page-aligned.
+    let first_vaddr = segments.clone().map(|x| x.p_vaddr as usize).min()?;
+    let bias = base - (first_vaddr & !(page_size() - 1));
+
+    segments
+        .filter(|x| x.p_flags & libc::PF_X != 0)
+        .map(|x| {
+            let start = bias + x.p_vaddr as usize;
+            start..start + x.p_memsz as usize
+        })
+        .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+}
+
+/// Return the range of the `__TEXT` segment of the Mach-O object loaded at `base`.
+#[cfg(target_os = "macos")]
+unsafe fn text_range(base: usize) -> Option<Range<usize>> {
+    const LC_SEGMENT_64: u32 = 0x19;
+
+    // The load commands follow the 32-byte `mach_header_64`, whose 5th field is their number.
+    let num_commands = *((base + 16) as *const u32);
+    let mut command = base + 32;
+    for _ in 0..num_commands {
+        let (kind, size) = (*(command as *const u32), *((command + 4) as *const u32));
+        // A `segment_command_64` has its name at offset 8, and its size at offset 32.
+        if kind == LC_SEGMENT_64 && *((command + 8) as *const [u8; 7]) == *b"__TEXT\0" {
+            return Some(base..base + *((command + 32) as *const u64) as usize);
+        }
+
+        command += size as usize;
+    }
+
+    None
+}
+
+/// Information about the guarded execution the current thread is running, if any.
+#[derive(Clone, Copy)]
+struct ActiveGuard {
+    guard: Option<(usize, usize)>,
+    fault_scope: *const ResolvedScope,
+    catch_faults: bool,
+    saved_sp: *mut *mut u8,
+}
+
+/// Why an execution was aborted.
+#[derive(Clone, Copy)]
+enum Abort {
+    StackOverflow,
+    Fault { signal: c_int, address: usize },
+}
+
+thread_local! {
+    static ACTIVE_GUARD: Cell<Option<ActiveGuard>> = const { Cell::new(None) };
+    static LAST_ABORT: Cell<Option<Abort>> = const { Cell::new(None) };
+    static STACK_POOL: RefCell<Vec<GuardedStack>> = const { RefCell::new(Vec::new()) };
+    static ALT_STACK: RefCell<Option<AltStack>> = const { RefCell::new(None) };
+}
+
+static PREVIOUS_GUARD_ACTIONS: OnceLock<Vec<(c_int, libc::sigaction)>> = OnceLock::new();
+static PREVIOUS_FAULT_ACTIONS: OnceLock<Vec<(c_int, libc::sigaction)>> = OnceLock::new();
+
+/// Run `f` as configured by `config`, catching the faults raised within `fault_scope`.
+///
+/// The `function_id` is the Sierra function being invoked. It is reported by
+/// [`Error::NativeFault`] when the faulting function cannot be determined.
+pub(crate) fn run_guarded<F>(
+    config: GuardConfig,
+    fault_scope: FaultScope,
+    function_id: u64,
+    f: F,
+) -> Result<()>
+where
+    F: FnOnce(),
+{
+    extern "C" fn callback<F>(data: *mut c_void)
+    where
+        F: FnOnce(),
+    {
+        let f = unsafe { &mut *data.cast::<Option<F>>() };
+        if let Some(f) = f.take() {
+            f();
+        }
+    }
+
+    if !config.is_enabled() {
+        f();
+        return Ok(());
+    }
+
+    install_signal_handlers(&PREVIOUS_GUARD_ACTIONS, &GUARD_SIGNALS);
+    if config.catch_faults {
+        install_signal_handlers(&PREVIOUS_FAULT_ACTIONS, &FAULT_SIGNALS);
+    }
+    ensure_alt_stack()?;
+
+    let stack = config
+        .stack_size
+        .map(|stack_size| {
+            STACK_POOL.with_borrow_mut(|pool| {
+                match pool.iter().position(|x| x.size() == stack_size) {
+                    Some(index) => Ok(pool.swap_remove(index)),
+                    None => GuardedStack::new(stack_size),
+                }
+            })
+        })
+        .transpose()?;
+
+    let fault_scope = ResolvedScope::new(fault_scope);
+    let mut f = Some(f);
+    let mut saved_sp = null_mut();
+
+    // Nested executions (for example, contracts calling other contracts through the syscall
+    // handler) run with their own guard. The outer one has to be restored afterwards.
+    let prev_guard = ACTIVE_GUARD.replace(Some(ActiveGuard {
+        guard: stack.as_ref().map(|x| {
+            let guard = x.guard();
+            (guard.start, guard.end)
+        }),
+        fault_scope: &raw const fault_scope,
+        catch_faults: config.catch_faults,
+        saved_sp: &raw mut saved_sp,
+    }));
+
+    let signal = unsafe {
+        invoke_on_stack(
+            callback::<F>,
+            (&raw mut f).cast(),
+            stack.as_ref().map_or(null_mut(), GuardedStack::top),
+            &raw mut saved_sp,
+        )
+    };
+
+    ACTIVE_GUARD.set(prev_guard);
+    if let Some(stack) = stack {
+        STACK_POOL.with_borrow_mut(|pool| pool.push(stack));
+    }
+
+    if signal == 0 {
+        return Ok(());
+    }
+
+    // The closure may not have been consumed if the callback was aborted before calling it, but
+    // even if it was not, dropping it could access stuff left in an invalid state.
+    std::mem::forget(f);
+
+    match LAST_ABORT.take() {
+        Some(Abort::Fault { signal, address }) => Err(Error::NativeFault {
+            signal,
+            function_id: resolve_function_id(address).unwrap_or(function_id),
+        }),
+        Some(Abort::StackOverflow) | None => Err(Error::StackOverflow),
+    }
+}
+
+/// Find the Sierra function that contains `address` using the dynamic linker's symbol tables.
+///
+/// Only works for AOT-compiled programs, since JIT-compiled code is not registered anywhere.
+fn resolve_function_id(address: usize) -> Option<u64> {
+    let symbol_name = unsafe {
+        let mut info = std::mem::zeroed::<libc::Dl_info>();
+        if libc::dladdr(address as *const c_void, &mut info) == 0 || info.dli_sname.is_null() {
+            return None;
+        }
+
+        CStr::from_ptr(info.dli_sname).to_str().ok()?
+    };
+
+    // Function names are generated either as `f{id}` or as `{debug_name}(f{id})`.
+    let symbol_name = symbol_name.strip_suffix(')').unwrap_or(symbol_name);
+    let (prefix, id) = symbol_name.rsplit_once('f')?;
+    (prefix.is_empty() || prefix.ends_with('('))
+        .then(|| id.parse().ok())
+        .flatten()
+}
+
+/// A `mmap`-allocated stack with a non-accessible guard region at its lowest addresses.
+struct GuardedStack {
+    base: NonNull<c_void>,
+    len: usize,
+    guard_len: usize,
+}
+
+impl GuardedStack {
+    fn new(size: usize) -> Result<Self> {
+        let page_size = page_size();
+        let guard_len = MIN_GUARD_SIZE.next_multiple_of(page_size);
+        let len = guard_len + size.next_multiple_of(page_size);
+
+        unsafe {
+            let base = libc::mmap(
+                null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            );
+            if base == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            if libc::mprotect(base, guard_len, libc::PROT_NONE) != 0 {
+                let error = io::Error::last_os_error();
+                libc::munmap(base, len);
+                return Err(error.into());
+            }
+
+            Ok(Self {
+                base: NonNull::new_unchecked(base),
+                len,
+                guard_len,
+            })
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.len - self.guard_len
+    }
+
+    fn guard(&self) -> Range<usize> {
+        let base = self.base.as_ptr() as usize;
+        base..base + self.guard_len
+    }
+
+    fn top(&self) -> *mut u8 {
+        // Page-aligned, therefore also 16-byte aligned.
+        unsafe { self.base.as_ptr().cast::<u8>().add(self.len) }
+    }
+}
+
+impl Drop for GuardedStack {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base.as_ptr(), self.len);
+        }
+    }
+}
+
+/// An alternate signal stack registered with `sigaltstack`.
+///
+/// The signal handler can't run on the overflowed stack, so an alternate stack is required. Rust
+/// already registers one for the threads it creates, but other threads may not have any.
+struct AltStack(GuardedStack);
+
+impl Drop for AltStack {
+    fn drop(&mut self) {
+        unsafe {
+            let stack = libc::stack_t {
+                ss_sp: null_mut(),
+                ss_flags: libc::SS_DISABLE,
+                ss_size: 0,
+            };
+            libc::sigaltstack(&stack, null_mut());
+        }
+    }
+}
+
+fn ensure_alt_stack() -> Result<()> {
+    unsafe {
+        let mut current = std::mem::zeroed::<libc::stack_t>();
+        if libc::sigaltstack(null_mut(), &mut current) != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        if current.ss_flags & libc::SS_DISABLE == 0 {
+            return Ok(());
+        }
+    }
+
+    let alt_stack = GuardedStack::new(ALT_STACK_SIZE.max(libc::SIGSTKSZ))?;
+    unsafe {
+        let stack = libc::stack_t {
+            ss_sp: alt_stack.top().sub(alt_stack.size()).cast(),
+            ss_flags: 0,
+            ss_size: alt_stack.size(),
+        };
+        if libc::sigaltstack(&stack, null_mut()) != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+    }
+
+    ALT_STACK.set(Some(AltStack(alt_stack)));
+    Ok(())
+}
+
+fn install_signal_handlers(
+    previous_actions: &OnceLock<Vec<(c_int, libc::sigaction)>>,
+    signals: &[c_int],
+) {
+    previous_actions.get_or_init(|| {
+        signals
+            .iter()
+            .map(|&signal| unsafe {
+                let mut action = std::mem::zeroed::<libc::sigaction>();
+                action.sa_sigaction = handle_signal as *const () as usize;
+                action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                let mut previous = std::mem::zeroed::<libc::sigaction>();
+                libc::sigaction(signal, &action, &mut previous);
+
+                (signal, previous)
+            })
+            .collect()
+    });
+}
+
+extern "C" fn handle_signal(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    if let Some(active_guard) = ACTIVE_GUARD.get() {
+        let address = unsafe { fault_address(info) };
+        let pc = unsafe { context_pc(context) };
+        // Owned by the `run_guarded` frame that's still running.
+        let fault_scope = unsafe { &*active_guard.fault_scope };
+        let abort = match active_guard.guard {
+            Some((start, end))
+                if GUARD_SIGNALS.contains(&signal) && (start..end).contains(&address) =>
+            {
+                // Only the program's frames can be abandoned safely. Anything else overflowing the
+                // stack (the runtime, the syscall handler...) must take the process down.
+                if !fault_scope.contains(pc) {
+                    unsafe { raise_with_default_action(signal) };
+                    return;
+                }
+
+                Some(Abort::StackOverflow)
+            }
+            _ => (active_guard.catch_faults && is_synchronous(info) && fault_scope.contains(pc))
+                .then_some(Abort::Fault {
+                    signal,
+                    address: pc,
+                }),
+        };
+
+        if let Some(abort) = abort {
+            LAST_ABORT.set(Some(abort));
+            unsafe {
+                redirect_to_landing_pad(context, *active_guard.saved_sp, signal);
+            }
+            return;
+        }
+    }
+
+    unsafe { forward_signal(signal, info, context) }
+}
+
+/// Whether the signal was caused by the thread that received it.
+///
+/// Signals sent by other processes (or threads) are not faults of the execution and therefore
+/// must not abort it.
+fn is_synchronous(info: *mut siginfo_t) -> bool {
+    unsafe { (*info).si_code > 0 }
+}
+
+/// Forward a signal we're not interested in to the handler that was installed before ours.
+unsafe fn forward_signal(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    let previous = [&PREVIOUS_GUARD_ACTIONS, &PREVIOUS_FAULT_ACTIONS]
+        .into_iter()
+        .filter_map(OnceLock::get)
+        .flatten()
+        .find(|(x, _)| *x == signal)
+        .map(|(_, action)| *action);
+
+    match previous {
+        Some(action)
+            if action.sa_sigaction != libc::SIG_DFL && action.sa_sigaction != libc::SIG_IGN =>
+        {
+            if action.sa_flags & libc::SA_SIGINFO != 0 {
+                let handler = std::mem::transmute::<
+                    usize,
+                    extern "C" fn(c_int, *mut siginfo_t, *mut c_void),
+                >(action.sa_sigaction);
+                handler(signal, info, context);
+            } else {
+                let handler =
+                    std::mem::transmute::<usize, extern "C" fn(c_int)>(action.sa_sigaction);
+                handler(signal);
+            }
+        }
+        _ => raise_with_default_action(signal),
+    }
+}
+
+/// Restore the default action and raise the signal again. It'll be delivered once the handler
+/// returns, triggering the default action this time.
+unsafe fn raise_with_default_action(signal: c_int) {
+    let mut action = std::mem::zeroed::<libc::sigaction>();
+    action.sa_sigaction = libc::SIG_DFL;
+    libc::sigemptyset(&mut action.sa_mask);
+    libc::sigaction(signal, &action, null_mut());
+    libc::raise(signal);
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn fault_address(info: *mut siginfo_t) -> usize {
+    (*info).si_addr() as usize
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn fault_address(info: *mut siginfo_t) -> usize {
+    (*info).si_addr as usize
+}
+
+/// Return the program counter of the interrupted context.
+unsafe fn context_pc(context: *mut c_void) -> usize {
+    let context = &*context.cast::<libc::ucontext_t>();
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    return context.uc_mcontext.gregs[libc::REG_RIP as usize] as usize;
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    return context.uc_mcontext.pc as usize;
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    return (*context.uc_mcontext).__ss.__rip as usize;
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    return (*context.uc_mcontext).__ss.__pc as usize;
+}
+
+/// Modify the interrupted context so that, once the handler returns, execution resumes at the
+/// landing pad with the stack pointer that was saved before switching stacks.
+unsafe fn redirect_to_landing_pad(context: *mut c_void, saved_sp: *mut u8, signal: c_int) {
+    let context = &mut *context.cast::<libc::ucontext_t>();
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    {
+        let gregs = &mut context.uc_mcontext.gregs;
+        gregs[libc::REG_RSP as usize] = saved_sp as i64;
+        gregs[libc::REG_RIP as usize] = invoke_on_stack_abort as *const () as usize as i64;
+        gregs[libc::REG_RDI as usize] = signal as i64;
+    }
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    {
+        let mcontext = &mut context.uc_mcontext;
+        mcontext.sp = saved_sp as u64;
+        mcontext.pc = invoke_on_stack_abort as *const () as usize as u64;
+        mcontext.regs[0] = signal as u64;
+    }
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    {
+        let state = &mut (*context.uc_mcontext).__ss;
+        state.__rsp = saved_sp as u64;
+        state.__rip = invoke_on_stack_abort as *const () as usize as u64;
+        state.__rdi = signal as u64;
+    }
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    {
+        let state = &mut (*context.uc_mcontext).__ss;
+        state.__sp = saved_sp as u64;
+        state.__pc = invoke_on_stack_abort as *const () as usize as u64;
+        state.__x[0] = signal as u64;
+    }
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STACK_CONFIG: GuardConfig = GuardConfig {
+        stack_size: Some(256 * 1024),
+        catch_faults: false,
+    };
+    const FAULT_CONFIG: GuardConfig = GuardConfig {
+        stack_size: None,
+        catch_faults: true,
+    };
+
+    /// The scope of the test binary's code, where the faulting closures live.
+    fn test_scope() -> FaultScope {
+        FaultScope::of(test_scope as *const () as *const c_void)
+    }
+
+    #[inline(never)]
+    fn recurse(n: u64) -> u64 {
+        let buffer = std::hint::black_box([n; 64]);
+        if n == 0 {
+            0
+        } else {
+            buffer[0] + recurse(n - 1)
+        }
+    }
+
+    #[test]
+    fn test_run_guarded() {
+        let mut value = 0;
+        run_guarded(STACK_CONFIG, FaultScope::Unregistered, 0, || value = 42).unwrap();
+        assert_eq!(value, 42);
+
+        let mut value = 0;
+        run_guarded(FAULT_CONFIG, test_scope(), 0, || value = 42).unwrap();
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_run_guarded_stack_overflow() {
+        let result = run_guarded(STACK_CONFIG, test_scope(), 0, || {
+            std::hint::black_box(recurse(u64::MAX));
+        });
+        assert!(matches!(result, Err(Error::StackOverflow)));
+
+        // The thread should still be usable afterwards.
+        let mut value = 0;
+        run_guarded(STACK_CONFIG, test_scope(), 0, || value = recurse(10)).unwrap();
+        assert_eq!(value, 55);
+    }
+
+    /// Overflows outside of the scope must crash the process, so they're run in a child process
+    /// (see [`foreign_stack_overflow`]).
+    #[test]
+    fn test_run_guarded_foreign_stack_overflow() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "executor::guard::tests::foreign_stack_overflow"])
+            .env(FOREIGN_STACK_OVERFLOW_VAR, "1")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(matches!(
+            status.signal(),
+            Some(libc::SIGSEGV | libc::SIGBUS)
+        ));
+    }
+
+    const FOREIGN_STACK_OVERFLOW_VAR: &str = "CAIRO_NATIVE_TEST_FOREIGN_STACK_OVERFLOW";
+
+    #[test]
+    fn foreign_stack_overflow() {
+        if std::env::var_os(FOREIGN_STACK_OVERFLOW_VAR).is_none() {
+            return;
+        }
+
+        // The recursion lives in the test binary, not in the (empty) scope.
+        let scope = FaultScope::Library { start: 0, end: 0 };
+        let _ = run_guarded(STACK_CONFIG, scope, 0, || {
+            std::hint::black_box(recurse(u64::MAX));
+        });
+    }
+
+    #[test]
+    fn test_run_guarded_fault() {
+        let result = run_guarded(FAULT_CONFIG, test_scope(), 7, || unsafe {
+            std::ptr::write_volatile(std::ptr::dangling_mut::<u64>(), 0);
+        });
+        assert!(matches!(
+            result,
+            Err(Error::NativeFault {
+                signal: libc::SIGSEGV,
+                function_id: 7,
+            })
+        ));
+
+        // The thread should still be usable afterwards.
+        let mut value = 0;
+        run_guarded(FAULT_CONFIG, test_scope(), 7, || value = recurse(10)).unwrap();
+        assert_eq!(value, 55);
+    }
+
+    #[test]
+    fn test_fault_scope() {
+        // Only the library's own code is within its scope.
+        let scope = test_scope();
+        assert!(matches!(scope, FaultScope::Library { .. }));
+        let scope = ResolvedScope::new(scope);
+        assert!(scope.contains(recurse as *const () as usize));
+        assert!(!scope.contains(libc::abort as *const () as usize));
+
+        // Code from loaded objects is never considered JIT-compiled code.
+        assert_eq!(FaultScope::of(null_mut()), FaultScope::Unregistered);
+        let scope = ResolvedScope::new(FaultScope::Unregistered);
+        assert!(!scope.contains(recurse as *const () as usize));
+        assert!(!scope.contains(libc::abort as *const () as usize));
+        let heap = Box::new(0u8);
+        assert!(scope.contains(&raw const *heap as usize));
+    }
+}
@@ -1,11 +1,19 @@
 use crate::{
     error::Error,
     execution_result::{ContractExecutionResult, ExecutionResult},
+    executor::{
+        guard::GuardConfig,
+        plan::{InvocationPlan, PlanCache},
+        typed::{invoke_typed, TypedArguments, TypedExecutionResult, TypedPlan, TypedReturns},
+    },
+    ffi::TargetOptions,
+    limits::{self, ResourceGuard},
     metadata::{
         felt252_dict::Felt252DictOverrides, gas::GasMetadata, runtime_bindings::setup_runtime,
     },
     module::NativeModule,
     starknet::{DummySyscallHandler, StarknetSyscallHandler},
+    statistics::CompilationStats,
     utils::{create_engine, generate_function_name},
     values::Value,
     OptLevel,
@@ -19,7 +27,7 @@ use cairo_lang_sierra::{
 use libc::c_void;
 use melior::{ir::Module, ExecutionEngine};
 use starknet_types_core::felt::Felt;
-use std::mem::transmute;
+use std::{any::TypeId, mem::transmute, sync::Arc, time::Instant};
 
 /// A MLIR JIT execution engine in the context of Cairo Native.
 pub struct JitNativeExecutor<'m> {
@@ -30,6 +38,12 @@ pub struct JitNativeExecutor<'m> {
 
     gas_metadata: GasMetadata,
     dict_overrides: Felt252DictOverrides,
+
+    guard_config: GuardConfig,
+    plans: PlanCache<FunctionId, InvocationPlan>,
+    typed_plans: PlanCache<(FunctionId, TypeId), TypedPlan>,
+
+    compilation_stats: CompilationStats,
 }
 
 unsafe impl Send for JitNativeExecutor<'_> {}
@@ -55,12 +69,33 @@ impl<'m> JitNativeExecutor<'m> {
             mut metadata,
         } = native_module;
 
+        // The execution engine always generates code for the host.
+        if let Some(target) = metadata.get::<TargetOptions>() {
+            target.check_host()?;
+        }
+
+        // The time and memory used to generate the code are limited on their own.
+        let guard = metadata
+            .remove::<ResourceGuard>()
+            .map(ResourceGuard::restart);
+
+        let mut compilation_stats = metadata.remove::<CompilationStats>().unwrap_or_default();
+        let pre_engine_instant = Instant::now();
+        let engine = create_engine(&module, &metadata, opt_level);
+        compilation_stats.llvm_passes = pre_engine_instant.elapsed();
+        limits::check(guard.as_ref())?;
+        compilation_stats.record_peak_memory();
+
         let executor = Self {
-            engine: create_engine(&module, &metadata, opt_level),
+            engine,
             module,
             registry,
             gas_metadata: metadata.remove().ok_or(Error::MissingMetadata)?,
             dict_overrides: metadata.remove().unwrap_or_default(),
+            guard_config: GuardConfig::default(),
+            plans: PlanCache::default(),
+            typed_plans: PlanCache::default(),
+            compilation_stats,
         };
 
         setup_runtime(|name| executor.find_symbol_ptr(name));
@@ -68,6 +103,27 @@ impl<'m> JitNativeExecutor<'m> {
         Ok(executor)
     }
 
+    /// Run the programs on a dedicated stack of the given size, protected by a guard region.
+    ///
+    /// Overflowing the stack will make the invocation return [`Error::StackOverflow`] instead of
+    /// crashing the process. Overflows raised elsewhere, like in the runtime or the syscall
+    /// handler, still crash it since their frames can't be abandoned safely.
+    pub fn with_stack_size(mut self, stack_size: usize) -> Self {
+        self.guard_config.stack_size = Some(stack_size);
+        self
+    }
+
+    /// Catch the faults (invalid memory accesses, traps...) raised by the programs'
+    /// compiled code. Faults raised elsewhere, like in the runtime or the syscall handler, still
+    /// reach the process' signal handlers.
+    ///
+    /// A fault will make the invocation return [`Error::NativeFault`] instead of crashing the
+    /// process. Anything allocated by the aborted execution is leaked.
+    pub fn with_fault_isolation(mut self) -> Self {
+        self.guard_config.catch_faults = true;
+        self
+    }
+
     pub const fn program_registry(&self) -> &ProgramRegistry<CoreType, CoreLibfunc> {
         &self.registry
     }
@@ -76,6 +132,15 @@ impl<'m> JitNativeExecutor<'m> {
         &self.module
     }
 
+    /// The statistics of the program's compilation.
+    ///
+    /// The execution engine translates, optimizes and generates the code all at once, which is
+    /// accounted for as [`llvm_passes`](CompilationStats::llvm_passes). No object is generated nor
+    /// linked.
+    pub const fn compilation_stats(&self) -> &CompilationStats {
+        &self.compilation_stats
+    }
+
     /// Execute a program with the given params.
     pub fn invoke_dynamic(
         &self,
@@ -90,12 +155,13 @@ impl<'m> JitNativeExecutor<'m> {
 
         super::invoke_dynamic(
             &self.registry,
-            self.find_function_ptr(function_id),
-            self.extract_signature(function_id)?,
+            function_id,
+            &self.plan(function_id)?,
             args,
             available_gas,
             Option::<DummySyscallHandler>::None,
             self.build_find_dict_overrides(),
+            self.guard_config,
         )
     }
 
@@ -114,12 +180,65 @@ impl<'m> JitNativeExecutor<'m> {
 
         super::invoke_dynamic(
             &self.registry,
-            self.find_function_ptr(function_id),
-            self.extract_signature(function_id)?,
+            function_id,
+            &self.plan(function_id)?,
             args,
             available_gas,
             Some(syscall_handler),
             self.build_find_dict_overrides(),
+            self.guard_config,
+        )
+    }
+
+    /// Invoke a function using statically typed arguments and return values instead of
+    /// [`Value`]s.
+    ///
+    /// Only the non-builtin arguments and return values are given, as tuples. Their types are
+    /// checked against the function's signature before invoking it.
+    pub fn invoke<A: TypedArguments, R: TypedReturns>(
+        &self,
+        function_id: &FunctionId,
+        args: A,
+        gas: Option<u64>,
+    ) -> Result<TypedExecutionResult<R>, Error> {
+        let available_gas = self
+            .gas_metadata
+            .get_initial_available_gas(function_id, gas)
+            .map_err(crate::error::Error::GasMetadataError)?;
+
+        let (plan, typed_plan) = self.typed_plan::<A, R>(function_id)?;
+        invoke_typed(
+            function_id,
+            &plan,
+            &typed_plan,
+            &args,
+            available_gas,
+            Option::<DummySyscallHandler>::None,
+            self.guard_config,
+        )
+    }
+
+    pub fn invoke_with_syscall_handler<A: TypedArguments, R: TypedReturns>(
+        &self,
+        function_id: &FunctionId,
+        args: A,
+        gas: Option<u64>,
+        syscall_handler: impl StarknetSyscallHandler,
+    ) -> Result<TypedExecutionResult<R>, Error> {
+        let available_gas = self
+            .gas_metadata
+            .get_initial_available_gas(function_id, gas)
+            .map_err(crate::error::Error::GasMetadataError)?;
+
+        let (plan, typed_plan) = self.typed_plan::<A, R>(function_id)?;
+        invoke_typed(
+            function_id,
+            &plan,
+            &typed_plan,
+            &args,
+            available_gas,
+            Some(syscall_handler),
+            self.guard_config,
         )
     }
 
@@ -137,8 +256,8 @@ impl<'m> JitNativeExecutor<'m> {
 
         ContractExecutionResult::from_execution_result(super::invoke_dynamic(
             &self.registry,
-            self.find_function_ptr(function_id),
-            self.extract_signature(function_id)?,
+            function_id,
+            &self.plan(function_id)?,
             &[Value::Struct {
                 fields: vec![Value::Array(
                     args.iter().cloned().map(Value::Felt252).collect(),
@@ -148,6 +267,7 @@ impl<'m> JitNativeExecutor<'m> {
             available_gas,
             Some(syscall_handler),
             self.build_find_dict_overrides(),
+            self.guard_config,
         )?)
     }
 
@@ -169,6 +289,33 @@ impl<'m> JitNativeExecutor<'m> {
         }
     }
 
+    /// Return the invocation plan of the function, building it on its first invocation.
+    fn plan(&self, function_id: &FunctionId) -> Result<Arc<InvocationPlan>, Error> {
+        self.plans.get_or_try_insert_with(function_id, || {
+            InvocationPlan::new(
+                &self.registry,
+                self.find_function_ptr(function_id),
+                self.extract_signature(function_id)?,
+            )
+        })
+    }
+
+    /// Return the invocation plan of the function along with the outcome of checking its
+    /// signature against `A` and `R`, building them on their first use.
+    fn typed_plan<A: TypedArguments, R: TypedReturns>(
+        &self,
+        function_id: &FunctionId,
+    ) -> Result<(Arc<InvocationPlan>, Arc<TypedPlan>), Error> {
+        let plan = self.plan(function_id)?;
+        let typed_plan = self
+            .typed_plans
+            .get_or_try_insert_with(&(function_id.clone(), TypeId::of::<(A, R)>()), || {
+                TypedPlan::new::<A, R>(&self.registry, &plan)
+            })?;
+
+        Ok((plan, typed_plan))
+    }
+
     fn extract_signature(&self, function_id: &FunctionId) -> Result<&FunctionSignature, Error> {
         Ok(self
             .program_registry()
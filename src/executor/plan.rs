@@ -0,0 +1,230 @@
+//! # Invocation plans
+//!
+//! Everything needed to invoke a function which depends only on its signature (the function
+//! pointer, which parameters are builtins, the return pointer's layout...) is computed once per
+//! function and cached by the executors. Later invocations only have to fill in the values.
+
+use super::{contract::BuiltinType, guard::FaultScope};
+use crate::{error::Result, types::TypeBuilder};
+use cairo_lang_sierra::{
+    extensions::core::{CoreLibfunc, CoreType},
+    ids::ConcreteTypeId,
+    program::FunctionSignature,
+    program_registry::ProgramRegistry,
+};
+use libc::c_void;
+use std::{
+    alloc::Layout,
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, RwLock},
+};
+
+/// The precomputed information required to invoke a function.
+#[derive(Debug)]
+pub(crate) struct InvocationPlan {
+    pub function_ptr: *const c_void,
+    /// The code whose faults are caught when fault isolation is enabled.
+    pub fault_scope: FaultScope,
+    /// The non-ZST parameters, in order.
+    pub params: Vec<ParamSlot>,
+    /// The layout of the memory the return pointer points to, when one is needed.
+    pub return_layout: Option<Layout>,
+    /// The non-ZST builtins returned before the return value, in order.
+    pub return_builtins: Vec<BuiltinType>,
+    /// The return value's type, unless the function only returns builtins.
+    pub return_type: Option<ConcreteTypeId>,
+}
+
+unsafe impl Send for InvocationPlan {}
+unsafe impl Sync for InvocationPlan {}
+
+#[derive(Debug)]
+pub(crate) enum ParamSlot {
+    Builtin(BuiltinType),
+    Value(ConcreteTypeId),
+}
+
+impl InvocationPlan {
+    pub fn new(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        function_ptr: *const c_void,
+        function_signature: &FunctionSignature,
+    ) -> Result<Self> {
+        let mut params = Vec::with_capacity(function_signature.param_types.len());
+        for type_id in &function_signature.param_types {
+            let type_info = registry.get_type(type_id)?;
+            if type_info.is_zst(registry)? {
+                continue;
+            }
+
+            params.push(match BuiltinType::from_type_info(type_info) {
+                Some(builtin) => ParamSlot::Builtin(builtin),
+                None => ParamSlot::Value(type_id.clone()),
+            });
+        }
+
+        // Generate return pointer (if necessary).
+        //
+        // Generated when either:
+        //   - There are more than one non-zst return values.
+        //     - All builtins except GasBuiltin and Starknet are ZST.
+        //     - The unit struct is a ZST.
+        //   - The return argument is complex.
+        let mut return_builtins = Vec::new();
+        let mut return_layout = Layout::new::<()>();
+        let mut num_return_args = 0;
+        let mut first_is_complex = None;
+        for type_id in &function_signature.ret_types {
+            let type_info = registry.get_type(type_id)?;
+            if type_info.is_builtin() && type_info.is_zst(registry)? {
+                continue;
+            }
+
+            if let Some(builtin) = BuiltinType::from_type_info(type_info) {
+                return_builtins.push(builtin);
+            }
+            if first_is_complex.is_none() {
+                first_is_complex = Some(type_info.is_complex(registry)?);
+            }
+
+            return_layout = return_layout.extend(type_info.layout(registry)?)?.0;
+            num_return_args += 1;
+        }
+
+        let return_type = match function_signature.ret_types.last() {
+            Some(type_id) if !registry.get_type(type_id)?.is_builtin() => Some(type_id.clone()),
+            _ => None,
+        };
+
+        Ok(Self {
+            function_ptr,
+            fault_scope: FaultScope::of(function_ptr),
+            params,
+            return_layout: (num_return_args > 1 || first_is_complex == Some(true))
+                .then_some(return_layout),
+            return_builtins,
+            return_type,
+        })
+    }
+}
+
+/// A thread-safe cache of plans, built the first time they're requested.
+#[derive(Debug)]
+pub(crate) struct PlanCache<K, P> {
+    plans: RwLock<HashMap<K, Arc<P>>>,
+}
+
+impl<K, P> Default for PlanCache<K, P> {
+    fn default() -> Self {
+        Self {
+            plans: RwLock::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, P> Clone for PlanCache<K, P> {
+    fn clone(&self) -> Self {
+        Self {
+            plans: RwLock::new(self.read().clone()),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, P> PlanCache<K, P> {
+    /// Return the cached plan for `key`, building it with `build` if it isn't cached yet.
+    pub fn get_or_try_insert_with(
+        &self,
+        key: &K,
+        build: impl FnOnce() -> Result<P>,
+    ) -> Result<Arc<P>> {
+        if let Some(plan) = self.read().get(key) {
+            return Ok(plan.clone());
+        }
+
+        let plan = Arc::new(build()?);
+        Ok(self
+            .plans
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.clone())
+            .or_insert(plan)
+            .clone())
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<K, Arc<P>>> {
+        // The map is never left in an inconsistent state, so poisoning can be ignored.
+        self.plans.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::load_cairo;
+    use cairo_lang_sierra::{extensions::core::CoreTypeConcrete, program::Program};
+    use rstest::*;
+    use std::ptr::null;
+
+    #[fixture]
+    fn program() -> Program {
+        let (_, program) = load_cairo! {
+            fn run_test(a: u32, b: u32) -> u32 {
+                a + b
+            }
+
+            fn identity(a: felt252) -> felt252 {
+                a
+            }
+        };
+        program
+    }
+
+    #[rstest]
+    fn test_invocation_plan(program: Program) {
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap();
+
+        let plan = InvocationPlan::new(&registry, null(), &program.funcs[0].signature).unwrap();
+        assert!(matches!(
+            plan.params.as_slice(),
+            [
+                ParamSlot::Builtin(BuiltinType::RangeCheck),
+                ParamSlot::Value(_),
+                ParamSlot::Value(_),
+            ]
+        ));
+        assert_eq!(plan.return_builtins, [BuiltinType::RangeCheck]);
+        assert!(plan.return_layout.is_some());
+        assert!(matches!(
+            registry
+                .get_type(plan.return_type.as_ref().unwrap())
+                .unwrap(),
+            CoreTypeConcrete::Enum(_)
+        ));
+
+        let plan = InvocationPlan::new(&registry, null(), &program.funcs[1].signature).unwrap();
+        assert!(matches!(plan.params.as_slice(), [ParamSlot::Value(_)]));
+        assert!(plan.return_builtins.is_empty());
+        assert_eq!(
+            plan.return_type,
+            Some(program.funcs[1].signature.ret_types[0].clone())
+        );
+    }
+
+    #[test]
+    fn test_plan_cache() {
+        let cache = PlanCache::<u64, u64>::default();
+
+        assert_eq!(*cache.get_or_try_insert_with(&0, || Ok(1)).unwrap(), 1);
+        assert_eq!(
+            *cache
+                .get_or_try_insert_with(&0, || unreachable!("plan should be cached"))
+                .unwrap(),
+            1
+        );
+        assert!(cache
+            .get_or_try_insert_with(&1, || Err(crate::error::Error::SelectorNotFound))
+            .is_err());
+        assert_eq!(*cache.get_or_try_insert_with(&1, || Ok(2)).unwrap(), 2);
+    }
+}
@@ -0,0 +1,540 @@
+//! # Background compilation
+//!
+//! Compiling a contract may take a while, during which it can still be run by other means (for
+//! example, using the Cairo VM). The [`CompilationService`] compiles the contracts in a pool of
+//! worker threads, returning a [`CompilationHandle`] which can be polled, waited for or awaited as
+//! a future.
+
+use crate::{
+    error::{Error, Result},
+    executor::AotContractExecutor,
+    limits::CompilationLimits,
+    options::CompilationOptions,
+    OptLevel, TargetOptions,
+};
+use cairo_lang_sierra::program::Program;
+use cairo_lang_starknet_classes::{
+    compiler_version::VersionId, contract_class::ContractEntryPoints,
+};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    fmt::{self, Debug},
+    future::Future,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+/// A pool of threads compiling contracts in the background.
+///
+/// Dropping the service cancels the queued compilations and waits for the running ones to finish.
+pub struct CompilationService {
+    queue: Arc<Queue>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl CompilationService {
+    /// Start a service with the given number of worker threads (at least one).
+    pub fn new(workers: usize) -> Self {
+        Self::with_limits(workers, CompilationLimits::default())
+    }
+
+    /// Like [`new`](Self::new), but the compilations exceeding the given `limits` fail with
+    /// [`Error::ResourceLimitExceeded`].
+    ///
+    /// The [`max_memory`](CompilationLimits::max_memory) limit is measured on the whole process'
+    /// resident memory, therefore the compilations running in parallel count towards each other's
+    /// limit. Use a single worker for it to be accurate.
+    pub fn with_limits(workers: usize, limits: CompilationLimits) -> Self {
+        let queue = Arc::new(Queue {
+            limits,
+            ..Default::default()
+        });
+        let workers = (0..workers.max(1))
+            .map(|i| {
+                let queue = queue.clone();
+                thread::Builder::new()
+                    .name(format!("cairo-native-compiler-{i}"))
+                    .spawn(move || queue.work())
+                    .expect("failed to spawn a compilation worker")
+            })
+            .collect();
+
+        Self { queue, workers }
+    }
+
+    /// The number of worker threads.
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queue a contract for compilation with the given `options`, for the given `target` (which
+    /// must be the host's triple, see
+    /// [`AotContractExecutor::new_with_options`]).
+    ///
+    /// The contracts are compiled in the same order they're submitted. Dropping the returned
+    /// handle cancels the compilation.
+    pub fn submit(
+        &self,
+        program: Program,
+        entry_points: ContractEntryPoints,
+        sierra_version: VersionId,
+        opt_level: OptLevel,
+        options: CompilationOptions,
+        target: TargetOptions,
+    ) -> CompilationHandle {
+        let state = Arc::<JobState>::default();
+
+        let mut queue = self.queue.lock();
+        queue.jobs.push_back(Job {
+            program,
+            entry_points,
+            sierra_version,
+            opt_level,
+            options,
+            target,
+            state: state.clone(),
+        });
+        drop(queue);
+        self.queue.condvar.notify_one();
+
+        CompilationHandle { state }
+    }
+}
+
+impl Debug for CompilationService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompilationService")
+            .field("workers", &self.workers.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for CompilationService {
+    fn drop(&mut self) {
+        let jobs = {
+            let mut queue = self.queue.lock();
+            queue.shutdown = true;
+            mem::take(&mut queue.jobs)
+        };
+        self.queue.condvar.notify_all();
+
+        for job in jobs {
+            job.state.cancel();
+        }
+        for worker in self.workers.drain(..) {
+            // The workers never panic, since the compilation panics are caught.
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A handle to a contract being compiled by a [`CompilationService`].
+///
+/// The result can be retrieved by polling ([`try_get`](Self::try_get)), blocking
+/// ([`wait`](Self::wait)) or awaiting the handle, which implements [`Future`] without depending on
+/// any specific async runtime.
+#[derive(Debug)]
+pub struct CompilationHandle {
+    state: Arc<JobState>,
+}
+
+impl CompilationHandle {
+    /// Whether the compilation has finished (or was cancelled).
+    pub fn is_ready(&self) -> bool {
+        matches!(
+            self.state.lock().status,
+            Status::Done(_) | Status::Cancelled | Status::Taken
+        )
+    }
+
+    /// Return the result of the compilation if it has finished, without blocking.
+    ///
+    /// Once the result has been returned, it's not available anymore and further calls will
+    /// return `None`.
+    pub fn try_get(&mut self) -> Option<Result<AotContractExecutor>> {
+        self.state.lock().take()
+    }
+
+    /// Block until the compilation finishes and return its result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result was already returned by [`try_get`](Self::try_get).
+    pub fn wait(self) -> Result<AotContractExecutor> {
+        let mut inner = self.state.lock();
+        loop {
+            if matches!(inner.status, Status::Taken) {
+                panic!("the compilation result was already taken");
+            }
+            if let Some(result) = inner.take() {
+                return result;
+            }
+
+            inner = self
+                .state
+                .condvar
+                .wait(inner)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Cancel the compilation.
+    ///
+    /// Queued compilations will never start. Running ones can't be interrupted, but their result
+    /// is discarded. Either way, the result will be [`Error::CompilationCancelled`].
+    pub fn cancel(&self) {
+        self.state.cancel();
+    }
+}
+
+impl Future for CompilationHandle {
+    type Output = Result<AotContractExecutor>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.state.lock();
+        match inner.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                inner.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for CompilationHandle {
+    fn drop(&mut self) {
+        // Nobody can retrieve the result anymore.
+        self.state.cancel();
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Mutex<Jobs>,
+    condvar: Condvar,
+    limits: CompilationLimits,
+}
+
+#[derive(Default)]
+struct Jobs {
+    jobs: VecDeque<Job>,
+    shutdown: bool,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, Jobs> {
+        // The queue is never left in an inconsistent state, so poisoning can be ignored.
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The worker threads' main loop.
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut jobs = self.lock();
+                loop {
+                    if jobs.shutdown {
+                        return;
+                    }
+                    if let Some(job) = jobs.jobs.pop_front() {
+                        break job;
+                    }
+                    jobs = self.condvar.wait(jobs).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            {
+                let mut inner = job.state.lock();
+                if !matches!(inner.status, Status::Queued) {
+                    continue;
+                }
+                inner.status = Status::Running;
+            }
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                AotContractExecutor::new_with_options(
+                    &job.program,
+                    &job.entry_points,
+                    job.sierra_version,
+                    job.opt_level,
+                    &self.limits,
+                    &job.options,
+                    &job.target,
+                )
+            }))
+            .unwrap_or_else(|payload| Err(Error::CompilationPanicked(panic_message(payload))));
+
+            job.state.finish(result);
+        }
+    }
+}
+
+struct Job {
+    program: Program,
+    entry_points: ContractEntryPoints,
+    sierra_version: VersionId,
+    opt_level: OptLevel,
+    options: CompilationOptions,
+    target: TargetOptions,
+    state: Arc<JobState>,
+}
+
+#[derive(Debug, Default)]
+struct JobState {
+    inner: Mutex<JobInner>,
+    condvar: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct JobInner {
+    status: Status,
+    waker: Option<Waker>,
+}
+
+#[derive(Debug, Default)]
+enum Status {
+    #[default]
+    Queued,
+    Running,
+    Done(Result<AotContractExecutor>),
+    Cancelled,
+    /// The result has already been returned.
+    Taken,
+}
+
+impl JobState {
+    fn lock(&self) -> MutexGuard<'_, JobInner> {
+        // The state is never left in an inconsistent state, so poisoning can be ignored.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn finish(&self, result: Result<AotContractExecutor>) {
+        let mut inner = self.lock();
+        if matches!(inner.status, Status::Running) {
+            inner.status = Status::Done(result);
+            inner.notify(&self.condvar);
+        }
+    }
+
+    fn cancel(&self) {
+        let mut inner = self.lock();
+        if matches!(inner.status, Status::Queued | Status::Running) {
+            inner.status = Status::Cancelled;
+            inner.notify(&self.condvar);
+        }
+    }
+}
+
+impl JobInner {
+    fn take(&mut self) -> Option<Result<AotContractExecutor>> {
+        match self.status {
+            Status::Queued | Status::Running | Status::Taken => None,
+            Status::Done(_) | Status::Cancelled => {
+                match mem::replace(&mut self.status, Status::Taken) {
+                    Status::Done(result) => Some(result),
+                    _ => Some(Err(Error::CompilationCancelled)),
+                }
+            }
+        }
+    }
+
+    fn notify(&mut self, condvar: &Condvar) {
+        condvar.notify_all();
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|x| x.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{starknet_stub::StubSyscallHandler, utils::test::load_starknet_contract};
+    use cairo_lang_starknet_classes::contract_class::{
+        version_id_from_serialized_sierra_program, ContractClass,
+    };
+    use starknet_types_core::felt::Felt;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        task::Wake,
+        time::Duration,
+    };
+
+    fn contract() -> ContractClass {
+        let (_, contract) = load_starknet_contract! {
+            #[starknet::interface]
+            trait ISimpleStorage<TContractState> {
+                fn get(self: @TContractState, x: felt252) -> felt252;
+            }
+
+            #[starknet::contract]
+            mod contract {
+                #[storage]
+                struct Storage {}
+
+                #[abi(embed_v0)]
+                impl ISimpleStorageImpl of super::ISimpleStorage<ContractState> {
+                    fn get(self: @ContractState, x: felt252) -> felt252 {
+                        x * 2
+                    }
+                }
+            }
+        };
+        contract
+    }
+
+    fn submit(service: &CompilationService, contract: &ContractClass) -> CompilationHandle {
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+        service.submit(
+            contract.extract_sierra_program().unwrap(),
+            contract.entry_points_by_type.clone(),
+            sierra_version,
+            OptLevel::None,
+            CompilationOptions::default(),
+            TargetOptions::default(),
+        )
+    }
+
+    fn run(executor: &AotContractExecutor, contract: &ContractClass) -> Vec<Felt> {
+        let selector = &contract.entry_points_by_type.external[0].selector;
+        executor
+            .run(
+                Felt::from(selector),
+                &[21.into()],
+                u64::MAX,
+                None,
+                &mut StubSyscallHandler::default(),
+            )
+            .unwrap()
+            .return_values
+    }
+
+    #[test]
+    fn test_service_wait() {
+        let contract = contract();
+        let service = CompilationService::new(2);
+
+        let handles = (0..3)
+            .map(|_| submit(&service, &contract))
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let executor = handle.wait().unwrap();
+            assert_eq!(run(&executor, &contract), vec![Felt::from(42)]);
+        }
+    }
+
+    #[test]
+    fn test_service_options() {
+        let contract = contract();
+        let service = CompilationService::new(1);
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+        let submit = |options, target| {
+            service.submit(
+                contract.extract_sierra_program().unwrap(),
+                contract.entry_points_by_type.clone(),
+                sierra_version,
+                OptLevel::None,
+                options,
+                target,
+            )
+        };
+
+        let handle = submit(
+            CompilationOptions {
+                codegen_units: 2,
+                ..Default::default()
+            },
+            TargetOptions::default(),
+        );
+        let executor = handle.wait().unwrap();
+        assert_eq!(run(&executor, &contract), vec![Felt::from(42)]);
+
+        // The executors are loaded by the service, so they must target the host.
+        let handle = submit(
+            CompilationOptions::default(),
+            TargetOptions {
+                triple: Some("riscv64-unknown-linux-gnu".to_string()),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(handle.wait(), Err(Error::UnsupportedTarget(_))));
+    }
+
+    #[test]
+    fn test_service_poll() {
+        let contract = contract();
+        let service = CompilationService::new(1);
+
+        let mut handle = submit(&service, &contract);
+        let executor = loop {
+            match handle.try_get() {
+                Some(result) => break result.unwrap(),
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        assert!(handle.is_ready());
+        assert!(handle.try_get().is_none());
+        assert_eq!(run(&executor, &contract), vec![Felt::from(42)]);
+    }
+
+    #[test]
+    fn test_service_future() {
+        struct Flag(AtomicBool);
+        impl Wake for Flag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let contract = contract();
+        let service = CompilationService::new(1);
+
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut handle = submit(&service, &contract);
+        let executor = loop {
+            match Pin::new(&mut handle).poll(&mut cx) {
+                Poll::Ready(result) => break result.unwrap(),
+                Poll::Pending => {
+                    while !flag.0.swap(false, Ordering::SeqCst) {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                }
+            }
+        };
+        assert_eq!(run(&executor, &contract), vec![Felt::from(42)]);
+    }
+
+    #[test]
+    fn test_service_cancel() {
+        let contract = contract();
+        let service = CompilationService::new(1);
+
+        // The single worker is busy with the first contract, so the second one is still queued.
+        let first = submit(&service, &contract);
+        let second = submit(&service, &contract);
+        second.cancel();
+
+        assert!(second.is_ready());
+        assert!(matches!(second.wait(), Err(Error::CompilationCancelled)));
+        assert!(first.wait().is_ok());
+    }
+}
@@ -0,0 +1,471 @@
+//! # Typed invocations
+//!
+//! Statically typed alternative to `invoke_dynamic`. Instead of building [`Value`] trees, the
+//! arguments and return values are plain Rust types which know how to map themselves to their
+//! Sierra counterparts. The function signature is checked against the Rust types on the first
+//! invocation and the result is cached, so later invocations only write the arguments directly
+//! into the trampoline buffer.
+//!
+//! [`Value`]: crate::values::Value
+
+use super::{
+    guard::GuardConfig,
+    invoke_with_plan,
+    plan::{InvocationPlan, ParamSlot},
+};
+use crate::{
+    arch::AbiArgument,
+    error::{panic::ToNativeAssertError, Error, Result},
+    execution_result::BuiltinStats,
+    starknet::StarknetSyscallHandler,
+    types::TypeBuilder,
+};
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::StarkNetTypeConcrete,
+    },
+    ids::{ConcreteTypeId, FunctionId},
+    program_registry::ProgramRegistry,
+};
+use starknet_types_core::felt::Felt;
+use std::{alloc::Layout, ptr::NonNull, slice};
+
+/// The result of a typed invocation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TypedExecutionResult<R> {
+    pub remaining_gas: Option<u64>,
+    pub return_values: R,
+    pub builtin_stats: BuiltinStats,
+}
+
+/// Implemented by the Rust types which can be passed to and returned from Sierra functions
+/// without going through [`Value`](crate::values::Value).
+pub trait TypedValue: Sized + 'static {
+    /// Return whether the Sierra type can be represented by `Self`.
+    ///
+    /// `NonZero<T>` and `Snapshot<T>` have already been unwrapped when this method is called.
+    fn is_compatible(type_info: &CoreTypeConcrete) -> bool;
+
+    /// Serialize the value into the trampoline buffer.
+    fn write_argument(&self, buffer: &mut Vec<u8>) -> Result<()>;
+
+    /// Read the value from memory.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must point to an aligned and initialized value of a compatible Sierra type.
+    unsafe fn read_from_ptr(ptr: NonNull<()>) -> Self;
+
+    /// Read the value from the return registers.
+    fn read_from_registers(registers: &[u64]) -> Result<Self>;
+}
+
+macro_rules! impl_typed_value_for_integers {
+    ( $( $ty:ty => $variant:ident ),* $(,)? ) => {
+        $(
+            impl TypedValue for $ty {
+                fn is_compatible(type_info: &CoreTypeConcrete) -> bool {
+                    matches!(type_info, CoreTypeConcrete::$variant(_))
+                }
+
+                fn write_argument(&self, buffer: &mut Vec<u8>) -> Result<()> {
+                    self.to_bytes(buffer, |_| unreachable!())
+                }
+
+                unsafe fn read_from_ptr(ptr: NonNull<()>) -> Self {
+                    ptr.cast::<Self>().read()
+                }
+
+                fn read_from_registers(registers: &[u64]) -> Result<Self> {
+                    // Truncating the 128 bit value yields the correct result for every width.
+                    Ok((((registers[1] as u128) << 64) | registers[0] as u128) as Self)
+                }
+            }
+        )*
+    };
+}
+
+impl_typed_value_for_integers! {
+    u8 => Uint8,
+    u16 => Uint16,
+    u32 => Uint32,
+    u64 => Uint64,
+    u128 => Uint128,
+    i8 => Sint8,
+    i16 => Sint16,
+    i32 => Sint32,
+    i64 => Sint64,
+    i128 => Sint128,
+}
+
+impl TypedValue for Felt {
+    fn is_compatible(type_info: &CoreTypeConcrete) -> bool {
+        matches!(
+            type_info,
+            CoreTypeConcrete::Felt252(_)
+                | CoreTypeConcrete::StarkNet(
+                    StarkNetTypeConcrete::ClassHash(_)
+                        | StarkNetTypeConcrete::ContractAddress(_)
+                        | StarkNetTypeConcrete::StorageAddress(_)
+                        | StarkNetTypeConcrete::StorageBaseAddress(_)
+                )
+        )
+    }
+
+    fn write_argument(&self, buffer: &mut Vec<u8>) -> Result<()> {
+        self.to_bytes(buffer, |_| unreachable!())
+    }
+
+    unsafe fn read_from_ptr(ptr: NonNull<()>) -> Self {
+        let mut data = ptr.cast::<[u8; 32]>().read();
+        data[31] &= 0x0F; // Filter out first 4 bits (they're outside an i252).
+        Felt::from_bytes_le(&data)
+    }
+
+    fn read_from_registers(registers: &[u64]) -> Result<Self> {
+        // Since x86_64's return values hold at most two different 64bit registers, felts will
+        // always be returned by memory on that architecture.
+        if registers.len() < 4 {
+            return Err(Error::ParseAttributeError);
+        }
+
+        let mut data = [0u8; 32];
+        for (chunk, register) in data.chunks_exact_mut(8).zip(registers) {
+            chunk.copy_from_slice(&register.to_le_bytes());
+        }
+        data[31] &= 0x0F; // Filter out first 4 bits (they're outside an i252).
+
+        Ok(Felt::from_bytes_le(&data))
+    }
+}
+
+/// Implemented by tuples of [`TypedValue`]s, which represent the (non-builtin) arguments of a
+/// function.
+pub trait TypedArguments: 'static {
+    /// The number of arguments.
+    const LEN: usize;
+
+    /// Return whether the argument at `index` can represent the given Sierra type.
+    fn is_compatible(index: usize, type_info: &CoreTypeConcrete) -> bool;
+
+    /// Serialize the argument at `index` into the trampoline buffer.
+    fn write_argument(&self, index: usize, buffer: &mut Vec<u8>) -> Result<()>;
+}
+
+/// Implemented by tuples of [`TypedValue`]s, which represent the (non-builtin) return values of
+/// a function.
+pub trait TypedReturns: Sized + 'static {
+    /// The number of return values.
+    const LEN: usize;
+
+    /// Return whether the return value at `index` can represent the given Sierra type.
+    fn is_compatible(index: usize, type_info: &CoreTypeConcrete) -> bool;
+
+    /// Read the return values in order.
+    fn read(reader: &mut ReturnReader) -> Result<Self>;
+}
+
+/// Sequential reader over the return values of a typed invocation.
+pub struct ReturnReader<'a> {
+    return_ptr: Option<NonNull<()>>,
+    registers: &'a [u64],
+    layouts: slice::Iter<'a, Layout>,
+}
+
+impl ReturnReader<'_> {
+    /// Read the next return value.
+    pub fn read<T: TypedValue>(&mut self) -> Result<T> {
+        let layout = self
+            .layouts
+            .next()
+            .to_native_assert_error("return value should have been checked")?;
+
+        match &mut self.return_ptr {
+            Some(return_ptr) => unsafe {
+                let align_offset = return_ptr
+                    .cast::<u8>()
+                    .as_ptr()
+                    .align_offset(layout.align());
+                let value_ptr = return_ptr.cast::<u8>().as_ptr().add(align_offset);
+
+                *return_ptr = NonNull::new_unchecked(value_ptr.add(layout.size())).cast();
+                Ok(T::read_from_ptr(NonNull::new_unchecked(value_ptr).cast()))
+            },
+            None => T::read_from_registers(self.registers),
+        }
+    }
+}
+
+impl TypedArguments for () {
+    const LEN: usize = 0;
+
+    fn is_compatible(_index: usize, _type_info: &CoreTypeConcrete) -> bool {
+        false
+    }
+
+    fn write_argument(&self, index: usize, _buffer: &mut Vec<u8>) -> Result<()> {
+        crate::native_panic!("argument index out of bounds: {index}")
+    }
+}
+
+impl TypedReturns for () {
+    const LEN: usize = 0;
+
+    fn is_compatible(_index: usize, _type_info: &CoreTypeConcrete) -> bool {
+        false
+    }
+
+    fn read(_reader: &mut ReturnReader) -> Result<Self> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_typed_tuples {
+    ( $( ( $len:literal; $( $name:ident : $idx:tt ),+ ) )* ) => {
+        $(
+            impl<$( $name: TypedValue ),+> TypedArguments for ( $( $name, )+ ) {
+                const LEN: usize = $len;
+
+                fn is_compatible(index: usize, type_info: &CoreTypeConcrete) -> bool {
+                    match index {
+                        $( $idx => $name::is_compatible(type_info), )+
+                        _ => false,
+                    }
+                }
+
+                fn write_argument(&self, index: usize, buffer: &mut Vec<u8>) -> Result<()> {
+                    match index {
+                        $( $idx => self.$idx.write_argument(buffer), )+
+                        _ => crate::native_panic!("argument index out of bounds: {index}"),
+                    }
+                }
+            }
+
+            impl<$( $name: TypedValue ),+> TypedReturns for ( $( $name, )+ ) {
+                const LEN: usize = $len;
+
+                fn is_compatible(index: usize, type_info: &CoreTypeConcrete) -> bool {
+                    match index {
+                        $( $idx => $name::is_compatible(type_info), )+
+                        _ => false,
+                    }
+                }
+
+                fn read(reader: &mut ReturnReader) -> Result<Self> {
+                    Ok(( $( reader.read::<$name>()?, )+ ))
+                }
+            }
+        )*
+    };
+}
+
+impl_typed_tuples! {
+    (1; T0: 0)
+    (2; T0: 0, T1: 1)
+    (3; T0: 0, T1: 1, T2: 2)
+    (4; T0: 0, T1: 1, T2: 2, T3: 3)
+    (5; T0: 0, T1: 1, T2: 2, T3: 3, T4: 4)
+    (6; T0: 0, T1: 1, T2: 2, T3: 3, T4: 4, T5: 5)
+    (7; T0: 0, T1: 1, T2: 2, T3: 3, T4: 4, T5: 5, T6: 6)
+    (8; T0: 0, T1: 1, T2: 2, T3: 3, T4: 4, T5: 5, T6: 6, T7: 7)
+}
+
+/// Follow `NonZero<T>` and `Snapshot<T>` down to `T`, since they share the same representation.
+fn resolve_type<'a>(
+    registry: &'a ProgramRegistry<CoreType, CoreLibfunc>,
+    mut type_info: &'a CoreTypeConcrete,
+) -> Result<&'a CoreTypeConcrete> {
+    while let CoreTypeConcrete::NonZero(info) | CoreTypeConcrete::Snapshot(info) = type_info {
+        type_info = registry.get_type(&info.ty)?;
+    }
+
+    Ok(type_info)
+}
+
+fn type_name(type_id: &ConcreteTypeId) -> String {
+    type_id
+        .debug_name
+        .as_ref()
+        .map(|x| x.to_string())
+        .unwrap_or_else(|| type_id.to_string())
+}
+
+/// The outcome of checking a function's signature against the Rust types of a typed invocation.
+///
+/// Built once per function and pair of argument and return types, and cached by the executors.
+#[derive(Debug)]
+pub(crate) struct TypedPlan {
+    /// The layouts of the return values, in order.
+    return_layouts: Vec<Layout>,
+}
+
+impl TypedPlan {
+    /// Check the function's signature against `A` and `R`.
+    pub fn new<A: TypedArguments, R: TypedReturns>(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        plan: &InvocationPlan,
+    ) -> Result<Self> {
+        let mut num_args = 0;
+        for param in &plan.params {
+            if let ParamSlot::Value(type_id) = param {
+                let type_info = registry.get_type(type_id)?;
+                if !A::is_compatible(num_args, resolve_type(registry, type_info)?) {
+                    return Err(Error::SignatureMismatch(format!(
+                        "argument #{num_args} of type '{}' is not compatible with the given Rust type",
+                        type_name(type_id),
+                    )));
+                }
+                num_args += 1;
+            }
+        }
+        if num_args != A::LEN {
+            return Err(Error::SignatureMismatch(format!(
+                "expected {num_args} arguments but {} were given",
+                A::LEN,
+            )));
+        }
+
+        let mut return_layouts = Vec::with_capacity(R::LEN);
+        if let Some(type_id) = &plan.return_type {
+            let type_info = registry.get_type(type_id)?;
+            if !R::is_compatible(0, resolve_type(registry, type_info)?) {
+                return Err(Error::SignatureMismatch(format!(
+                    "return value of type '{}' is not compatible with the given Rust type",
+                    type_name(type_id),
+                )));
+            }
+            return_layouts.push(type_info.layout(registry)?);
+        }
+        if return_layouts.len() != R::LEN {
+            return Err(Error::SignatureMismatch(format!(
+                "expected {} return values but {} were given",
+                return_layouts.len(),
+                R::LEN,
+            )));
+        }
+
+        Ok(Self { return_layouts })
+    }
+}
+
+/// Internal method.
+///
+/// Typed counterpart of `invoke_dynamic`. The signature must have been checked against `A` and `R`
+/// by `TypedPlan::new`, so the arguments can be written directly into the trampoline buffer.
+pub(super) fn invoke_typed<A: TypedArguments, R: TypedReturns>(
+    function_id: &FunctionId,
+    plan: &InvocationPlan,
+    typed_plan: &TypedPlan,
+    args: &A,
+    gas: u64,
+    syscall_handler: Option<impl StarknetSyscallHandler>,
+    guard_config: GuardConfig,
+) -> Result<TypedExecutionResult<R>> {
+    let (remaining_gas, builtin_stats, return_values) = invoke_with_plan(
+        function_id,
+        plan,
+        gas,
+        syscall_handler,
+        guard_config,
+        |index, _, invoke_data| args.write_argument(index, invoke_data),
+        |return_ptr, ret_registers| {
+            R::read(&mut ReturnReader {
+                return_ptr,
+                registers: &ret_registers,
+                layouts: typed_plan.return_layouts.iter(),
+            })
+        },
+    )?;
+
+    Ok(TypedExecutionResult {
+        remaining_gas,
+        return_values,
+        builtin_stats,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedPlan;
+    use crate::{
+        context::NativeContext,
+        error::Error,
+        executor::{plan::InvocationPlan, AotNativeExecutor},
+        utils::test::load_cairo,
+        OptLevel,
+    };
+    use cairo_lang_sierra::{
+        extensions::core::{CoreLibfunc, CoreType},
+        program::Program,
+        program_registry::ProgramRegistry,
+    };
+    use rstest::*;
+    use starknet_types_core::felt::Felt;
+    use std::ptr::null;
+
+    #[fixture]
+    fn program() -> Program {
+        let (_, program) = load_cairo! {
+            fn run_test(a: u32, b: felt252) -> felt252 {
+                a.into() + b
+            }
+
+            fn widen(a: u8) -> u16 {
+                a.into()
+            }
+        };
+        program
+    }
+
+    #[rstest]
+    fn test_invoke_typed(program: Program) {
+        let native_context = NativeContext::new();
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+
+        let result = executor
+            .invoke::<(u32, Felt), (Felt,)>(&program.funcs[0].id, (1, Felt::from(41)), None)
+            .unwrap();
+        assert_eq!(result.return_values, (Felt::from(42),));
+
+        let result = executor
+            .invoke::<(u8,), (u16,)>(&program.funcs[1].id, (255,), None)
+            .unwrap();
+        assert_eq!(result.return_values, (255,));
+    }
+
+    #[rstest]
+    fn test_invoke_typed_signature_mismatch(program: Program) {
+        let native_context = NativeContext::new();
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::default()).unwrap();
+
+        let result = executor.invoke::<(u64, Felt), (Felt,)>(
+            &program.funcs[0].id,
+            (1, Felt::from(41)),
+            None,
+        );
+        assert!(matches!(result, Err(Error::SignatureMismatch(_))));
+
+        let result = executor.invoke::<(u8, u8), (u16,)>(&program.funcs[1].id, (1, 2), None);
+        assert!(matches!(result, Err(Error::SignatureMismatch(_))));
+    }
+
+    #[rstest]
+    fn test_typed_plan(program: Program) {
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap();
+        let plan = InvocationPlan::new(&registry, null(), &program.funcs[1].signature).unwrap();
+
+        let typed_plan = TypedPlan::new::<(u8,), (u16,)>(&registry, &plan).unwrap();
+        assert_eq!(typed_plan.return_layouts.len(), 1);
+        assert!(matches!(
+            TypedPlan::new::<(u8,), (u32,)>(&registry, &plan),
+            Err(Error::SignatureMismatch(_))
+        ));
+    }
+}
@@ -3,43 +3,74 @@
 //! This is a "hotfix" for missing Rust interfaces to the C/C++ libraries we use, namely LLVM/MLIR
 //! APIs that are missing from melior.
 
-use crate::error::{panic::ToNativeAssertError, Error, Result};
+use crate::{
+    error::{panic::ToNativeAssertError, Error, Result},
+    limits::{self, ResourceGuard},
+    options::CompilationOptions,
+    statistics::CompilationStats,
+};
 use llvm_sys::{
+    bit_reader::LLVMParseBitcodeInContext2,
+    bit_writer::LLVMWriteBitcodeToMemoryBuffer,
     core::{
-        LLVMContextCreate, LLVMContextDispose, LLVMDisposeMemoryBuffer, LLVMDisposeMessage,
-        LLVMDisposeModule, LLVMGetBufferSize, LLVMGetBufferStart,
+        LLVMAddFunction, LLVMAddGlobal, LLVMContextCreate, LLVMContextDispose,
+        LLVMCreateMemoryBufferWithMemoryRange, LLVMDeleteFunction, LLVMDeleteGlobal,
+        LLVMDisposeMemoryBuffer, LLVMDisposeMessage, LLVMDisposeModule, LLVMGetBufferSize,
+        LLVMGetBufferStart, LLVMGetFirstBasicBlock, LLVMGetFirstFunction, LLVMGetFirstGlobal,
+        LLVMGetFirstInstruction, LLVMGetFunctionCallConv, LLVMGetLinkage, LLVMGetNextBasicBlock,
+        LLVMGetNextFunction, LLVMGetNextGlobal, LLVMGetNextInstruction, LLVMGetValueName2,
+        LLVMGetVisibility, LLVMGlobalGetValueType, LLVMIsDeclaration, LLVMIsGlobalConstant,
+        LLVMIsThreadLocal, LLVMPrintModuleToString, LLVMReplaceAllUsesWith,
+        LLVMSetFunctionCallConv, LLVMSetGlobalConstant, LLVMSetLinkage, LLVMSetThreadLocal,
+        LLVMSetValueName2, LLVMSetVisibility,
     },
-    error::LLVMGetErrorMessage,
-    prelude::LLVMMemoryBufferRef,
+    error::{LLVMDisposeErrorMessage, LLVMGetErrorMessage},
+    prelude::{LLVMContextRef, LLVMMemoryBufferRef, LLVMModuleRef, LLVMValueRef},
     target::{
-        LLVM_InitializeAllAsmParsers, LLVM_InitializeAllAsmPrinters, LLVM_InitializeAllTargetInfos,
-        LLVM_InitializeAllTargetMCs, LLVM_InitializeAllTargets,
+        LLVMCopyStringRepOfTargetData, LLVMDisposeTargetData, LLVM_InitializeAllAsmParsers,
+        LLVM_InitializeAllAsmPrinters, LLVM_InitializeAllTargetInfos, LLVM_InitializeAllTargetMCs,
+        LLVM_InitializeAllTargets,
     },
     target_machine::{
-        LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMCreateTargetMachine,
-        LLVMDisposeTargetMachine, LLVMGetDefaultTargetTriple, LLVMGetHostCPUFeatures,
-        LLVMGetHostCPUName, LLVMGetTargetFromTriple, LLVMRelocMode,
-        LLVMTargetMachineEmitToMemoryBuffer, LLVMTargetRef,
+        LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMCreateTargetDataLayout,
+        LLVMCreateTargetMachine, LLVMDisposeTargetMachine, LLVMGetDefaultTargetTriple,
+        LLVMGetHostCPUFeatures, LLVMGetHostCPUName, LLVMGetTargetFromTriple,
+        LLVMNormalizeTargetTriple, LLVMRelocMode, LLVMTargetMachineEmitToMemoryBuffer,
+        LLVMTargetMachineRef, LLVMTargetRef,
     },
     transforms::pass_builder::{
         LLVMCreatePassBuilderOptions, LLVMDisposePassBuilderOptions, LLVMRunPasses,
     },
+    LLVMLinkage, LLVMVisibility,
 };
 use melior::ir::{Module, Type, TypeLike};
 use mlir_sys::{mlirLLVMStructTypeGetElementType, mlirTranslateModuleToLLVMIR};
+use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
-    ffi::{CStr, CString},
+    collections::HashSet,
+    ffi::{c_char, CStr, CString},
     io::Write,
     mem::MaybeUninit,
-    path::Path,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
     ptr::{addr_of_mut, null_mut},
-    sync::OnceLock,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
     time::Instant,
 };
 use tempfile::NamedTempFile;
 use tracing::trace;
 
+#[cfg(all(
+    target_os = "linux",
+    target_env = "gnu",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linker;
+
 /// For any `!llvm.struct<...>` type, return the MLIR type of the field at the requested index.
 pub fn get_struct_field_type_at<'c>(r#type: &Type<'c>, index: usize) -> Type<'c> {
     assert!(r#type.is_llvm_struct_type());
@@ -52,7 +83,9 @@ pub fn get_struct_field_type_at<'c>(r#type: &Type<'c>, index: usize) -> Type<'c>
 }
 
 /// Optimization levels.
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
 pub enum OptLevel {
     None,
     Less,
@@ -94,8 +127,499 @@ impl From<u8> for OptLevel {
     }
 }
 
+/// The platform to generate code for.
+///
+/// By default the code is generated for the host, using all the features its CPU supports. When
+/// cross-compiling, the code can only be loaded on machines matching the target.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TargetOptions {
+    /// The target triple, like `aarch64-unknown-linux-gnu`. Defaults to the host's.
+    pub triple: Option<String>,
+    /// The target CPU, like `neoverse-n1`. Defaults to the host's CPU when targeting the host, and
+    /// to `generic` otherwise.
+    pub cpu: Option<String>,
+    /// The target CPU features, like `+avx2,-sse4a`. Defaults to the host's CPU features when
+    /// targeting the host, and to none otherwise.
+    pub features: Option<String>,
+    /// The relocation model.
+    pub reloc_model: RelocModel,
+    /// The code model.
+    pub code_model: CodeModel,
+}
+
+impl TargetOptions {
+    /// Target the host's architecture, but only using the features of a portable CPU baseline.
+    ///
+    /// The generated code doesn't depend on the machine compiling it, therefore it can be shared
+    /// with any machine supporting the baseline.
+    ///
+    /// Fails with [`Error::CpuBaselineMismatch`] if the baseline is for another architecture.
+    pub fn portable(baseline: CpuBaseline) -> Result<Self> {
+        let host_triple = get_host_triple();
+        if !baseline.supports_triple(&host_triple) {
+            return Err(Error::CpuBaselineMismatch {
+                baseline,
+                triple: host_triple,
+            });
+        }
+
+        Ok(Self {
+            cpu: Some(baseline.cpu_name().to_string()),
+            features: Some(
+                baseline
+                    .features()
+                    .iter()
+                    .map(|x| format!("+{x}"))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            ..Default::default()
+        })
+    }
+
+    /// The CPU and features the code will be generated for.
+    pub fn target_cpu(&self) -> TargetCpu {
+        let (_, name, features) = self.resolve_strings();
+        TargetCpu { name, features }
+    }
+
+    /// Return whether the code is generated for the host's triple, therefore it can be run by the
+    /// executors.
+    pub fn is_host(&self) -> bool {
+        self.triple.as_ref().is_none_or(|x| *x == get_host_triple())
+    }
+
+    /// Fail with [`Error::UnsupportedTarget`] unless the code is generated for the host's triple.
+    pub(crate) fn check_host(&self) -> Result<()> {
+        match &self.triple {
+            Some(triple) if !self.is_host() => Err(Error::UnsupportedTarget(triple.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// The resolved target triple, CPU and features.
+    fn resolve(&self) -> Result<(CString, CString, CString)> {
+        let (triple, cpu, features) = self.resolve_strings();
+
+        let to_c_string = |value: String| {
+            CString::new(value).map_err(|_| {
+                Error::LLVMCompileError("target options can't contain null bytes".to_string())
+            })
+        };
+        Ok((
+            to_c_string(triple)?,
+            to_c_string(cpu)?,
+            to_c_string(features)?,
+        ))
+    }
+
+    fn resolve_strings(&self) -> (String, String, String) {
+        let is_host = self.is_host();
+
+        let triple = self.triple.clone().unwrap_or_else(get_host_triple);
+        let cpu = match &self.cpu {
+            Some(cpu) => cpu.clone(),
+            None if is_host => get_host_cpu_name(),
+            None => "generic".to_string(),
+        };
+        let features = match &self.features {
+            Some(features) => features.clone(),
+            None if is_host => get_host_cpu_features(),
+            None => String::new(),
+        };
+
+        (triple, cpu, features)
+    }
+}
+
+/// Portable CPU baselines, named after their LLVM CPUs.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CpuBaseline {
+    /// Any x86-64 CPU.
+    X86_64,
+    /// x86-64 CPUs supporting up to SSE4.2 (Nehalem and later).
+    X86_64V2,
+    /// x86-64 CPUs supporting AVX2 (Haswell, Zen and later).
+    X86_64V3,
+    /// x86-64 CPUs supporting AVX-512 (Skylake-X, Zen 4 and later).
+    X86_64V4,
+    /// Any ARMv8-A CPU.
+    Armv8A,
+}
+
+impl CpuBaseline {
+    /// Whether the baseline is for the architecture of the given target triple.
+    pub fn supports_triple(self, triple: &str) -> bool {
+        let arch = triple.split('-').next().unwrap_or_default();
+        match self {
+            Self::X86_64 | Self::X86_64V2 | Self::X86_64V3 | Self::X86_64V4 => arch == "x86_64",
+            // Apple names the architecture `arm64`.
+            Self::Armv8A => matches!(arch, "aarch64" | "arm64"),
+        }
+    }
+
+    /// The LLVM CPU name.
+    pub const fn cpu_name(self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86-64",
+            Self::X86_64V2 => "x86-64-v2",
+            Self::X86_64V3 => "x86-64-v3",
+            Self::X86_64V4 => "x86-64-v4",
+            Self::Armv8A => "generic",
+        }
+    }
+
+    /// The LLVM features the baseline guarantees.
+    ///
+    /// They're recorded explicitly (instead of relying on the CPU name) so that they can be checked
+    /// against the features of the machine loading the code.
+    pub fn features(self) -> Vec<&'static str> {
+        let mut features = match self {
+            Self::X86_64 | Self::X86_64V2 | Self::X86_64V3 | Self::X86_64V4 => {
+                vec!["cmov", "cx8", "fxsr", "sse", "sse2"]
+            }
+            Self::Armv8A => return vec!["fp-armv8", "neon"],
+        };
+
+        if matches!(self, Self::X86_64V2 | Self::X86_64V3 | Self::X86_64V4) {
+            features.extend([
+                "cx16", "popcnt", "sahf", "sse3", "sse4.1", "sse4.2", "ssse3",
+            ]);
+        }
+        if matches!(self, Self::X86_64V3 | Self::X86_64V4) {
+            features.extend([
+                "avx", "avx2", "bmi", "bmi2", "f16c", "fma", "lzcnt", "movbe", "xsave",
+            ]);
+        }
+        if matches!(self, Self::X86_64V4) {
+            features.extend(["avx512bw", "avx512cd", "avx512dq", "avx512f", "avx512vl"]);
+        }
+
+        features
+    }
+}
+
+/// The CPU some code was generated for, recorded so that it's not loaded on machines which can't
+/// run it.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TargetCpu {
+    /// The LLVM CPU name.
+    pub name: String,
+    /// The LLVM features, like `+avx2,-sse4a`.
+    pub features: String,
+}
+
+impl TargetCpu {
+    /// Check whether the host's CPU can run the code.
+    ///
+    /// Only the features explicitly enabled are checked, since the ones implied by the CPU's name
+    /// aren't known. When the host's features can't be detected the check always succeeds.
+    pub fn check_host(&self) -> Result<()> {
+        let host_features = get_host_cpu_features();
+        if host_features.is_empty() {
+            return Ok(());
+        }
+
+        let host_features = host_features
+            .split(',')
+            .filter_map(|x| x.strip_prefix('+'))
+            .collect::<HashSet<_>>();
+        let missing_features = self
+            .features
+            .split(',')
+            .filter_map(|x| x.strip_prefix('+'))
+            .filter(|x| !host_features.contains(x))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        if missing_features.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedCpu {
+                cpu: self.name.clone(),
+                missing_features,
+            })
+        }
+    }
+}
+
+/// Relocation models.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum RelocModel {
+    Static,
+    /// Position independent code, required for shared libraries on most platforms.
+    #[default]
+    Pic,
+    DynamicNoPic,
+}
+
+impl From<RelocModel> for LLVMRelocMode {
+    fn from(value: RelocModel) -> Self {
+        match value {
+            RelocModel::Static => LLVMRelocMode::LLVMRelocStatic,
+            RelocModel::Pic => LLVMRelocMode::LLVMRelocPIC,
+            RelocModel::DynamicNoPic => LLVMRelocMode::LLVMRelocDynamicNoPic,
+        }
+    }
+}
+
+/// Code models.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum CodeModel {
+    /// The target's default code model.
+    #[default]
+    Default,
+    Tiny,
+    Small,
+    Kernel,
+    Medium,
+    Large,
+}
+
+impl From<CodeModel> for LLVMCodeModel {
+    fn from(value: CodeModel) -> Self {
+        match value {
+            CodeModel::Default => LLVMCodeModel::LLVMCodeModelDefault,
+            CodeModel::Tiny => LLVMCodeModel::LLVMCodeModelTiny,
+            CodeModel::Small => LLVMCodeModel::LLVMCodeModelSmall,
+            CodeModel::Kernel => LLVMCodeModel::LLVMCodeModelKernel,
+            CodeModel::Medium => LLVMCodeModel::LLVMCodeModelMedium,
+            CodeModel::Large => LLVMCodeModel::LLVMCodeModelLarge,
+        }
+    }
+}
+
 /// Converts a MLIR module to a compile object, that can be linked with a linker.
-pub fn module_to_object(module: &Module<'_>, opt_level: OptLevel) -> Result<Vec<u8>> {
+///
+/// The module must have been compiled for the same target, which defaults to the host.
+pub fn module_to_object(
+    module: &Module<'_>,
+    opt_level: OptLevel,
+    target: Option<&TargetOptions>,
+) -> Result<Vec<u8>> {
+    module_to_object_with_options(module, opt_level, target, &CompilationOptions::default())
+}
+
+/// Like [`module_to_object`], but running the LLVM pipeline given by the [`CompilationOptions`].
+///
+/// The code is always emitted as a single object, regardless of the options' `codegen_units`
+/// (see [`module_to_objects`]).
+pub fn module_to_object_with_options(
+    module: &Module<'_>,
+    opt_level: OptLevel,
+    target: Option<&TargetOptions>,
+    options: &CompilationOptions,
+) -> Result<Vec<u8>> {
+    let codegen = Codegen {
+        opt_level,
+        target: target.cloned().unwrap_or_default(),
+        options,
+        output: Output::Object,
+        guard: None,
+    };
+
+    module_to_output(module, &codegen, &mut CompilationStats::default())
+}
+
+/// Converts a MLIR module into textual LLVM IR, after running the LLVM passes on it.
+///
+/// It's the code which [`module_to_object_with_options`] would generate the object from.
+pub fn module_to_llvm_ir(
+    module: &Module<'_>,
+    opt_level: OptLevel,
+    target: Option<&TargetOptions>,
+    options: &CompilationOptions,
+) -> Result<String> {
+    let codegen = Codegen {
+        opt_level,
+        target: target.cloned().unwrap_or_default(),
+        options,
+        output: Output::LlvmIr,
+        guard: None,
+    };
+
+    let data = module_to_output(module, &codegen, &mut CompilationStats::default())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Converts a MLIR module into the target's assembly, as [`module_to_object_with_options`] would
+/// generate it.
+pub fn module_to_assembly(
+    module: &Module<'_>,
+    opt_level: OptLevel,
+    target: Option<&TargetOptions>,
+    options: &CompilationOptions,
+) -> Result<String> {
+    let codegen = Codegen {
+        opt_level,
+        target: target.cloned().unwrap_or_default(),
+        options,
+        output: Output::Assembly,
+        guard: None,
+    };
+
+    let data = module_to_output(module, &codegen, &mut CompilationStats::default())?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+fn module_to_output(
+    module: &Module<'_>,
+    codegen: &Codegen,
+    stats: &mut CompilationStats,
+) -> Result<Vec<u8>> {
+    initialize_llvm();
+
+    let data = unsafe {
+        let llvm_context = LLVMContextCreate();
+        let llvm_module = translate_module(module, llvm_context, stats);
+
+        let data = limits::check(codegen.guard).and_then(|()| emit(llvm_module, codegen, stats));
+
+        LLVMDisposeModule(llvm_module);
+        LLVMContextDispose(llvm_context);
+
+        data?
+    };
+
+    limits::check(codegen.guard)?;
+    Ok(data)
+}
+
+/// Converts a MLIR module into (up to) the options' `codegen_units` compile objects, which are
+/// optimized and emitted in parallel. All of them have to be linked together.
+///
+/// The functions are distributed among the objects depending only on the module's contents,
+/// therefore the output is deterministic. It's equivalent to [`module_to_object_with_options`]'s
+/// except that functions can't be inlined across different objects.
+pub fn module_to_objects(
+    module: &Module<'_>,
+    opt_level: OptLevel,
+    target: Option<&TargetOptions>,
+    options: &CompilationOptions,
+) -> Result<Vec<Vec<u8>>> {
+    module_to_objects_with_stats(
+        module,
+        opt_level,
+        target,
+        options,
+        &mut CompilationStats::default(),
+        None,
+    )
+}
+
+/// Like [`module_to_objects`], but records the time spent on every phase and the objects' size.
+///
+/// Since the objects are emitted in parallel, the durations of the LLVM passes and the object
+/// emission are those of the slowest object. When a `guard` is given, its limits are checked
+/// between the phases.
+pub(crate) fn module_to_objects_with_stats(
+    module: &Module<'_>,
+    opt_level: OptLevel,
+    target: Option<&TargetOptions>,
+    options: &CompilationOptions,
+    stats: &mut CompilationStats,
+    guard: Option<&ResourceGuard>,
+) -> Result<Vec<Vec<u8>>> {
+    let codegen = Codegen {
+        opt_level,
+        target: target.cloned().unwrap_or_default(),
+        options,
+        output: Output::Object,
+        guard,
+    };
+
+    if options.codegen_units <= 1 {
+        return Ok(vec![module_to_output(module, &codegen, stats)?]);
+    }
+
+    initialize_llvm();
+
+    // LLVM contexts can't be shared between threads, so every partition is parsed from bitcode
+    // into its own context.
+    let (partitions, bitcode) = unsafe {
+        let llvm_context = LLVMContextCreate();
+        let llvm_module = translate_module(module, llvm_context, stats);
+
+        let partitions = partition_module(llvm_module, options.codegen_units);
+
+        let buffer = LLVMWriteBitcodeToMemoryBuffer(llvm_module);
+        let bitcode = std::slice::from_raw_parts(
+            LLVMGetBufferStart(buffer).cast::<u8>(),
+            LLVMGetBufferSize(buffer),
+        )
+        .to_vec();
+        LLVMDisposeMemoryBuffer(buffer);
+
+        LLVMDisposeModule(llvm_module);
+        LLVMContextDispose(llvm_context);
+
+        (partitions, bitcode)
+    };
+    limits::check(guard)?;
+
+    // The partitions are shared among at most one thread per core, each of them emitting the next
+    // pending partition until there are none left.
+    let num_threads = std::thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(partitions.len());
+    let next_partition = AtomicUsize::new(0);
+
+    trace!(
+        partitions = partitions.len(),
+        threads = num_threads,
+        "starting parallel codegen"
+    );
+    let pre_codegen_instant = Instant::now();
+    let mut results = std::thread::scope(|s| {
+        let handles = (0..num_threads)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next_partition.fetch_add(1, Ordering::Relaxed);
+                        let Some(functions) = partitions.get(index) else {
+                            break results;
+                        };
+
+                        let mut stats = CompilationStats::default();
+                        let result = unsafe {
+                            emit_partition(&bitcode, index == 0, functions, &codegen, &mut stats)
+                        };
+                        results.push((index, result.map(|object| (object, stats))));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(index, _)| *index);
+
+    let objects = results
+        .into_iter()
+        .map(|(_, result)| {
+            let (object, partition_stats) = result?;
+            stats.llvm_passes = stats.llvm_passes.max(partition_stats.llvm_passes);
+            stats.object_emission = stats.object_emission.max(partition_stats.object_emission);
+            stats.object_size += partition_stats.object_size;
+            Ok(object)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let codegen_time = pre_codegen_instant.elapsed().as_millis();
+    trace!(time = codegen_time, "parallel codegen finished");
+
+    limits::check(guard)?;
+    Ok(objects)
+}
+
+fn initialize_llvm() {
     static INITIALIZED: OnceLock<()> = OnceLock::new();
 
     INITIALIZED.get_or_init(|| unsafe {
@@ -105,143 +629,555 @@ pub fn module_to_object(module: &Module<'_>, opt_level: OptLevel) -> Result<Vec<
         LLVM_InitializeAllAsmPrinters();
         LLVM_InitializeAllAsmParsers();
     });
+}
 
-    unsafe {
-        let llvm_context = LLVMContextCreate();
+unsafe fn translate_module(
+    module: &Module<'_>,
+    llvm_context: LLVMContextRef,
+    stats: &mut CompilationStats,
+) -> LLVMModuleRef {
+    let op = module.as_operation().to_raw();
 
-        let op = module.as_operation().to_raw();
+    trace!("starting mlir to llvm compilation");
+    let pre_mlir_instant = Instant::now();
+    let llvm_module = mlirTranslateModuleToLLVMIR(op, llvm_context as *mut _) as *mut _;
+    stats.mlir_to_llvm = pre_mlir_instant.elapsed();
+    trace!(
+        time = stats.mlir_to_llvm.as_millis(),
+        "mlir to llvm finished"
+    );
 
-        trace!("starting mlir to llvm compilation");
-        let pre_mlir_instant = Instant::now();
-        let llvm_module = mlirTranslateModuleToLLVMIR(op, llvm_context as *mut _) as *mut _;
-        let mlir_time = pre_mlir_instant.elapsed().as_millis();
-        trace!(time = mlir_time, "mlir to llvm finished");
+    llvm_module
+}
 
-        let mut null = null_mut();
-        let mut error_buffer = addr_of_mut!(null);
+/// How the LLVM modules are optimized and emitted.
+struct Codegen<'a> {
+    opt_level: OptLevel,
+    target: TargetOptions,
+    options: &'a CompilationOptions,
+    output: Output,
+    guard: Option<&'a ResourceGuard>,
+}
 
-        let target_triple = LLVMGetDefaultTargetTriple();
-        let target_cpu = LLVMGetHostCPUName();
-        let target_cpu_features = LLVMGetHostCPUFeatures();
+/// What is emitted from the optimized LLVM modules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    LlvmIr,
+    Assembly,
+    Object,
+}
 
-        let mut target: MaybeUninit<LLVMTargetRef> = MaybeUninit::uninit();
+/// Optimizes the LLVM module and emits it as configured by the `codegen`.
+unsafe fn emit(
+    llvm_module: LLVMModuleRef,
+    codegen: &Codegen,
+    stats: &mut CompilationStats,
+) -> Result<Vec<u8>> {
+    let mut null = null_mut();
+    let error_buffer = addr_of_mut!(null);
 
-        if LLVMGetTargetFromTriple(target_triple, target.as_mut_ptr(), error_buffer) != 0 {
-            let error = CStr::from_ptr(*error_buffer);
-            let err = error.to_string_lossy().to_string();
-            LLVMDisposeMessage(*error_buffer);
-            Err(Error::LLVMCompileError(err))?;
-        } else if !(*error_buffer).is_null() {
-            LLVMDisposeMessage(*error_buffer);
-            error_buffer = addr_of_mut!(null);
-        }
+    // The passes may come from the user, so they're validated before creating anything.
+    let passes = CString::new(codegen.options.llvm_pipeline(codegen.opt_level))
+        .map_err(|_| Error::LLVMCompileError("the LLVM passes contain a null byte".to_string()))?;
 
-        let target = target.assume_init();
-
-        let machine = LLVMCreateTargetMachine(
-            target,
-            target_triple.cast(),
-            target_cpu.cast(),
-            target_cpu_features.cast(),
-            match opt_level {
-                OptLevel::None => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
-                OptLevel::Less => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
-                OptLevel::Default => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
-                OptLevel::Aggressive => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
-            },
-            LLVMRelocMode::LLVMRelocPIC,
-            LLVMCodeModel::LLVMCodeModelDefault,
-        );
+    let machine = create_target_machine(
+        &codegen.target,
+        match codegen.opt_level {
+            OptLevel::None => LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
+            OptLevel::Less => LLVMCodeGenOptLevel::LLVMCodeGenLevelLess,
+            OptLevel::Default => LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            OptLevel::Aggressive => LLVMCodeGenOptLevel::LLVMCodeGenLevelAggressive,
+        },
+    )?;
 
-        let opts = LLVMCreatePassBuilderOptions();
+    let opts = LLVMCreatePassBuilderOptions();
 
-        let opt = match opt_level {
-            OptLevel::None => 0,
-            OptLevel::Less => 1,
-            // slp-vectorizer pass did cause some issues, but after the change
-            // on function attributes it seems to not trigger them anymore.
-            // https://github.com/llvm/llvm-project/issues/107198
-            OptLevel::Default => 2,
-            OptLevel::Aggressive => 3,
-        };
-        let passes = CString::new(format!("default<O{opt}>"))
-            .to_native_assert_error("only fails if the hardcoded string contains a null byte")?;
-
-        trace!("starting llvm passes");
-        let pre_passes_instant = Instant::now();
-        let error = LLVMRunPasses(llvm_module, passes.as_ptr(), machine, opts);
-        let passes_time = pre_passes_instant.elapsed().as_millis();
-        trace!(time = passes_time, "llvm passes finished");
-
-        if !error.is_null() {
-            let msg = LLVMGetErrorMessage(error);
-            let msg = CStr::from_ptr(msg);
-            Err(Error::LLVMCompileError(msg.to_string_lossy().into_owned()))?;
+    trace!("starting llvm passes");
+    let pre_passes_instant = Instant::now();
+    let error = LLVMRunPasses(llvm_module, passes.as_ptr(), machine, opts);
+    stats.llvm_passes = pre_passes_instant.elapsed();
+    trace!(time = stats.llvm_passes.as_millis(), "llvm passes finished");
+
+    LLVMDisposePassBuilderOptions(opts);
+
+    if !error.is_null() {
+        let msg = LLVMGetErrorMessage(error);
+        let err = CStr::from_ptr(msg).to_string_lossy().into_owned();
+        LLVMDisposeErrorMessage(msg);
+        LLVMDisposeTargetMachine(machine);
+        return Err(Error::LLVMCompileError(err));
+    }
+
+    if let Err(error) = limits::check(codegen.guard) {
+        LLVMDisposeTargetMachine(machine);
+        return Err(error);
+    }
+
+    if codegen.output == Output::LlvmIr {
+        let ir = LLVMPrintModuleToString(llvm_module);
+        let data = CStr::from_ptr(ir).to_bytes().to_vec();
+        LLVMDisposeMessage(ir);
+        LLVMDisposeTargetMachine(machine);
+
+        return Ok(data);
+    }
+
+    let mut out_buf: MaybeUninit<LLVMMemoryBufferRef> = MaybeUninit::uninit();
+
+    trace!("starting llvm to object compilation");
+    let pre_llvm_compilation_instant = Instant::now();
+    let ok = LLVMTargetMachineEmitToMemoryBuffer(
+        machine,
+        llvm_module,
+        match codegen.output {
+            Output::Assembly => LLVMCodeGenFileType::LLVMAssemblyFile,
+            _ => LLVMCodeGenFileType::LLVMObjectFile,
+        },
+        error_buffer,
+        out_buf.as_mut_ptr(),
+    );
+    stats.object_emission = pre_llvm_compilation_instant.elapsed();
+    trace!(
+        time = stats.object_emission.as_millis(),
+        "llvm to object compilation finished"
+    );
+
+    if ok != 0 {
+        let error = CStr::from_ptr(*error_buffer);
+        let err = error.to_string_lossy().to_string();
+        LLVMDisposeMessage(*error_buffer);
+        LLVMDisposeTargetMachine(machine);
+        Err(Error::LLVMCompileError(err))?;
+    } else if !(*error_buffer).is_null() {
+        LLVMDisposeMessage(*error_buffer);
+    }
+
+    let out_buf = out_buf.assume_init();
+
+    let out_buf_start: *const u8 = LLVMGetBufferStart(out_buf).cast();
+    let out_buf_size = LLVMGetBufferSize(out_buf);
+
+    // keep it in rust side
+    let data = std::slice::from_raw_parts(out_buf_start, out_buf_size).to_vec();
+
+    LLVMDisposeMemoryBuffer(out_buf);
+    LLVMDisposeTargetMachine(machine);
+
+    stats.object_size += data.len();
+    Ok(data)
+}
+
+/// Distributes the module's functions into at most `codegen_units` partitions, returning the names
+/// of the functions defined in each one of them.
+///
+/// Every function and mutable global is made visible to the other partitions. The symbols which
+/// weren't exported already are given hidden visibility, so that they stay internal to the shared
+/// library.
+unsafe fn partition_module(
+    llvm_module: LLVMModuleRef,
+    codegen_units: usize,
+) -> Vec<HashSet<CString>> {
+    let mut num_symbols = 0;
+
+    let mut functions = Vec::new();
+    let mut function = LLVMGetFirstFunction(llvm_module);
+    while !function.is_null() {
+        if LLVMIsDeclaration(function) == 0 {
+            export_symbol(function, &mut num_symbols);
+            functions.push((value_name(function), count_instructions(function)));
         }
 
-        LLVMDisposePassBuilderOptions(opts);
-
-        let mut out_buf: MaybeUninit<LLVMMemoryBufferRef> = MaybeUninit::uninit();
-
-        trace!("starting llvm to object compilation");
-        let pre_llvm_compilation_instant = Instant::now();
-        let ok = LLVMTargetMachineEmitToMemoryBuffer(
-            machine,
-            llvm_module,
-            LLVMCodeGenFileType::LLVMObjectFile,
-            error_buffer,
-            out_buf.as_mut_ptr(),
-        );
-        let llvm_compilation_time = pre_llvm_compilation_instant.elapsed().as_millis();
-        trace!(
-            time = llvm_compilation_time,
-            "llvm to object compilation finished"
-        );
-
-        if ok != 0 {
-            let error = CStr::from_ptr(*error_buffer);
-            let err = error.to_string_lossy().to_string();
-            LLVMDisposeMessage(*error_buffer);
-            Err(Error::LLVMCompileError(err))?;
-        } else if !(*error_buffer).is_null() {
-            LLVMDisposeMessage(*error_buffer);
+        function = LLVMGetNextFunction(function);
+    }
+
+    let mut global = LLVMGetFirstGlobal(llvm_module);
+    while !global.is_null() {
+        // Local constants are duplicated into every partition instead.
+        if LLVMIsDeclaration(global) == 0
+            && !(is_local(global) && LLVMIsGlobalConstant(global) != 0)
+        {
+            export_symbol(global, &mut num_symbols);
         }
 
-        let out_buf = out_buf.assume_init();
+        global = LLVMGetNextGlobal(global);
+    }
 
-        let out_buf_start: *const u8 = LLVMGetBufferStart(out_buf).cast();
-        let out_buf_size = LLVMGetBufferSize(out_buf);
+    // Assign the biggest functions first, each one to the partition with the least instructions.
+    // Both the sort and `min_by_key` are stable, which keeps the assignment deterministic.
+    let mut order = (0..functions.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&index| std::cmp::Reverse(functions[index].1));
 
-        // keep it in rust side
-        let data = std::slice::from_raw_parts(out_buf_start, out_buf_size).to_vec();
+    let mut partitions = vec![(0, HashSet::new()); codegen_units.min(functions.len()).max(1)];
+    for index in order {
+        let (name, size) = &functions[index];
+        let partition = partitions
+            .iter_mut()
+            .min_by_key(|(total_size, _)| *total_size)
+            .expect("there's always at least one partition");
 
-        LLVMDisposeMemoryBuffer(out_buf);
-        LLVMDisposeTargetMachine(machine);
-        LLVMDisposeModule(llvm_module);
+        partition.0 += *size;
+        partition.1.insert(name.clone());
+    }
+
+    partitions.into_iter().map(|(_, names)| names).collect()
+}
+
+/// Parses the module and emits the object for a single partition.
+///
+/// Functions not defined in this partition are replaced by declarations. The first partition keeps
+/// all the non-constant globals, which the other ones only declare.
+unsafe fn emit_partition(
+    bitcode: &[u8],
+    is_first: bool,
+    functions: &HashSet<CString>,
+    codegen: &Codegen,
+    stats: &mut CompilationStats,
+) -> Result<Vec<u8>> {
+    let llvm_context = LLVMContextCreate();
+
+    let buffer = LLVMCreateMemoryBufferWithMemoryRange(
+        bitcode.as_ptr().cast(),
+        bitcode.len(),
+        c"partition".as_ptr(),
+        0,
+    );
+    let mut llvm_module = null_mut();
+    let failed = LLVMParseBitcodeInContext2(llvm_context, buffer, &mut llvm_module);
+    LLVMDisposeMemoryBuffer(buffer);
+    if failed != 0 {
         LLVMContextDispose(llvm_context);
+        return Err(Error::LLVMCompileError(
+            "failed to parse the partition's bitcode".to_string(),
+        ));
+    }
+
+    // Collect everything beforehand since replacing values appends new ones to the module.
+    let mut external_functions = Vec::new();
+    let mut function = LLVMGetFirstFunction(llvm_module);
+    while !function.is_null() {
+        if LLVMIsDeclaration(function) == 0 && !functions.contains(&value_name(function)) {
+            external_functions.push(function);
+        }
+
+        function = LLVMGetNextFunction(function);
+    }
+
+    let mut external_globals = Vec::new();
+    if !is_first {
+        let mut global = LLVMGetFirstGlobal(llvm_module);
+        while !global.is_null() {
+            if LLVMIsDeclaration(global) == 0
+                && !(is_local(global) && LLVMIsGlobalConstant(global) != 0)
+            {
+                external_globals.push(global);
+            }
+
+            global = LLVMGetNextGlobal(global);
+        }
+    }
+
+    for function in external_functions {
+        let name = value_name(function);
+        LLVMSetValueName2(function, c"".as_ptr(), 0);
+
+        let declaration =
+            LLVMAddFunction(llvm_module, name.as_ptr(), LLVMGlobalGetValueType(function));
+        LLVMSetFunctionCallConv(declaration, LLVMGetFunctionCallConv(function));
+        LLVMSetVisibility(declaration, LLVMGetVisibility(function));
+
+        LLVMReplaceAllUsesWith(function, declaration);
+        LLVMDeleteFunction(function);
+    }
+
+    for global in external_globals {
+        // Special globals such as `llvm.global_ctors` must only be present once.
+        if !matches!(LLVMGetLinkage(global), LLVMLinkage::LLVMAppendingLinkage) {
+            let name = value_name(global);
+            LLVMSetValueName2(global, c"".as_ptr(), 0);
+
+            let declaration =
+                LLVMAddGlobal(llvm_module, LLVMGlobalGetValueType(global), name.as_ptr());
+            LLVMSetGlobalConstant(declaration, LLVMIsGlobalConstant(global));
+            LLVMSetThreadLocal(declaration, LLVMIsThreadLocal(global));
+            LLVMSetVisibility(declaration, LLVMGetVisibility(global));
+
+            LLVMReplaceAllUsesWith(global, declaration);
+        }
+
+        LLVMDeleteGlobal(global);
+    }
+
+    let data = emit(llvm_module, codegen, stats);
 
-        Ok(data)
+    LLVMDisposeModule(llvm_module);
+    LLVMContextDispose(llvm_context);
+
+    data
+}
+
+unsafe fn is_local(value: LLVMValueRef) -> bool {
+    matches!(
+        LLVMGetLinkage(value),
+        LLVMLinkage::LLVMPrivateLinkage | LLVMLinkage::LLVMInternalLinkage
+    )
+}
+
+/// Gives external linkage to a symbol so that it can be referenced from other partitions.
+unsafe fn export_symbol(value: LLVMValueRef, num_symbols: &mut usize) {
+    if !is_local(value) {
+        return;
     }
+
+    if value_name(value).is_empty() {
+        let name = CString::new(format!("cairo_native__partition_symbol{num_symbols}"))
+            .expect("the generated name doesn't contain null bytes");
+        LLVMSetValueName2(value, name.as_ptr(), name.as_bytes().len());
+    }
+
+    LLVMSetLinkage(value, LLVMLinkage::LLVMExternalLinkage);
+    LLVMSetVisibility(value, LLVMVisibility::LLVMHiddenVisibility);
+    *num_symbols += 1;
+}
+
+unsafe fn value_name(value: LLVMValueRef) -> CString {
+    let mut length = 0;
+    let name = LLVMGetValueName2(value, &mut length);
+    CString::new(std::slice::from_raw_parts(name.cast::<u8>(), length))
+        .expect("symbol names don't contain null bytes")
+}
+
+unsafe fn count_instructions(function: LLVMValueRef) -> usize {
+    let mut count = 0;
+    let mut block = LLVMGetFirstBasicBlock(function);
+    while !block.is_null() {
+        let mut instruction = LLVMGetFirstInstruction(block);
+        while !instruction.is_null() {
+            count += 1;
+            instruction = LLVMGetNextInstruction(instruction);
+        }
+
+        block = LLVMGetNextBasicBlock(block);
+    }
+
+    count
 }
 
 /// Links the passed object into a shared library, stored on the given path.
 pub fn object_to_shared_lib(object: &[u8], output_filename: &Path) -> Result<()> {
+    objects_to_shared_lib(&[object], output_filename, &CompilationOptions::default())
+}
+
+/// Links the passed objects together into a shared library, stored on the given path.
+///
+/// The system linker is used unless the options enable the built-in one.
+pub fn objects_to_shared_lib(
+    objects: &[impl AsRef<[u8]>],
+    output_filename: &Path,
+    options: &CompilationOptions,
+) -> Result<()> {
+    if options.builtin_linker {
+        std::fs::write(output_filename, link_builtin(objects)?)?;
+        return Ok(());
+    }
+
     // linker seems to need a file and doesn't accept stdin
-    let mut file = NamedTempFile::new()?;
-    file.write_all(object)?;
-    let file = file.into_temp_path();
-
-    let file_path = file.display().to_string();
-    let output_path = output_filename.display().to_string();
-    if let Ok(x) = std::env::var("NATIVE_DEBUG_DUMP") {
-        if x == "1" || x == "true" {
-            // forget so the temp file is not deleted and the debugger can load it.
-            // its still in a temp file directory so eventually the OS will delete it, but just not instantly.
-            // todo: maybe remove it when exiting, for example using atexit.
-            std::mem::forget(file);
+    let files = objects
+        .iter()
+        .map(|object| {
+            let mut file = NamedTempFile::new()?;
+            file.write_all(object.as_ref())?;
+            Ok(file.into_temp_path())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    link_object_files(&files, output_filename)
+}
+
+/// Links the object files together into a shared library using the system linker, which unlike
+/// the built-in one keeps the debug info.
+pub(crate) fn link_object_files(paths: &[impl AsRef<Path>], output_filename: &Path) -> Result<()> {
+    run_linker(
+        paths
+            .iter()
+            .map(|x| x.as_ref().display().to_string())
+            .collect(),
+        output_filename.display().to_string(),
+        std::process::Command::new("ld"),
+    )
+}
+
+/// A shared library which only lives as long as this value.
+///
+/// On Linux it's stored in an anonymous in-memory file (see `memfd_create(2)`), therefore it never
+/// touches the filesystem. On other platforms a temporary file is used instead.
+///
+/// The library must be loaded from [`path`](Self::path), and this value kept alive for as long as
+/// it's loaded.
+#[derive(Debug)]
+pub struct EphemeralSharedLib {
+    #[cfg(target_os = "linux")]
+    fd: std::os::fd::OwnedFd,
+    #[cfg(not(target_os = "linux"))]
+    file: tempfile::TempPath,
+}
+
+impl EphemeralSharedLib {
+    /// The path from which the library can be loaded.
+    pub fn path(&self) -> PathBuf {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::fd::AsRawFd;
+            PathBuf::from(format!("/proc/self/fd/{}", self.fd.as_raw_fd()))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            self.file.to_path_buf()
         }
     }
+}
+
+/// Links the object into an [`EphemeralSharedLib`].
+///
+/// On Linux neither the object nor the shared library are written to the filesystem. It uses the
+/// system linker, see [`objects_to_ephemeral_shared_lib`] for its requirements.
+pub fn object_to_ephemeral_shared_lib(object: &[u8]) -> Result<EphemeralSharedLib> {
+    objects_to_ephemeral_shared_lib(&[object], &CompilationOptions::default())
+}
+
+/// Links the objects together into an [`EphemeralSharedLib`].
+///
+/// On Linux neither the objects nor the shared library are written to the filesystem. The system
+/// linker is used unless the options enable the built-in one.
+///
+/// On Linux, the system linker requires `ld` in the `PATH` and `procfs` mounted at `/proc`: the
+/// in-memory files are passed to `ld` as `/proc/self/fd/N` paths. Only the `ld` process inherits
+/// their descriptors. The built-in linker has neither requirement.
+pub fn objects_to_ephemeral_shared_lib(
+    objects: &[impl AsRef<[u8]>],
+    options: &CompilationOptions,
+) -> Result<EphemeralSharedLib> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::{
+            fs::File,
+            os::{
+                fd::{AsRawFd, FromRawFd, OwnedFd},
+                unix::process::CommandExt,
+            },
+        };
+
+        fn memfd_create(name: &CStr) -> Result<OwnedFd> {
+            let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
 
+            Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+        }
+
+        if options.builtin_linker {
+            let library_fd = memfd_create(c"cairo-native-library")?;
+            File::from(library_fd.try_clone()?).write_all(&link_builtin(objects)?)?;
+            return Ok(EphemeralSharedLib { fd: library_fd });
+        }
+
+        if !Path::new("/proc/self/fd").is_dir() {
+            return Err(Error::LinkError(
+                "linking in memory with `ld` requires procfs mounted at /proc".to_string(),
+            ));
+        }
+
+        let object_fds = objects
+            .iter()
+            .map(|object| {
+                let object_fd = memfd_create(c"cairo-native-object")?;
+                File::from(object_fd.try_clone()?).write_all(object.as_ref())?;
+                Ok(object_fd)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let library_fd = memfd_create(c"cairo-native-library")?;
+
+        // The linker is given the files through `/proc/self/fd/`, so they must be inherited. They
+        // stay close-on-exec in this process, and are only made inheritable in the forked child
+        // right before it executes `ld`, so that no other process (spawned concurrently by other
+        // threads, for example) ever gets them.
+        let fds = object_fds
+            .iter()
+            .chain([&library_fd])
+            .map(|fd| fd.as_raw_fd())
+            .collect::<Vec<_>>();
+        let mut linker = std::process::Command::new("ld");
+        unsafe {
+            let fds = fds.clone();
+            linker.pre_exec(move || {
+                for &fd in &fds {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) < 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+
+        let mut paths = fds.iter().map(|fd| format!("/proc/self/fd/{fd}"));
+        run_linker(
+            paths.by_ref().take(object_fds.len()).collect(),
+            paths
+                .next()
+                .to_native_assert_error("the library's fd is always present")?,
+            linker,
+        )?;
+
+        Ok(EphemeralSharedLib { fd: library_fd })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let file = NamedTempFile::new()?.into_temp_path();
+        objects_to_shared_lib(objects, &file, options)?;
+
+        Ok(EphemeralSharedLib { file })
+    }
+}
+
+/// Links the objects using the built-in linker, which doesn't depend on any external tool.
+///
+/// It's only available on glibc-based Linux hosts running on x86-64 or AArch64, and fails on the
+/// objects using features it doesn't support.
+fn link_builtin(objects: &[impl AsRef<[u8]>]) -> Result<Vec<u8>> {
+    #[cfg(all(
+        target_os = "linux",
+        target_env = "gnu",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    {
+        trace!("starting built-in linking");
+        let pre_linking_instant = Instant::now();
+        let objects = objects.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let library = linker::link(&objects)?;
+        let linking_time = pre_linking_instant.elapsed().as_millis();
+        trace!(time = linking_time, "built-in linking finished");
+
+        Ok(library)
+    }
+    #[cfg(not(all(
+        target_os = "linux",
+        target_env = "gnu",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    )))]
+    {
+        let _ = objects;
+        Err(Error::LinkError(
+            "the built-in linker is only available on glibc-based Linux for x86-64 and AArch64"
+                .to_string(),
+        ))
+    }
+}
+
+fn run_linker(
+    file_paths: Vec<String>,
+    output_path: String,
+    mut linker: std::process::Command,
+) -> Result<()> {
     let args: Vec<Cow<'static, str>> = {
         #[cfg(target_os = "macos")]
         {
@@ -254,12 +1190,8 @@ pub fn object_to_shared_lib(object: &[u8], output_filename: &Path) -> Result<()>
                 "-L/Library/Developer/CommandLineTools/SDKs/MacOSX.sdk/usr/lib".into(),
             ];
 
-            args.extend([
-                Cow::from(file_path),
-                "-o".into(),
-                Cow::from(output_path),
-                "-lSystem".into(),
-            ]);
+            args.extend(file_paths.into_iter().map(Cow::from));
+            args.extend(["-o".into(), Cow::from(output_path), "-lSystem".into()]);
 
             args
         }
@@ -272,12 +1204,8 @@ pub fn object_to_shared_lib(object: &[u8], output_filename: &Path) -> Result<()>
                 "-L/usr/lib/../lib64".into(),
             ];
 
-            args.extend([
-                "-o".into(),
-                Cow::from(output_path),
-                "-lc".into(),
-                Cow::from(file_path),
-            ]);
+            args.extend(["-o".into(), Cow::from(output_path), "-lc".into()]);
+            args.extend(file_paths.into_iter().map(Cow::from));
 
             args
         }
@@ -287,8 +1215,6 @@ pub fn object_to_shared_lib(object: &[u8], output_filename: &Path) -> Result<()>
         }
     };
 
-    let mut linker = std::process::Command::new("ld");
-
     trace!("starting linking");
     let pre_linking_instant = Instant::now();
     let proc = linker.args(args.iter().map(|x| x.as_ref())).output()?;
@@ -304,63 +1230,93 @@ pub fn object_to_shared_lib(object: &[u8], output_filename: &Path) -> Result<()>
 }
 
 /// Gets the target triple, which identifies the platform and ABI.
-pub fn get_target_triple() -> String {
-    let target_triple = unsafe {
-        let value = LLVMGetDefaultTargetTriple();
-        CStr::from_ptr(value).to_string_lossy().into_owned()
-    };
-    target_triple
+pub fn get_target_triple(target: &TargetOptions) -> String {
+    target.triple.clone().unwrap_or_else(get_host_triple)
+}
+
+/// Normalizes a target triple, filling in the missing components (for example,
+/// `aarch64-linux-gnu` becomes `aarch64-unknown-linux-gnu`).
+pub fn normalize_target_triple(triple: &str) -> Result<String> {
+    let triple = CString::new(triple).map_err(|_| {
+        Error::LLVMCompileError("target triples can't contain null bytes".to_string())
+    })?;
+    Ok(unsafe { take_llvm_message(LLVMNormalizeTargetTriple(triple.as_ptr())) })
+}
+
+/// Gets the host's target triple.
+fn get_host_triple() -> String {
+    unsafe { take_llvm_message(LLVMGetDefaultTargetTriple()) }
+}
+
+fn get_host_cpu_name() -> String {
+    unsafe { take_llvm_message(LLVMGetHostCPUName()) }
+}
+
+fn get_host_cpu_features() -> String {
+    unsafe { take_llvm_message(LLVMGetHostCPUFeatures()) }
 }
 
 /// Gets the data layout reprrsentation as a string, to be given to the MLIR module.
 /// LLVM uses this to know the proper alignments for the given sizes, etc.
-/// This function gets the data layout of the host target triple.
-pub fn get_data_layout_rep() -> Result<String> {
+pub fn get_data_layout_rep(target: &TargetOptions) -> Result<String> {
     unsafe {
-        let mut null = null_mut();
-        let error_buffer = addr_of_mut!(null);
+        let machine = create_target_machine(target, LLVMCodeGenOptLevel::LLVMCodeGenLevelNone)?;
 
-        let target_triple = LLVMGetDefaultTargetTriple();
+        let data_layout = LLVMCreateTargetDataLayout(machine);
+        let data_layout_str = take_llvm_message(LLVMCopyStringRepOfTargetData(data_layout));
 
-        let target_cpu = LLVMGetHostCPUName();
+        LLVMDisposeTargetData(data_layout);
+        LLVMDisposeTargetMachine(machine);
 
-        let target_cpu_features = LLVMGetHostCPUFeatures();
+        Ok(data_layout_str)
+    }
+}
 
-        let mut target: MaybeUninit<LLVMTargetRef> = MaybeUninit::uninit();
+/// Creates a target machine for the given target, which must be disposed by the caller.
+unsafe fn create_target_machine(
+    target: &TargetOptions,
+    opt_level: LLVMCodeGenOptLevel,
+) -> Result<LLVMTargetMachineRef> {
+    initialize_llvm();
+    let (target_triple, target_cpu, target_cpu_features) = target.resolve()?;
 
-        if LLVMGetTargetFromTriple(target_triple, target.as_mut_ptr(), error_buffer) != 0 {
-            let error = CStr::from_ptr(*error_buffer);
-            let err = error.to_string_lossy().to_string();
-            tracing::error!("error getting target triple: {}", err);
-            LLVMDisposeMessage(*error_buffer);
-            Err(Error::LLVMCompileError(err))?;
-        }
-        if !(*error_buffer).is_null() {
-            LLVMDisposeMessage(*error_buffer);
-        }
-
-        let target = target.assume_init();
+    let mut error = null_mut();
+    let mut llvm_target: MaybeUninit<LLVMTargetRef> = MaybeUninit::uninit();
+    if LLVMGetTargetFromTriple(target_triple.as_ptr(), llvm_target.as_mut_ptr(), &mut error) != 0 {
+        let err = take_llvm_message(error);
+        tracing::error!("error getting target triple: {}", err);
+        return Err(Error::LLVMCompileError(err));
+    } else if !error.is_null() {
+        LLVMDisposeMessage(error);
+    }
 
-        let machine = LLVMCreateTargetMachine(
-            target,
-            target_triple.cast(),
-            target_cpu.cast(),
-            target_cpu_features.cast(),
-            LLVMCodeGenOptLevel::LLVMCodeGenLevelNone,
-            LLVMRelocMode::LLVMRelocDynamicNoPic,
-            LLVMCodeModel::LLVMCodeModelDefault,
-        );
+    Ok(LLVMCreateTargetMachine(
+        llvm_target.assume_init(),
+        target_triple.as_ptr(),
+        target_cpu.as_ptr(),
+        target_cpu_features.as_ptr(),
+        opt_level,
+        target.reloc_model.into(),
+        target.code_model.into(),
+    ))
+}
 
-        let data_layout = llvm_sys::target_machine::LLVMCreateTargetDataLayout(machine);
-        let data_layout_str =
-            CStr::from_ptr(llvm_sys::target::LLVMCopyStringRepOfTargetData(data_layout));
-        Ok(data_layout_str.to_string_lossy().into_owned())
-    }
+/// Converts a message allocated by LLVM into a string, disposing of the original.
+unsafe fn take_llvm_message(message: *mut c_char) -> String {
+    let value = CStr::from_ptr(message).to_string_lossy().into_owned();
+    LLVMDisposeMessage(message);
+    value
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        context::NativeContext,
+        executor::{AotNativeExecutor, JitNativeExecutor},
+        utils::test::{find_test_function, load_cairo},
+        values::Value,
+    };
 
     #[test]
     fn test_opt_level_default() {
@@ -396,4 +1352,135 @@ mod tests {
         assert_eq!(OptLevel::from(3u8), OptLevel::Aggressive);
         assert_eq!(OptLevel::from(30u8), OptLevel::Aggressive);
     }
+
+    #[test]
+    fn test_portable_baseline_mismatch() {
+        let (baseline, other) = if cfg!(target_arch = "aarch64") {
+            (CpuBaseline::Armv8A, CpuBaseline::X86_64V3)
+        } else {
+            (CpuBaseline::X86_64V3, CpuBaseline::Armv8A)
+        };
+
+        assert!(TargetOptions::portable(baseline).is_ok());
+        assert!(matches!(
+            TargetOptions::portable(other),
+            Err(Error::CpuBaselineMismatch { baseline, .. }) if baseline == other
+        ));
+        assert!(CpuBaseline::Armv8A.supports_triple("arm64-apple-darwin23.0.0"));
+        assert!(!CpuBaseline::X86_64.supports_triple("aarch64-unknown-linux-gnu"));
+    }
+
+    #[test]
+    fn test_cross_compile() {
+        let (_, program) = load_cairo! {
+            fn run_test(lhs: felt252, rhs: felt252) -> felt252 {
+                lhs * rhs
+            }
+        };
+
+        let target = TargetOptions {
+            triple: Some("aarch64-unknown-linux-gnu".to_string()),
+            ..Default::default()
+        };
+        let native_context = NativeContext::new().with_target(target.clone());
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let object = module_to_object(module.module(), OptLevel::Default, Some(&target)).unwrap();
+
+        // An ELF object whose machine is EM_AARCH64.
+        assert_eq!(&object[..4], b"\x7fELF");
+        assert_eq!(u16::from_le_bytes([object[18], object[19]]), 183);
+
+        // The executors can't run it, unless the host happens to be the target.
+        if !target.is_host() {
+            let module = native_context
+                .compile(&program, false, Some(Default::default()))
+                .unwrap();
+            assert!(matches!(
+                JitNativeExecutor::from_native_module(module, OptLevel::Default),
+                Err(Error::UnsupportedTarget(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_emit_llvm_ir_and_assembly() {
+        let (_, program) = load_cairo! {
+            fn run_test(lhs: felt252, rhs: felt252) -> felt252 {
+                lhs * rhs
+            }
+        };
+
+        let module = NativeContext::new()
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let options = CompilationOptions::default();
+
+        let ir = module_to_llvm_ir(module.module(), OptLevel::Default, None, &options).unwrap();
+        assert!(ir.contains("define "));
+        assert!(ir.contains("run_test"));
+
+        let asm = module_to_assembly(module.module(), OptLevel::Default, None, &options).unwrap();
+        assert!(asm.contains("run_test"));
+    }
+
+    #[test]
+    fn test_codegen_units() {
+        let program = load_cairo! {
+            fn run_test(lhs: felt252, rhs: felt252) -> felt252 {
+                double(lhs) + square(rhs) + cube(lhs)
+            }
+
+            #[inline(never)]
+            fn double(value: felt252) -> felt252 {
+                value + value
+            }
+
+            #[inline(never)]
+            fn square(value: felt252) -> felt252 {
+                value * value
+            }
+
+            #[inline(never)]
+            fn cube(value: felt252) -> felt252 {
+                value * value * value
+            }
+        };
+
+        let native_context = NativeContext::new().with_options(CompilationOptions {
+            codegen_units: 4,
+            ..Default::default()
+        });
+        let compile = || {
+            let module = native_context
+                .compile(&program.1, false, Some(Default::default()))
+                .unwrap();
+            module_to_objects(
+                module.module(),
+                OptLevel::Default,
+                None,
+                native_context.options(),
+            )
+            .unwrap()
+        };
+
+        // The partitions and their order don't depend on which thread emits them.
+        let objects = compile();
+        assert!(objects.len() > 1);
+        assert_eq!(objects, compile());
+
+        let module = native_context
+            .compile(&program.1, false, Some(Default::default()))
+            .unwrap();
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::Default).unwrap();
+        let result = executor
+            .invoke_dynamic(
+                &find_test_function(&program, "run_test").id,
+                &[Value::Felt252(3.into()), Value::Felt252(5.into())],
+                Some(u64::MAX),
+            )
+            .unwrap();
+        assert_eq!(result.return_value, Value::Felt252(58.into()));
+    }
 }
@@ -0,0 +1,1898 @@
+//! # Built-in linker
+//!
+//! A minimal ELF linker which turns the relocatable objects generated by LLVM into a shared
+//! library, without depending on any external binary.
+//!
+//! Only what the generated code needs is supported: position-independent code for x86-64 and
+//! AArch64 whose undefined symbols are all provided by the [`NEEDED_LIBRARIES`], which are the only
+//! dependencies of the library. When linking for the host they're checked to provide every import,
+//! otherwise that's left to the target's dynamic loader. Anything else (TLS, text relocations, copy
+//! relocations...) is reported as an error. Sections which aren't loaded at runtime, like the debug
+//! info, are discarded.
+//!
+//! The supported platforms are glibc-based Linux distributions on x86-64 and AArch64, which are
+//! the only ones it's built for and tested on (the tests load and run the libraries it links).
+//! The libraries only depend on glibc's sonames, have no symbol versions and only a `DT_HASH`
+//! table, therefore they can't be loaded on musl-based systems, nor on containers without
+//! `libgcc_s.so.1`. Use the system linker there.
+//!
+//! The library's own symbols are always bound locally (as if linked with `-Bsymbolic`). Calls to
+//! imported functions go through stubs which jump using a GOT entry, bound eagerly by the dynamic
+//! loader.
+
+use crate::error::{Error, Result};
+use libloading::os::unix::Library;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+};
+
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_HASH: u32 = 5;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOTE: u32 = 7;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHT_DYNSYM: u32 = 11;
+const SHT_INIT_ARRAY: u32 = 14;
+const SHT_FINI_ARRAY: u32 = 15;
+const SHT_GROUP: u32 = 17;
+const SHT_SYMTAB_SHNDX: u32 = 18;
+const SHT_X86_64_UNWIND: u32 = 0x7000_0001;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_TLS: u64 = 0x400;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xfff1;
+const SHN_COMMON: u16 = 0xfff2;
+const SHN_XINDEX: u16 = 0xffff;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STT_TLS: u8 = 6;
+const STV_INTERNAL: u8 = 1;
+const STV_HIDDEN: u8 = 2;
+
+const GRP_COMDAT: u32 = 1;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_INIT_ARRAY: u64 = 25;
+const DT_FINI_ARRAY: u64 = 26;
+const DT_INIT_ARRAYSZ: u64 = 27;
+const DT_FINI_ARRAYSZ: u64 = 28;
+
+const EHDR_SIZE: u64 = 64;
+const PHDR_SIZE: u64 = 56;
+const SHDR_SIZE: u64 = 64;
+const SYM_SIZE: u64 = 24;
+const RELA_SIZE: u64 = 24;
+const DYN_SIZE: u64 = 16;
+const GOT_ENTRY_SIZE: u64 = 8;
+const PLT_ENTRY_SIZE: u64 = 16;
+const NUM_PHDRS: u64 = 5;
+
+/// The libraries the generated code depends on: the C library and the compiler's runtime library,
+/// which provides the helpers for the 128-bit divisions.
+const NEEDED_LIBRARIES: [&str; 2] = ["libc.so.6", "libgcc_s.so.1"];
+
+/// Links the objects into a shared library, returning its contents.
+pub(crate) fn link(objects: &[&[u8]]) -> Result<Vec<u8>> {
+    let objects = objects
+        .iter()
+        .map(|data| Object::parse(data))
+        .collect::<Result<Vec<_>>>()?;
+
+    let arch = match objects.first() {
+        Some(object) => object.arch,
+        None => return Err(link_error("no objects to link")),
+    };
+    if objects.iter().any(|object| object.arch != arch) {
+        return Err(link_error("objects for different architectures"));
+    }
+
+    Linker::new(arch, objects)?.link()
+}
+
+fn link_error(msg: impl Into<String>) -> Error {
+    Error::LinkError(msg.into())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Arch {
+    X86_64,
+    AArch64,
+}
+
+impl Arch {
+    const fn page_size(self) -> u64 {
+        match self {
+            Arch::X86_64 => 0x1000,
+            // AArch64 systems may use pages of up to 64KiB.
+            Arch::AArch64 => 0x10000,
+        }
+    }
+
+    const fn is_host(self) -> bool {
+        match self {
+            Arch::X86_64 => cfg!(target_arch = "x86_64"),
+            Arch::AArch64 => cfg!(target_arch = "aarch64"),
+        }
+    }
+
+    const fn machine(self) -> u16 {
+        match self {
+            Arch::X86_64 => EM_X86_64,
+            Arch::AArch64 => EM_AARCH64,
+        }
+    }
+
+    const fn r_abs64(self) -> u32 {
+        match self {
+            Arch::X86_64 => 1,
+            Arch::AArch64 => 257,
+        }
+    }
+
+    const fn r_glob_dat(self) -> u32 {
+        match self {
+            Arch::X86_64 => 6,
+            Arch::AArch64 => 1025,
+        }
+    }
+
+    const fn r_relative(self) -> u32 {
+        match self {
+            Arch::X86_64 => 8,
+            Arch::AArch64 => 1027,
+        }
+    }
+
+    /// Classify a relocation by the linker-generated entries it needs.
+    fn relocation_class(self, kind: u32) -> Result<RelocationClass> {
+        Ok(match (self, kind) {
+            (Arch::X86_64, 0) | (Arch::AArch64, 0) => RelocationClass::None,
+            (Arch::X86_64, 1) | (Arch::AArch64, 257) => RelocationClass::Absolute,
+            (Arch::X86_64, 9 | 41 | 42) | (Arch::AArch64, 311 | 312) => RelocationClass::Got,
+            (Arch::X86_64, 4) | (Arch::AArch64, 282 | 283) => RelocationClass::Call,
+            (Arch::X86_64, 2 | 24 | 25 | 26)
+            | (Arch::AArch64, 260 | 261 | 274 | 275 | 277..=280 | 284..=286 | 299) => {
+                RelocationClass::Direct
+            }
+            _ => {
+                return Err(link_error(format!(
+                    "unsupported relocation type {kind} for {self:?}"
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RelocationClass {
+    None,
+    /// An absolute address, which requires a dynamic relocation.
+    Absolute,
+    /// A reference to the symbol's GOT entry.
+    Got,
+    /// A call, which goes through a stub when the symbol is imported.
+    Call,
+    /// A reference which must be resolved at link time.
+    Direct,
+}
+
+/// The sections of the output library, in the order they're laid out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OutputSection {
+    Hash,
+    DynSym,
+    DynStr,
+    RelaDyn,
+    ReadOnly,
+    Text,
+    Plt,
+    Data,
+    InitArray,
+    FiniArray,
+    Dynamic,
+    Got,
+    Bss,
+}
+
+impl OutputSection {
+    const ALL: [Self; 13] = [
+        Self::Hash,
+        Self::DynSym,
+        Self::DynStr,
+        Self::RelaDyn,
+        Self::ReadOnly,
+        Self::Text,
+        Self::Plt,
+        Self::Data,
+        Self::InitArray,
+        Self::FiniArray,
+        Self::Dynamic,
+        Self::Got,
+        Self::Bss,
+    ];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Hash => ".hash",
+            Self::DynSym => ".dynsym",
+            Self::DynStr => ".dynstr",
+            Self::RelaDyn => ".rela.dyn",
+            Self::ReadOnly => ".rodata",
+            Self::Text => ".text",
+            Self::Plt => ".plt",
+            Self::Data => ".data",
+            Self::InitArray => ".init_array",
+            Self::FiniArray => ".fini_array",
+            Self::Dynamic => ".dynamic",
+            Self::Got => ".got",
+            Self::Bss => ".bss",
+        }
+    }
+
+    const fn kind(self) -> u32 {
+        match self {
+            Self::Hash => SHT_HASH,
+            Self::DynSym => SHT_DYNSYM,
+            Self::DynStr => SHT_STRTAB,
+            Self::RelaDyn => SHT_RELA,
+            Self::InitArray => SHT_INIT_ARRAY,
+            Self::FiniArray => SHT_FINI_ARRAY,
+            Self::Dynamic => SHT_DYNAMIC,
+            Self::Bss => SHT_NOBITS,
+            _ => SHT_PROGBITS,
+        }
+    }
+
+    const fn flags(self) -> u64 {
+        match self {
+            Self::Hash | Self::DynSym | Self::DynStr | Self::RelaDyn | Self::ReadOnly => SHF_ALLOC,
+            Self::Text | Self::Plt => SHF_ALLOC | SHF_EXECINSTR,
+            _ => SHF_ALLOC | SHF_WRITE,
+        }
+    }
+
+    const fn entry_size(self) -> u64 {
+        match self {
+            Self::Hash => 4,
+            Self::DynSym => SYM_SIZE,
+            Self::RelaDyn => RELA_SIZE,
+            Self::Dynamic => DYN_SIZE,
+            Self::Got => GOT_ENTRY_SIZE,
+            Self::Plt => PLT_ENTRY_SIZE,
+            _ => 0,
+        }
+    }
+
+    /// The index of the section header.
+    fn index(self) -> u16 {
+        Self::ALL
+            .iter()
+            .position(|x| *x == self)
+            .expect("all sections are listed") as u16
+            + 1
+    }
+
+    /// Which output section an input section belongs to.
+    fn of(section: &InputSection) -> Result<Option<Self>> {
+        if section.flags & SHF_ALLOC == 0 || section.discarded {
+            return Ok(None);
+        }
+        if section.flags & SHF_TLS != 0 {
+            return Err(link_error("thread-local storage is not supported"));
+        }
+
+        Ok(Some(match section.kind {
+            SHT_INIT_ARRAY => Self::InitArray,
+            SHT_FINI_ARRAY => Self::FiniArray,
+            SHT_NOBITS => Self::Bss,
+            SHT_PROGBITS | SHT_X86_64_UNWIND if section.flags & SHF_EXECINSTR != 0 => Self::Text,
+            SHT_PROGBITS | SHT_X86_64_UNWIND if section.flags & SHF_WRITE != 0 => Self::Data,
+            SHT_PROGBITS | SHT_X86_64_UNWIND => Self::ReadOnly,
+            // Notes, like `.note.gnu.property`, are not needed.
+            SHT_NOTE => return Ok(None),
+            kind => {
+                return Err(link_error(format!(
+                    "unsupported allocated section type {kind:#x}"
+                )))
+            }
+        }))
+    }
+}
+
+struct Object<'a> {
+    arch: Arch,
+    sections: Vec<InputSection<'a>>,
+    symbols: Vec<InputSymbol<'a>>,
+    /// The COMDAT groups' signatures and members.
+    groups: Vec<(&'a str, Vec<usize>)>,
+}
+
+struct InputSection<'a> {
+    kind: u32,
+    flags: u64,
+    align: u64,
+    size: u64,
+    /// The contents, or `None` for `SHT_NOBITS` sections.
+    data: Option<&'a [u8]>,
+    relocations: Vec<Relocation>,
+    /// Whether the section belongs to a duplicated COMDAT group.
+    discarded: bool,
+    /// The output section and the section's address, once laid out.
+    output: Option<(OutputSection, u64)>,
+}
+
+struct InputSymbol<'a> {
+    name: &'a str,
+    info: u8,
+    other: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+impl InputSymbol<'_> {
+    const fn binding(&self) -> u8 {
+        self.info >> 4
+    }
+
+    const fn kind(&self) -> u8 {
+        self.info & 0xf
+    }
+
+    const fn is_hidden(&self) -> bool {
+        matches!(self.other & 0x3, STV_HIDDEN | STV_INTERNAL)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Relocation {
+    offset: u64,
+    kind: u32,
+    symbol: usize,
+    addend: i64,
+}
+
+impl<'a> Object<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.get(..4) != Some(b"\x7fELF".as_slice()) {
+            return Err(link_error("not an ELF object"));
+        }
+        // ELFCLASS64, ELFDATA2LSB and ET_REL.
+        if data.get(4..6) != Some([2, 1].as_slice()) || read_u16(data, 16)? != 1 {
+            return Err(link_error("not a 64-bit little-endian relocatable object"));
+        }
+
+        let arch = match read_u16(data, 18)? {
+            EM_X86_64 => Arch::X86_64,
+            EM_AARCH64 => Arch::AArch64,
+            machine => return Err(link_error(format!("unsupported machine {machine}"))),
+        };
+
+        let shoff = read_u64(data, 40)?;
+        let shnum = read_u16(data, 60)?;
+        if shnum == 0 && shoff != 0 {
+            return Err(link_error("too many sections"));
+        }
+
+        let headers = (0..u64::from(shnum))
+            .map(|idx| slice(data, shoff + idx * SHDR_SIZE, SHDR_SIZE))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut sections = headers
+            .iter()
+            .map(|header| {
+                let kind = read_u32(header, 4)?;
+                let offset = read_u64(header, 24)?;
+                let size = read_u64(header, 32)?;
+
+                Ok(InputSection {
+                    kind,
+                    flags: read_u64(header, 8)?,
+                    align: read_u64(header, 48)?.max(1),
+                    size,
+                    data: match kind {
+                        SHT_NOBITS => None,
+                        _ => Some(slice(data, offset, size)?),
+                    },
+                    relocations: Vec::new(),
+                    discarded: false,
+                    output: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut symbols = Vec::new();
+        let mut group_headers = Vec::new();
+        for (idx, header) in headers.iter().enumerate() {
+            match sections[idx].kind {
+                SHT_SYMTAB => {
+                    let strtab = sections
+                        .get(read_u32(header, 40)? as usize)
+                        .and_then(|x| x.data)
+                        .ok_or_else(|| link_error("malformed object: missing string table"))?;
+
+                    for entry in sections[idx]
+                        .data
+                        .unwrap_or_default()
+                        .chunks_exact(SYM_SIZE as usize)
+                    {
+                        symbols.push(InputSymbol {
+                            name: read_str(strtab, read_u32(entry, 0)?.into())?,
+                            info: entry[4],
+                            other: entry[5],
+                            shndx: read_u16(entry, 6)?,
+                            value: read_u64(entry, 8)?,
+                            size: read_u64(entry, 16)?,
+                        });
+                    }
+                }
+                SHT_RELA => {
+                    let relocations = sections[idx]
+                        .data
+                        .unwrap_or_default()
+                        .chunks_exact(RELA_SIZE as usize)
+                        .map(|entry| {
+                            let info = read_u64(entry, 8)?;
+                            Ok(Relocation {
+                                offset: read_u64(entry, 0)?,
+                                kind: info as u32,
+                                symbol: (info >> 32) as usize,
+                                addend: read_u64(entry, 16)? as i64,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+
+                    sections
+                        .get_mut(read_u32(header, 44)? as usize)
+                        .ok_or_else(|| link_error("malformed object: invalid relocation target"))?
+                        .relocations = relocations;
+                }
+                SHT_REL => return Err(link_error("unsupported SHT_REL relocations")),
+                SHT_SYMTAB_SHNDX => return Err(link_error("too many sections")),
+                SHT_GROUP => group_headers.push((idx, read_u32(header, 44)? as usize)),
+                _ => {}
+            }
+        }
+
+        let mut groups = Vec::new();
+        for (idx, signature) in group_headers {
+            let mut entries = sections[idx]
+                .data
+                .unwrap_or_default()
+                .chunks_exact(4)
+                .map(|x| read_u32(x, 0).map(|x| x as usize));
+
+            if entries.next().transpose()?.unwrap_or_default() as u32 & GRP_COMDAT != 0 {
+                let signature = symbols
+                    .get(signature)
+                    .ok_or_else(|| link_error("malformed object: invalid group signature"))?
+                    .name;
+                groups.push((signature, entries.collect::<Result<_>>()?));
+            }
+        }
+
+        Ok(Self {
+            arch,
+            sections,
+            symbols,
+            groups,
+        })
+    }
+}
+
+/// A key which identifies a symbol across all the objects.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum SymbolKey<'a> {
+    Local { object: usize, symbol: usize },
+    Global(&'a str),
+}
+
+/// What a symbol resolves to.
+#[derive(Clone, Copy, Debug)]
+enum Target<'a> {
+    Section {
+        object: usize,
+        section: usize,
+        value: u64,
+    },
+    Common(&'a str),
+    Absolute(u64),
+    GotBase,
+    Undefined(&'a str),
+}
+
+#[derive(Default)]
+struct Global {
+    /// The object and symbol index of the definition.
+    definition: Option<(usize, usize)>,
+    is_weak_definition: bool,
+    /// The size and alignment of a common symbol.
+    common: Option<(u64, u64)>,
+    has_strong_reference: bool,
+    is_hidden: bool,
+}
+
+struct Linker<'a> {
+    arch: Arch,
+    objects: Vec<Object<'a>>,
+    globals: HashMap<&'a str, Global>,
+
+    got: Vec<(SymbolKey<'a>, Target<'a>)>,
+    got_indices: HashMap<SymbolKey<'a>, usize>,
+    plt: Vec<SymbolKey<'a>>,
+    plt_indices: HashMap<SymbolKey<'a>, usize>,
+    imports: Vec<&'a str>,
+    num_dynamic_relocations: u64,
+
+    /// The addresses of the common symbols and the GOT, once laid out.
+    commons: HashMap<&'a str, u64>,
+    got_base: u64,
+}
+
+impl<'a> Linker<'a> {
+    fn new(arch: Arch, mut objects: Vec<Object<'a>>) -> Result<Self> {
+        // Only keep the first instance of every COMDAT group.
+        let mut signatures = HashSet::new();
+        for object in &mut objects {
+            for (signature, members) in &object.groups {
+                if !signatures.insert(*signature) {
+                    for member in members {
+                        if let Some(section) = object.sections.get_mut(*member) {
+                            section.discarded = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut globals = HashMap::<_, Global>::new();
+        for (object_idx, object) in objects.iter().enumerate() {
+            for (symbol_idx, symbol) in object.symbols.iter().enumerate().skip(1) {
+                if symbol.binding() == STB_LOCAL {
+                    continue;
+                }
+                if symbol.kind() == STT_TLS {
+                    return Err(link_error("thread-local storage is not supported"));
+                }
+
+                let global = globals.entry(symbol.name).or_default();
+                global.is_hidden |= symbol.is_hidden();
+
+                let is_weak = symbol.binding() == STB_WEAK;
+                match symbol.shndx {
+                    SHN_UNDEF => global.has_strong_reference |= !is_weak,
+                    SHN_COMMON => {
+                        let (size, align) = global.common.unwrap_or((0, 1));
+                        global.common = Some((size.max(symbol.size), align.max(symbol.value)));
+                    }
+                    SHN_XINDEX => return Err(link_error("too many sections")),
+                    shndx => {
+                        // Definitions in discarded groups have a copy elsewhere.
+                        if object
+                            .sections
+                            .get(shndx as usize)
+                            .is_some_and(|x| x.discarded)
+                        {
+                            continue;
+                        }
+
+                        match global.definition {
+                            Some(_) if !global.is_weak_definition && !is_weak => {
+                                return Err(link_error(format!(
+                                    "duplicate symbol `{}`",
+                                    symbol.name
+                                )));
+                            }
+                            Some(_) if !global.is_weak_definition || is_weak => {}
+                            _ => {
+                                global.definition = Some((object_idx, symbol_idx));
+                                global.is_weak_definition = is_weak;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            arch,
+            objects,
+            globals,
+            got: Vec::new(),
+            got_indices: HashMap::new(),
+            plt: Vec::new(),
+            plt_indices: HashMap::new(),
+            imports: Vec::new(),
+            num_dynamic_relocations: 0,
+            commons: HashMap::new(),
+            got_base: 0,
+        })
+    }
+
+    fn resolve(&self, object: usize, symbol: usize) -> Result<(SymbolKey<'a>, Target<'a>)> {
+        // Relocations without a symbol are relative to zero.
+        if symbol == 0 {
+            return Ok((SymbolKey::Local { object, symbol }, Target::Absolute(0)));
+        }
+
+        let input = self.objects[object]
+            .symbols
+            .get(symbol)
+            .ok_or_else(|| link_error("malformed object: invalid symbol index"))?;
+
+        if input.binding() == STB_LOCAL {
+            let target = match input.shndx {
+                SHN_ABS => Target::Absolute(input.value),
+                SHN_UNDEF | SHN_COMMON | SHN_XINDEX => {
+                    return Err(link_error("malformed object: invalid local symbol"))
+                }
+                shndx => {
+                    if self.objects[object]
+                        .sections
+                        .get(shndx as usize)
+                        .is_none_or(|x| x.discarded)
+                    {
+                        return Err(link_error("reference to a discarded section"));
+                    }
+
+                    Target::Section {
+                        object,
+                        section: shndx as usize,
+                        value: input.value,
+                    }
+                }
+            };
+
+            return Ok((SymbolKey::Local { object, symbol }, target));
+        }
+
+        let global = &self.globals[input.name];
+        let target = match global.definition {
+            Some((object, symbol)) => {
+                let definition = &self.objects[object].symbols[symbol];
+                match definition.shndx {
+                    SHN_ABS => Target::Absolute(definition.value),
+                    shndx => Target::Section {
+                        object,
+                        section: shndx as usize,
+                        value: definition.value,
+                    },
+                }
+            }
+            None if global.common.is_some() => Target::Common(input.name),
+            None if input.name == "_GLOBAL_OFFSET_TABLE_" => Target::GotBase,
+            None => Target::Undefined(input.name),
+        };
+
+        Ok((SymbolKey::Global(input.name), target))
+    }
+
+    /// Find out which GOT entries, stubs and dynamic relocations are needed.
+    fn scan_relocations(&mut self) -> Result<()> {
+        let mut imports = HashSet::new();
+
+        for object_idx in 0..self.objects.len() {
+            for section_idx in 0..self.objects[object_idx].sections.len() {
+                let section = &self.objects[object_idx].sections[section_idx];
+                if OutputSection::of(section)?.is_none() {
+                    continue;
+                }
+                let is_writable = section.flags & SHF_WRITE != 0;
+
+                for relocation_idx in 0..section.relocations.len() {
+                    let relocation =
+                        self.objects[object_idx].sections[section_idx].relocations[relocation_idx];
+                    let (key, target) = self.resolve(object_idx, relocation.symbol)?;
+
+                    match self.arch.relocation_class(relocation.kind)? {
+                        RelocationClass::None => {}
+                        RelocationClass::Absolute => {
+                            if !is_writable {
+                                return Err(link_error("text relocations are not supported"));
+                            }
+
+                            match target {
+                                Target::Absolute(_) => {}
+                                Target::Undefined(name) => {
+                                    imports.insert(name);
+                                    self.num_dynamic_relocations += 1;
+                                }
+                                _ => self.num_dynamic_relocations += 1,
+                            }
+                        }
+                        RelocationClass::Got => {
+                            if self.arch == Arch::AArch64 && relocation.addend != 0 {
+                                return Err(link_error("GOT relocations with addends"));
+                            }
+
+                            self.add_got_entry(key, target, &mut imports);
+                        }
+                        RelocationClass::Call => {
+                            if let Target::Undefined(_) = target {
+                                self.add_got_entry(key, target, &mut imports);
+                                if !self.plt_indices.contains_key(&key) {
+                                    self.plt_indices.insert(key, self.plt.len());
+                                    self.plt.push(key);
+                                }
+                            }
+                        }
+                        RelocationClass::Direct => {
+                            if let Target::Undefined(name) = target {
+                                return Err(link_error(format!(
+                                    "undefined symbol `{name}` must be defined at link time"
+                                )));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.imports = imports.into_iter().collect();
+        self.imports.sort_unstable();
+
+        // The target's libraries aren't available when cross-linking.
+        if self.arch.is_host() {
+            self.check_imports()?;
+        }
+
+        Ok(())
+    }
+
+    /// Check that the needed libraries provide every import which isn't weak, since the library
+    /// would fail to load otherwise.
+    ///
+    /// Only the needed libraries (and their dependencies) are searched, not everything loaded in the
+    /// current process, so that the library can be loaded by any other process.
+    fn check_imports(&self) -> Result<()> {
+        let libraries = NEEDED_LIBRARIES
+            .iter()
+            .map(|name| {
+                unsafe { Library::new(name) }
+                    .map_err(|e| link_error(format!("failed to load `{name}`: {e}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for name in &self.imports {
+            if !self.globals[name].has_strong_reference {
+                continue;
+            }
+
+            let c_name = CString::new(*name)
+                .map_err(|_| link_error("symbol names can't contain null bytes"))?;
+            let is_provided = libraries.iter().any(|library| unsafe {
+                library.get::<*const ()>(c_name.as_bytes_with_nul()).is_ok()
+            });
+            if !is_provided {
+                return Err(link_error(format!(
+                    "undefined symbol `{name}`, which isn't provided by {}",
+                    NEEDED_LIBRARIES.join(" nor ")
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_got_entry(
+        &mut self,
+        key: SymbolKey<'a>,
+        target: Target<'a>,
+        imports: &mut HashSet<&'a str>,
+    ) {
+        if self.got_indices.contains_key(&key) {
+            return;
+        }
+
+        self.got_indices.insert(key, self.got.len());
+        self.got.push((key, target));
+
+        match target {
+            Target::Absolute(_) => {}
+            Target::Undefined(name) => {
+                imports.insert(name);
+                self.num_dynamic_relocations += 1;
+            }
+            _ => self.num_dynamic_relocations += 1,
+        }
+    }
+
+    fn link(mut self) -> Result<Vec<u8>> {
+        self.scan_relocations()?;
+
+        // Build the dynamic symbol table: the imports followed by the exports.
+        let mut exports = self
+            .globals
+            .iter()
+            .filter(|(_, global)| {
+                !global.is_hidden && (global.definition.is_some() || global.common.is_some())
+            })
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>();
+        exports.sort_unstable();
+
+        let dynamic_symbols = [""]
+            .into_iter()
+            .chain(self.imports.iter().copied())
+            .chain(exports.iter().copied())
+            .collect::<Vec<_>>();
+        let dynamic_symbol_indices = dynamic_symbols
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (*name, idx as u32))
+            .collect::<HashMap<_, _>>();
+
+        let mut dynstr = vec![0u8];
+        let mut push_str = |value: &str| {
+            let offset = dynstr.len() as u32;
+            dynstr.extend_from_slice(value.as_bytes());
+            dynstr.push(0);
+            offset
+        };
+        let needed_names = NEEDED_LIBRARIES.map(&mut push_str);
+        let dynamic_symbol_names = dynamic_symbols
+            .iter()
+            .map(|name| if name.is_empty() { 0 } else { push_str(name) })
+            .collect::<Vec<_>>();
+
+        let has_init_array = self.has_output_section(OutputSection::InitArray)?;
+        let has_fini_array = self.has_output_section(OutputSection::FiniArray)?;
+        let num_dynamic_entries = NEEDED_LIBRARIES.len() as u64
+            + 8
+            + 2 * (u64::from(has_init_array) + u64::from(has_fini_array))
+            + 1;
+
+        // Lay out the sections. File offsets and addresses are always the same.
+        let page_size = self.arch.page_size();
+        let mut ranges = HashMap::new();
+        let mut offset = EHDR_SIZE + NUM_PHDRS * PHDR_SIZE;
+        let mut segment_starts = Vec::new();
+        for output in OutputSection::ALL {
+            if matches!(output, OutputSection::Text | OutputSection::Data) {
+                offset = offset.next_multiple_of(page_size);
+                segment_starts.push(offset);
+            }
+
+            let start;
+            match output {
+                OutputSection::Hash => {
+                    start = offset.next_multiple_of(8);
+                    offset = start + 4 * (2 + 2 * dynamic_symbols.len() as u64);
+                }
+                OutputSection::DynSym => {
+                    start = offset.next_multiple_of(8);
+                    offset = start + SYM_SIZE * dynamic_symbols.len() as u64;
+                }
+                OutputSection::DynStr => {
+                    start = offset;
+                    offset += dynstr.len() as u64;
+                }
+                OutputSection::RelaDyn => {
+                    start = offset.next_multiple_of(8);
+                    offset = start + RELA_SIZE * self.num_dynamic_relocations;
+                }
+                OutputSection::Plt => {
+                    start = offset.next_multiple_of(PLT_ENTRY_SIZE);
+                    offset = start + PLT_ENTRY_SIZE * self.plt.len() as u64;
+                }
+                OutputSection::Dynamic => {
+                    start = offset.next_multiple_of(8);
+                    offset = start + DYN_SIZE * num_dynamic_entries;
+                }
+                OutputSection::Got => {
+                    start = offset.next_multiple_of(8);
+                    offset = start + GOT_ENTRY_SIZE * self.got.len() as u64;
+                }
+                _ => {
+                    let mut max_align = 1;
+                    for object in &self.objects {
+                        for section in &object.sections {
+                            if OutputSection::of(section)? == Some(output) {
+                                max_align = max_align.max(section.align);
+                            }
+                        }
+                    }
+                    start = offset.next_multiple_of(max_align);
+                    offset = start;
+
+                    for object in &mut self.objects {
+                        for section in &mut object.sections {
+                            if OutputSection::of(section)? == Some(output) {
+                                offset = offset.next_multiple_of(section.align);
+                                section.output = Some((output, offset));
+                                offset += section.size;
+                            }
+                        }
+                    }
+
+                    if output == OutputSection::Bss {
+                        let mut commons = self
+                            .globals
+                            .iter()
+                            .filter(|(_, global)| global.definition.is_none())
+                            .filter_map(|(name, global)| Some((*name, global.common?)))
+                            .collect::<Vec<_>>();
+                        commons.sort_unstable_by_key(|(name, _)| *name);
+
+                        for (name, (size, align)) in commons {
+                            offset = offset.next_multiple_of(align.max(1));
+                            self.commons.insert(name, offset);
+                            offset += size;
+                        }
+                    }
+                }
+            }
+
+            ranges.insert(output.index(), (start, offset));
+        }
+        let range = |output: OutputSection| ranges[&output.index()];
+        self.got_base = range(OutputSection::Got).0;
+
+        let file_size = range(OutputSection::Got).1;
+        let shstrtab_offset = file_size;
+        let mut shstrtab = vec![0u8];
+        let section_names = OutputSection::ALL
+            .iter()
+            .map(|x| x.name())
+            .chain([".shstrtab"])
+            .map(|name| {
+                let offset = shstrtab.len() as u32;
+                shstrtab.extend_from_slice(name.as_bytes());
+                shstrtab.push(0);
+                offset
+            })
+            .collect::<Vec<_>>();
+        let shoff = (shstrtab_offset + shstrtab.len() as u64).next_multiple_of(8);
+        let num_sections = OutputSection::ALL.len() as u64 + 2;
+
+        let mut buffer = vec![0u8; (shoff + num_sections * SHDR_SIZE) as usize];
+
+        // The input sections' contents.
+        for object in &self.objects {
+            for section in &object.sections {
+                if let (Some((_, address)), Some(data)) = (section.output, section.data) {
+                    buffer[address as usize..][..data.len()].copy_from_slice(data);
+                }
+            }
+        }
+
+        // The relocations.
+        let got_base = range(OutputSection::Got).0;
+        let plt_base = range(OutputSection::Plt).0;
+        let mut dynamic_relocations = Vec::new();
+        for object_idx in 0..self.objects.len() {
+            for section in &self.objects[object_idx].sections {
+                let Some((_, base)) = section.output else {
+                    continue;
+                };
+
+                for relocation in &section.relocations {
+                    let place = base + relocation.offset;
+                    let (key, target) = self.resolve(object_idx, relocation.symbol)?;
+                    let address = self.address(target);
+                    let addend = relocation.addend;
+
+                    let value = match self.arch.relocation_class(relocation.kind)? {
+                        RelocationClass::None => continue,
+                        RelocationClass::Absolute => {
+                            match target {
+                                Target::Absolute(_) => {}
+                                Target::Undefined(name) => dynamic_relocations.push((
+                                    place,
+                                    self.arch.r_abs64(),
+                                    dynamic_symbol_indices[name],
+                                    addend,
+                                )),
+                                _ => dynamic_relocations.push((
+                                    place,
+                                    self.arch.r_relative(),
+                                    0,
+                                    address.wrapping_add_signed(addend) as i64,
+                                )),
+                            }
+
+                            write_u64(&mut buffer, place, address.wrapping_add_signed(addend))?;
+                            continue;
+                        }
+                        RelocationClass::Got => {
+                            got_base + GOT_ENTRY_SIZE * self.got_indices[&key] as u64
+                        }
+                        RelocationClass::Call => match self.plt_indices.get(&key) {
+                            Some(idx) => plt_base + PLT_ENTRY_SIZE * *idx as u64,
+                            None => address,
+                        },
+                        RelocationClass::Direct => address,
+                    };
+
+                    self.apply_relocation(&mut buffer, relocation.kind, place, value, addend)?;
+                }
+            }
+        }
+
+        // The GOT.
+        for (idx, (_, target)) in self.got.iter().enumerate() {
+            let place = got_base + GOT_ENTRY_SIZE * idx as u64;
+            match target {
+                Target::Absolute(value) => write_u64(&mut buffer, place, *value)?,
+                Target::Undefined(name) => dynamic_relocations.push((
+                    place,
+                    self.arch.r_glob_dat(),
+                    dynamic_symbol_indices[name],
+                    0,
+                )),
+                _ => {
+                    let address = self.address(*target);
+                    write_u64(&mut buffer, place, address)?;
+                    dynamic_relocations.push((place, self.arch.r_relative(), 0, address as i64));
+                }
+            }
+        }
+
+        // The stubs for imported functions.
+        for idx in 0..self.plt.len() {
+            let place = plt_base + PLT_ENTRY_SIZE * idx as u64;
+            let slot = got_base + GOT_ENTRY_SIZE * self.got_indices[&self.plt[idx]] as u64;
+            self.write_stub(&mut buffer, place, slot)?;
+        }
+
+        // The dynamic relocations.
+        dynamic_relocations.sort_by_key(|(_, kind, _, _)| *kind != self.arch.r_relative());
+        let mut offset = range(OutputSection::RelaDyn).0;
+        for (place, kind, symbol, addend) in dynamic_relocations {
+            write_u64(&mut buffer, offset, place)?;
+            write_u64(
+                &mut buffer,
+                offset + 8,
+                (u64::from(symbol) << 32) | u64::from(kind),
+            )?;
+            write_u64(&mut buffer, offset + 16, addend as u64)?;
+            offset += RELA_SIZE;
+        }
+
+        // The dynamic symbol table.
+        let mut offset = range(OutputSection::DynSym).0;
+        for (idx, name) in dynamic_symbols.iter().enumerate() {
+            write_u32(&mut buffer, offset, dynamic_symbol_names[idx])?;
+            if idx != 0 {
+                let global = &self.globals[name];
+                let (info, other, shndx, value, size) = match global.definition {
+                    _ if idx <= self.imports.len() => {
+                        let binding = if global.has_strong_reference {
+                            STB_GLOBAL
+                        } else {
+                            STB_WEAK
+                        };
+                        (binding << 4, 0, SHN_UNDEF, 0, 0)
+                    }
+                    Some((object, symbol_idx)) => {
+                        let symbol = &self.objects[object].symbols[symbol_idx];
+                        let (_, target) = self.resolve(object, symbol_idx)?;
+                        let shndx = match target {
+                            Target::Section {
+                                object, section, ..
+                            } => self.objects[object].sections[section]
+                                .output
+                                .map_or(SHN_ABS, |(x, _)| x.index()),
+                            _ => SHN_ABS,
+                        };
+
+                        // Local default visibility, since they're always bound locally.
+                        let other = symbol.other & !0x3;
+                        (symbol.info, other, shndx, self.address(target), symbol.size)
+                    }
+                    None => {
+                        let (size, _) = global.common.unwrap_or_default();
+                        (
+                            (STB_GLOBAL << 4) | 1,
+                            0,
+                            OutputSection::Bss.index(),
+                            self.commons[name],
+                            size,
+                        )
+                    }
+                };
+
+                buffer[offset as usize + 4] = info;
+                buffer[offset as usize + 5] = other;
+                write_u16(&mut buffer, offset + 6, shndx)?;
+                write_u64(&mut buffer, offset + 8, value)?;
+                write_u64(&mut buffer, offset + 16, size)?;
+            }
+            offset += SYM_SIZE;
+        }
+
+        // The symbol hash table.
+        let num_buckets = dynamic_symbols.len() as u32;
+        let mut buckets = vec![0u32; num_buckets as usize];
+        let mut chains = vec![0u32; dynamic_symbols.len()];
+        for (idx, name) in dynamic_symbols.iter().enumerate().skip(1) {
+            let bucket = (elf_hash(name.as_bytes()) % num_buckets) as usize;
+            chains[idx] = buckets[bucket];
+            buckets[bucket] = idx as u32;
+        }
+        let mut offset = range(OutputSection::Hash).0;
+        for value in [num_buckets, chains.len() as u32]
+            .into_iter()
+            .chain(buckets)
+            .chain(chains)
+        {
+            write_u32(&mut buffer, offset, value)?;
+            offset += 4;
+        }
+
+        // The dynamic string table.
+        let dynstr_offset = range(OutputSection::DynStr).0;
+        buffer[dynstr_offset as usize..][..dynstr.len()].copy_from_slice(&dynstr);
+
+        // The dynamic section.
+        let mut dynamic_entries = needed_names
+            .into_iter()
+            .map(|name| (DT_NEEDED, u64::from(name)))
+            .collect::<Vec<_>>();
+        dynamic_entries.extend([
+            (DT_HASH, range(OutputSection::Hash).0),
+            (DT_STRTAB, range(OutputSection::DynStr).0),
+            (DT_SYMTAB, range(OutputSection::DynSym).0),
+            (DT_STRSZ, dynstr.len() as u64),
+            (DT_SYMENT, SYM_SIZE),
+            (DT_RELA, range(OutputSection::RelaDyn).0),
+            (DT_RELASZ, RELA_SIZE * self.num_dynamic_relocations),
+            (DT_RELAENT, RELA_SIZE),
+        ]);
+        if has_init_array {
+            let (start, end) = range(OutputSection::InitArray);
+            dynamic_entries.extend([(DT_INIT_ARRAY, start), (DT_INIT_ARRAYSZ, end - start)]);
+        }
+        if has_fini_array {
+            let (start, end) = range(OutputSection::FiniArray);
+            dynamic_entries.extend([(DT_FINI_ARRAY, start), (DT_FINI_ARRAYSZ, end - start)]);
+        }
+        dynamic_entries.push((DT_NULL, 0));
+
+        let mut offset = range(OutputSection::Dynamic).0;
+        for (tag, value) in dynamic_entries {
+            write_u64(&mut buffer, offset, tag)?;
+            write_u64(&mut buffer, offset + 8, value)?;
+            offset += DYN_SIZE;
+        }
+
+        // The ELF header.
+        buffer[..16].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        write_u16(&mut buffer, 16, 3)?; // ET_DYN
+        write_u16(&mut buffer, 18, self.arch.machine())?;
+        write_u32(&mut buffer, 20, 1)?;
+        write_u64(&mut buffer, 24, 0)?;
+        write_u64(&mut buffer, 32, EHDR_SIZE)?;
+        write_u64(&mut buffer, 40, shoff)?;
+        write_u32(&mut buffer, 48, 0)?;
+        write_u16(&mut buffer, 52, EHDR_SIZE as u16)?;
+        write_u16(&mut buffer, 54, PHDR_SIZE as u16)?;
+        write_u16(&mut buffer, 56, NUM_PHDRS as u16)?;
+        write_u16(&mut buffer, 58, SHDR_SIZE as u16)?;
+        write_u16(&mut buffer, 60, num_sections as u16)?;
+        write_u16(&mut buffer, 62, num_sections as u16 - 1)?;
+
+        // The program headers.
+        let (text_start, data_start) = (segment_starts[0], segment_starts[1]);
+        let (dynamic_start, dynamic_end) = range(OutputSection::Dynamic);
+        let program_headers = [
+            (PT_LOAD, PF_R, 0, text_start, text_start, page_size),
+            (
+                PT_LOAD,
+                PF_R | PF_X,
+                text_start,
+                range(OutputSection::Plt).1 - text_start,
+                range(OutputSection::Plt).1 - text_start,
+                page_size,
+            ),
+            (
+                PT_LOAD,
+                PF_R | PF_W,
+                data_start,
+                file_size - data_start,
+                range(OutputSection::Bss).1 - data_start,
+                page_size,
+            ),
+            (
+                PT_DYNAMIC,
+                PF_R | PF_W,
+                dynamic_start,
+                dynamic_end - dynamic_start,
+                dynamic_end - dynamic_start,
+                8,
+            ),
+            (PT_GNU_STACK, PF_R | PF_W, 0, 0, 0, 16),
+        ];
+        for (idx, (kind, flags, offset, file_size, mem_size, align)) in
+            program_headers.into_iter().enumerate()
+        {
+            let base = EHDR_SIZE + PHDR_SIZE * idx as u64;
+            write_u32(&mut buffer, base, kind)?;
+            write_u32(&mut buffer, base + 4, flags)?;
+            write_u64(&mut buffer, base + 8, offset)?;
+            write_u64(&mut buffer, base + 16, offset)?;
+            write_u64(&mut buffer, base + 24, offset)?;
+            write_u64(&mut buffer, base + 32, file_size)?;
+            write_u64(&mut buffer, base + 40, mem_size)?;
+            write_u64(&mut buffer, base + 48, align)?;
+        }
+
+        // The section headers, which aren't needed to load the library but help debugging it.
+        buffer[shstrtab_offset as usize..][..shstrtab.len()].copy_from_slice(&shstrtab);
+        for (idx, output) in OutputSection::ALL.iter().enumerate() {
+            let (start, end) = range(*output);
+            let link = match output {
+                OutputSection::Hash | OutputSection::RelaDyn => OutputSection::DynSym.index(),
+                OutputSection::DynSym | OutputSection::Dynamic => OutputSection::DynStr.index(),
+                _ => 0,
+            };
+
+            let base = shoff + SHDR_SIZE * (idx as u64 + 1);
+            write_u32(&mut buffer, base, section_names[idx])?;
+            write_u32(&mut buffer, base + 4, output.kind())?;
+            write_u64(&mut buffer, base + 8, output.flags())?;
+            write_u64(&mut buffer, base + 16, start)?;
+            write_u64(&mut buffer, base + 24, start)?;
+            write_u64(&mut buffer, base + 32, end - start)?;
+            write_u32(&mut buffer, base + 40, link.into())?;
+            write_u32(
+                &mut buffer,
+                base + 44,
+                u32::from(*output == OutputSection::DynSym),
+            )?;
+            write_u64(&mut buffer, base + 48, 8)?;
+            write_u64(&mut buffer, base + 56, output.entry_size())?;
+        }
+
+        let base = shoff + SHDR_SIZE * (num_sections - 1);
+        write_u32(&mut buffer, base, section_names[OutputSection::ALL.len()])?;
+        write_u32(&mut buffer, base + 4, SHT_STRTAB)?;
+        write_u64(&mut buffer, base + 24, shstrtab_offset)?;
+        write_u64(&mut buffer, base + 32, shstrtab.len() as u64)?;
+        write_u64(&mut buffer, base + 48, 1)?;
+
+        Ok(buffer)
+    }
+
+    /// Patch the place `P` with the value `S` of its relocation.
+    fn apply_relocation(
+        &self,
+        buffer: &mut [u8],
+        kind: u32,
+        place: u64,
+        value: u64,
+        addend: i64,
+    ) -> Result<()> {
+        let target = value.wrapping_add_signed(addend);
+        let relative = target.wrapping_sub(place) as i64;
+
+        match (self.arch, kind) {
+            (Arch::X86_64, 2 | 4 | 9 | 41 | 42) | (Arch::AArch64, 261) => {
+                write_u32(buffer, place, fit_signed(relative, 32)? as u32)
+            }
+            (Arch::X86_64, 24) | (Arch::AArch64, 260) => write_u64(buffer, place, relative as u64),
+            (Arch::X86_64, 25) => write_u64(buffer, place, target.wrapping_sub(self.got_base)),
+            (Arch::X86_64, 26) => {
+                let relative = self
+                    .got_base
+                    .wrapping_add_signed(addend)
+                    .wrapping_sub(place);
+                write_u32(buffer, place, fit_signed(relative as i64, 32)? as u32)
+            }
+            (Arch::AArch64, 274) => {
+                let insn = read_u32(buffer, place)?;
+                write_u32(buffer, place, encode_adr(insn, fit_signed(relative, 21)?))
+            }
+            (Arch::AArch64, 275 | 311) => {
+                let pages = ((target & !0xfff) as i64).wrapping_sub((place & !0xfff) as i64) >> 12;
+                let insn = read_u32(buffer, place)?;
+                write_u32(buffer, place, encode_adr(insn, fit_signed(pages, 21)?))
+            }
+            (Arch::AArch64, 277 | 278 | 284 | 285 | 286 | 299 | 312) => {
+                let shift = match kind {
+                    284 => 1,
+                    285 => 2,
+                    286 | 312 => 3,
+                    299 => 4,
+                    _ => 0,
+                };
+                let insn = read_u32(buffer, place)?;
+                let imm = ((target & 0xfff) >> shift) as u32;
+                write_u32(buffer, place, (insn & !(0xfff << 10)) | (imm << 10))
+            }
+            (Arch::AArch64, 279 | 280 | 282 | 283) => {
+                let (bits, shift) = match kind {
+                    279 => (14, 5),
+                    280 => (19, 5),
+                    _ => (26, 0),
+                };
+                if relative & 0x3 != 0 {
+                    return Err(link_error("misaligned branch target"));
+                }
+
+                let mask = ((1u32 << bits) - 1) << shift;
+                let imm = (fit_signed(relative >> 2, bits)? as u32) << shift;
+                let insn = read_u32(buffer, place)?;
+                write_u32(buffer, place, (insn & !mask) | (imm & mask))
+            }
+            _ => Err(link_error(format!(
+                "unsupported relocation type {kind} for {:?}",
+                self.arch
+            ))),
+        }
+    }
+
+    /// Write a stub which jumps to the address stored in a GOT entry.
+    fn write_stub(&self, buffer: &mut [u8], place: u64, slot: u64) -> Result<()> {
+        match self.arch {
+            Arch::X86_64 => {
+                // jmp *slot(%rip), followed by int3 padding.
+                let offset = fit_signed(slot.wrapping_sub(place + 6) as i64, 32)? as u32;
+                buffer[place as usize..][..PLT_ENTRY_SIZE as usize].fill(0xcc);
+                buffer[place as usize..][..2].copy_from_slice(&[0xff, 0x25]);
+                write_u32(buffer, place + 2, offset)
+            }
+            Arch::AArch64 => {
+                // adrp x16, slot; ldr x17, [x16, :lo12:slot]; br x17; nop
+                let pages = ((slot & !0xfff) as i64).wrapping_sub((place & !0xfff) as i64) >> 12;
+                write_u32(
+                    buffer,
+                    place,
+                    encode_adr(0x9000_0010, fit_signed(pages, 21)?),
+                )?;
+                write_u32(
+                    buffer,
+                    place + 4,
+                    0xf940_0211 | ((((slot & 0xfff) >> 3) as u32) << 10),
+                )?;
+                write_u32(buffer, place + 8, 0xd61f_0220)?;
+                write_u32(buffer, place + 12, 0xd503_201f)
+            }
+        }
+    }
+
+    fn has_output_section(&self, output: OutputSection) -> Result<bool> {
+        for object in &self.objects {
+            for section in &object.sections {
+                if OutputSection::of(section)? == Some(output) {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The address a symbol resolves to, or zero if it's undefined.
+    fn address(&self, target: Target) -> u64 {
+        match target {
+            Target::Section {
+                object,
+                section,
+                value,
+            } => {
+                self.objects[object].sections[section]
+                    .output
+                    .map(|(_, address)| address)
+                    .unwrap_or_default()
+                    + value
+            }
+            Target::Common(name) => self.commons[name],
+            Target::Absolute(value) => value,
+            Target::GotBase => self.got_base,
+            Target::Undefined(_) => 0,
+        }
+    }
+}
+
+/// Replace the immediate of an AArch64 `adr` or `adrp` instruction.
+const fn encode_adr(insn: u32, imm: i64) -> u32 {
+    let imm = imm as u32;
+    (insn & 0x9f00_001f) | ((imm & 0x3) << 29) | (((imm >> 2) & 0x7ffff) << 5)
+}
+
+/// Check that a value fits in a signed integer of `bits` bits.
+fn fit_signed(value: i64, bits: u32) -> Result<i64> {
+    let limit = 1i64 << (bits - 1);
+    if (-limit..limit).contains(&value) {
+        Ok(value)
+    } else {
+        Err(link_error("relocation out of range"))
+    }
+}
+
+/// The SysV ELF hash function, used in the `.hash` section.
+fn elf_hash(name: &[u8]) -> u32 {
+    let mut hash = 0u32;
+    for byte in name {
+        hash = (hash << 4).wrapping_add(u32::from(*byte));
+        let high = hash & 0xf000_0000;
+        hash ^= high >> 24;
+        hash &= !high;
+    }
+
+    hash
+}
+
+fn slice(data: &[u8], offset: u64, size: u64) -> Result<&[u8]> {
+    usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(size).ok())
+        .and_then(|(offset, size)| data.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| link_error("malformed object: out of bounds"))
+}
+
+fn read_u16(data: &[u8], offset: u64) -> Result<u16> {
+    Ok(u16::from_le_bytes(
+        slice(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(data: &[u8], offset: u64) -> Result<u32> {
+    Ok(u32::from_le_bytes(
+        slice(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64(data: &[u8], offset: u64) -> Result<u64> {
+    Ok(u64::from_le_bytes(
+        slice(data, offset, 8)?.try_into().unwrap(),
+    ))
+}
+
+fn read_str(data: &[u8], offset: u64) -> Result<&str> {
+    let data = data
+        .get(offset as usize..)
+        .ok_or_else(|| link_error("malformed object: out of bounds"))?;
+    let len = data
+        .iter()
+        .position(|x| *x == 0)
+        .ok_or_else(|| link_error("malformed object: unterminated string"))?;
+
+    std::str::from_utf8(&data[..len]).map_err(|_| link_error("malformed object: invalid string"))
+}
+
+fn write_bytes(buffer: &mut [u8], offset: u64, value: &[u8]) -> Result<()> {
+    buffer
+        .get_mut(offset as usize..)
+        .and_then(|x| x.get_mut(..value.len()))
+        .ok_or_else(|| link_error("write out of bounds"))?
+        .copy_from_slice(value);
+    Ok(())
+}
+
+fn write_u16(buffer: &mut [u8], offset: u64, value: u16) -> Result<()> {
+    write_bytes(buffer, offset, &value.to_le_bytes())
+}
+
+fn write_u32(buffer: &mut [u8], offset: u64, value: u32) -> Result<()> {
+    write_bytes(buffer, offset, &value.to_le_bytes())
+}
+
+fn write_u64(buffer: &mut [u8], offset: u64, value: u64) -> Result<()> {
+    write_bytes(buffer, offset, &value.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext,
+        executor::{AotContractExecutor, AotNativeExecutor},
+        ffi::{emit, initialize_llvm, Codegen, Output},
+        options::CompilationOptions,
+        starknet_stub::StubSyscallHandler,
+        statistics::CompilationStats,
+        utils::test::{
+            find_test_function, jit_enum, jit_struct, load_cairo, load_starknet_contract,
+        },
+        values::Value,
+        OptLevel, TargetOptions,
+    };
+    use cairo_lang_starknet_classes::contract_class::version_id_from_serialized_sierra_program;
+    use llvm_sys::{
+        core::{
+            LLVMContextCreate, LLVMContextDispose, LLVMCreateMemoryBufferWithMemoryRangeCopy,
+            LLVMDisposeModule,
+        },
+        ir_reader::LLVMParseIRInContext,
+    };
+    use starknet_types_core::felt::Felt;
+    use std::{
+        ffi::{c_char, c_void, CStr},
+        io::Write,
+        ptr::null_mut,
+    };
+    use tempfile::NamedTempFile;
+
+    /// Exercises every kind of reference the linker has to resolve: direct and GOT-relative
+    /// accesses to local data, absolute pointers in data (both to local and imported symbols),
+    /// calls through the PLT and GOT loads of imported data.
+    const LINKED_IR: &str = r#"
+        @counter = global i64 5
+        @hidden_value = hidden global i64 7
+        @buffer = dso_local global [16 x i8] zeroinitializer
+        @message = private unnamed_addr constant [6 x i8] c"hello\00"
+        @message_ptr = global ptr @message
+        @strlen_ptr = global ptr @strlen
+        @environ = external global ptr
+        @weak_missing = extern_weak global i64
+
+        declare i64 @strlen(ptr)
+
+        define i64 @message_length() {
+            %length = call i64 @strlen(ptr @message)
+            ret i64 %length
+        }
+
+        define i64 @indirect_message_length() {
+            %strlen = load ptr, ptr @strlen_ptr
+            %message = load ptr, ptr @message_ptr
+            %length = call i64 %strlen(ptr %message)
+            ret i64 %length
+        }
+
+        define i64 @increment() {
+            %value = load i64, ptr @counter
+            %next = add i64 %value, 1
+            store i64 %next, ptr @counter
+            ret i64 %next
+        }
+
+        define hidden i64 @hidden_helper() {
+            %value = load i64, ptr @hidden_value
+            ret i64 %value
+        }
+
+        define i64 @call_hidden() {
+            %value = call i64 @hidden_helper()
+            ret i64 %value
+        }
+
+        define i8 @buffer_byte() {
+            %byte = load i8, ptr getelementptr (i8, ptr @buffer, i64 15)
+            ret i8 %byte
+        }
+
+        define ptr @environment() {
+            %environ = load ptr, ptr @environ
+            ret ptr %environ
+        }
+
+        define ptr @weak_address() {
+            ret ptr @weak_missing
+        }
+    "#;
+
+    /// Compile textual LLVM IR into an object for the target.
+    fn compile_ir(ir: &str, target: &TargetOptions) -> Vec<u8> {
+        initialize_llvm();
+
+        let ir = format!(
+            "target triple = \"{}\"\n{ir}",
+            crate::ffi::get_target_triple(target)
+        );
+        unsafe {
+            let context = LLVMContextCreate();
+            let buffer = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+                ir.as_ptr().cast(),
+                ir.len(),
+                c"test".as_ptr(),
+            );
+
+            let mut module = null_mut();
+            let mut message = null_mut::<c_char>();
+            let failed = LLVMParseIRInContext(context, buffer, &mut module, &mut message);
+            assert_eq!(failed, 0, "{:?}", CStr::from_ptr(message));
+
+            let object = emit(
+                module,
+                &Codegen {
+                    opt_level: OptLevel::None,
+                    target: target.clone(),
+                    options: &CompilationOptions::default(),
+                    output: Output::Object,
+                    guard: None,
+                },
+                &mut CompilationStats::default(),
+            )
+            .unwrap();
+
+            LLVMDisposeModule(module);
+            LLVMContextDispose(context);
+
+            object
+        }
+    }
+
+    /// The names of the library's dynamic symbols, split into the imports and the exports.
+    fn dynamic_symbols(library: &[u8]) -> (Vec<String>, Vec<String>) {
+        let shoff = read_u64(library, 40).unwrap();
+        let shnum = read_u16(library, 60).unwrap();
+        let header = (0..u64::from(shnum))
+            .map(|idx| shoff + idx * SHDR_SIZE)
+            .find(|header| read_u32(library, header + 4).unwrap() == SHT_DYNSYM)
+            .unwrap();
+        let strtab_header = shoff + SHDR_SIZE * u64::from(read_u32(library, header + 40).unwrap());
+
+        let symbols = slice(
+            library,
+            read_u64(library, header + 24).unwrap(),
+            read_u64(library, header + 32).unwrap(),
+        )
+        .unwrap();
+        let strtab = slice(
+            library,
+            read_u64(library, strtab_header + 24).unwrap(),
+            read_u64(library, strtab_header + 32).unwrap(),
+        )
+        .unwrap();
+
+        let (mut imports, mut exports) = (Vec::new(), Vec::new());
+        for entry in symbols.chunks_exact(SYM_SIZE as usize).skip(1) {
+            let name = read_str(strtab, read_u32(entry, 0).unwrap().into())
+                .unwrap()
+                .to_string();
+            match read_u16(entry, 6).unwrap() {
+                SHN_UNDEF => imports.push(name),
+                _ => exports.push(name),
+            }
+        }
+
+        (imports, exports)
+    }
+
+    #[test]
+    fn link_relocations() {
+        let object = compile_ir(LINKED_IR, &TargetOptions::default());
+        let library = link(&[&object]).unwrap();
+
+        let (imports, exports) = dynamic_symbols(&library);
+        assert_eq!(imports, ["environ", "strlen", "weak_missing"]);
+        assert!(exports.contains(&"increment".to_string()));
+        assert!(exports.contains(&"counter".to_string()));
+        assert!(!exports.contains(&"hidden_helper".to_string()));
+        assert!(!exports.contains(&"hidden_value".to_string()));
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(&library).unwrap();
+        let path = file.into_temp_path();
+
+        unsafe {
+            let library = Library::new(&*path).unwrap();
+            let call = |name: &str| {
+                library
+                    .get::<unsafe extern "C" fn() -> i64>(name.as_bytes())
+                    .unwrap()()
+            };
+
+            assert_eq!(call("message_length"), 5);
+            assert_eq!(call("indirect_message_length"), 5);
+            assert_eq!(call("increment"), 6);
+            assert_eq!(call("increment"), 7);
+            assert_eq!(**library.get::<*const i64>(b"counter").unwrap(), 7);
+            assert_eq!(call("call_hidden"), 7);
+            assert_eq!(call("buffer_byte") as u8, 0);
+
+            // The imports are bound to the C library's symbols, and the weak one to null.
+            let environ = libc::dlsym(libc::RTLD_DEFAULT, c"environ".as_ptr());
+            let environment = library
+                .get::<unsafe extern "C" fn() -> *const c_void>(b"environment")
+                .unwrap()();
+            assert_eq!(environment, *environ.cast::<*const c_void>());
+            let weak_address = library
+                .get::<unsafe extern "C" fn() -> *const c_void>(b"weak_address")
+                .unwrap()();
+            assert!(weak_address.is_null());
+
+            // Hidden symbols are internal to the library.
+            assert!(library.get::<*const ()>(b"hidden_helper").is_err());
+            assert!(library.get::<*const ()>(b"hidden_value").is_err());
+        }
+    }
+
+    #[test]
+    fn link_aarch64() {
+        let target = TargetOptions {
+            triple: Some("aarch64-unknown-linux-gnu".to_string()),
+            ..Default::default()
+        };
+        let object = compile_ir(LINKED_IR, &target);
+        let library = link(&[&object]).unwrap();
+
+        // A shared library for EM_AARCH64.
+        assert_eq!(read_u16(&library, 16).unwrap(), 3);
+        assert_eq!(read_u16(&library, 18).unwrap(), EM_AARCH64);
+
+        let (imports, exports) = dynamic_symbols(&library);
+        assert_eq!(imports, ["environ", "strlen", "weak_missing"]);
+        assert!(exports.contains(&"indirect_message_length".to_string()));
+        assert!(!exports.contains(&"hidden_helper".to_string()));
+    }
+
+    #[test]
+    fn link_undefined_import() {
+        let object = compile_ir(
+            r#"
+                declare void @cairo_native__missing()
+
+                define void @run_test() {
+                    call void @cairo_native__missing()
+                    ret void
+                }
+            "#,
+            &TargetOptions::default(),
+        );
+
+        match link(&[&object]) {
+            Err(Error::LinkError(msg)) => assert!(msg.contains("`cairo_native__missing`")),
+            x => panic!("expected a link error, got {x:?}"),
+        }
+    }
+
+    #[test]
+    fn link_thread_local() {
+        let object = compile_ir(
+            r#"
+                @value = thread_local global i64 3
+
+                define i64 @run_test() {
+                    %value = load i64, ptr @value
+                    ret i64 %value
+                }
+            "#,
+            &TargetOptions::default(),
+        );
+
+        match link(&[&object]) {
+            Err(Error::LinkError(msg)) => assert!(msg.contains("thread-local storage")),
+            x => panic!("expected a link error, got {x:?}"),
+        }
+    }
+
+    #[test]
+    fn link_runtime_calls() {
+        // Arrays and dictionaries call into the C library and the runtime, and the `u128`
+        // division into the compiler's runtime library.
+        let program = load_cairo! {
+            use core::dict::Felt252DictTrait;
+
+            fn run_test(value: u128) -> (u32, felt252, u128) {
+                let mut items = ArrayTrait::new();
+                let mut i: u32 = 0;
+                while i != 10 {
+                    items.append(i);
+                    i += 1;
+                };
+
+                let mut dict: Felt252Dict<felt252> = Default::default();
+                dict.insert(2, 5);
+
+                (items.len(), dict.get(2), value / 7)
+            }
+        };
+
+        let module = NativeContext::new()
+            .with_options(CompilationOptions {
+                codegen_units: 2,
+                builtin_linker: true,
+                ..Default::default()
+            })
+            .compile(&program.1, false, Some(Default::default()))
+            .unwrap();
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::Default).unwrap();
+
+        let result = executor
+            .invoke_dynamic(
+                &find_test_function(&program, "run_test").id,
+                &[Value::Uint128(700)],
+                Some(u64::MAX),
+            )
+            .unwrap();
+        assert_eq!(
+            result.return_value,
+            jit_enum!(
+                0,
+                jit_struct!(jit_struct!(
+                    Value::Uint32(10),
+                    Value::Felt252(5.into()),
+                    Value::Uint128(100)
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn link_contract() {
+        let (_, contract) = load_starknet_contract! {
+            #[starknet::interface]
+            trait ICounter<TContractState> {
+                fn increment(ref self: TContractState, amount: u128) -> u128;
+            }
+
+            #[starknet::contract]
+            mod counter {
+                use starknet::storage::{StoragePointerReadAccess, StoragePointerWriteAccess};
+
+                #[storage]
+                struct Storage {
+                    value: u128,
+                }
+
+                #[abi(embed_v0)]
+                impl CounterImpl of super::ICounter<ContractState> {
+                    fn increment(ref self: ContractState, amount: u128) -> u128 {
+                        let value = self.value.read() + amount;
+                        self.value.write(value);
+                        value / 2
+                    }
+                }
+            }
+        };
+
+        let (sierra_version, _) =
+            version_id_from_serialized_sierra_program(&contract.sierra_program).unwrap();
+        let executor = AotContractExecutor::new_with_options(
+            &contract.extract_sierra_program().unwrap(),
+            &contract.entry_points_by_type,
+            sierra_version,
+            OptLevel::Default,
+            &Default::default(),
+            &CompilationOptions {
+                builtin_linker: true,
+                ..Default::default()
+            },
+            &TargetOptions::default(),
+        )
+        .unwrap();
+
+        let selector = Felt::from(&contract.entry_points_by_type.external[0].selector);
+        let mut syscall_handler = StubSyscallHandler::default();
+        for expected in [5, 10] {
+            let result = executor
+                .run(selector, &[10.into()], u64::MAX, None, &mut syscall_handler)
+                .unwrap();
+            assert!(!result.failure_flag);
+            assert_eq!(result.return_values, vec![Felt::from(expected)]);
+        }
+    }
+
+    #[test]
+    fn link_invalid_object() {
+        assert!(matches!(
+            link(&[b"not an object".as_slice()]),
+            Err(Error::LinkError(_))
+        ));
+        assert!(matches!(link(&[]), Err(Error::LinkError(_))));
+    }
+}
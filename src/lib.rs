@@ -10,8 +10,17 @@
 
 pub use self::{
     compiler::compile,
-    ffi::{module_to_object, object_to_shared_lib, OptLevel},
+    dump::{DumpConfig, DumpStage},
+    ffi::{
+        module_to_assembly, module_to_llvm_ir, module_to_object, module_to_object_with_options,
+        module_to_objects, object_to_ephemeral_shared_lib, object_to_shared_lib,
+        objects_to_ephemeral_shared_lib, objects_to_shared_lib, CodeModel, CpuBaseline,
+        EphemeralSharedLib, OptLevel, RelocModel, TargetCpu, TargetOptions,
+    },
+    limits::CompilationLimits,
+    options::{CompilationOptions, SizeLevel},
     runtime::FormattedItem,
+    statistics::CompilationStats,
     values::Value,
 };
 
@@ -21,16 +30,20 @@ mod compiler;
 pub mod context;
 pub mod debug;
 pub mod docs;
+pub mod dump;
 pub mod error;
 pub mod execution_result;
 pub mod executor;
 mod ffi;
 mod libfuncs;
+pub mod limits;
 pub mod metadata;
 pub mod module;
+pub mod options;
 mod runtime;
 pub mod starknet;
 pub mod starknet_stub;
+pub mod statistics;
 mod types;
 pub mod utils;
 mod values;
@@ -0,0 +1,222 @@
+//! # Compilation limits
+//!
+//! Limits on the resources a program's compilation may use, so that pathological programs (for
+//! example, contracts declared by untrusted parties) are refused instead of stalling the process.
+//!
+//! The limits are set in the [`NativeContext`](crate::context::NativeContext), which stores a
+//! resource guard in the module's metadata so that the limits keep being checked while
+//! generating the code. The time and memory are measured separately for compiling the module and
+//! for loading it into an executor, so a module may be loaded long after it was compiled.
+//!
+//! The checks happen after every function's compilation and between the compilation phases. The
+//! MLIR and LLVM passes and the code generation can't be interrupted, so a compilation exceeding
+//! its limits within them is only refused once they finish. Untrusted programs should also be
+//! bounded by their size (`max_statements` and `max_functions`), which is checked upfront.
+
+use crate::{
+    error::{ResourceLimitError, Result},
+    utils::peak_resident_memory,
+};
+use cairo_lang_sierra::program::Program;
+use std::time::{Duration, Instant};
+
+/// The resources a compilation may use. Everything is unlimited by default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CompilationLimits {
+    /// The maximum wall time of compiling the program into a module, and then of loading the module
+    /// into an executor (generating and linking its code), each measured on its own.
+    ///
+    /// [`AotContractExecutor::new_with_limits`](crate::executor::AotContractExecutor::new_with_limits)
+    /// does both at once, so it's the time of the whole call.
+    pub max_time: Option<Duration>,
+    /// The maximum number of Sierra statements.
+    pub max_statements: Option<usize>,
+    /// The maximum number of Sierra functions.
+    pub max_functions: Option<usize>,
+    /// The maximum growth of the process' resident memory while compiling, in bytes. Like the
+    /// time, it's measured separately for the module and for the executor.
+    ///
+    /// It's measured for the whole process, therefore other threads' allocations count too.
+    pub max_memory: Option<u64>,
+}
+
+/// Tracks the resources used by a compilation phase since it started.
+#[derive(Clone, Debug)]
+pub(crate) struct ResourceGuard {
+    limits: CompilationLimits,
+    start: Instant,
+    base_memory: u64,
+}
+
+impl ResourceGuard {
+    /// Start tracking a compilation, checking the program's size right away.
+    pub fn new(limits: &CompilationLimits, program: &Program) -> Result<Self> {
+        if let Some(limit) = limits.max_statements {
+            if program.statements.len() > limit {
+                return Err(ResourceLimitError::Statements {
+                    limit,
+                    found: program.statements.len(),
+                }
+                .into());
+            }
+        }
+        if let Some(limit) = limits.max_functions {
+            if program.funcs.len() > limit {
+                return Err(ResourceLimitError::Functions {
+                    limit,
+                    found: program.funcs.len(),
+                }
+                .into());
+            }
+        }
+
+        Ok(Self::start(limits.clone()))
+    }
+
+    fn start(limits: CompilationLimits) -> Self {
+        Self {
+            base_memory: match limits.max_memory {
+                Some(_) => resident_memory(),
+                None => 0,
+            },
+            start: Instant::now(),
+            limits,
+        }
+    }
+
+    /// Start tracking the next phase of the compilation with the same limits, like loading the
+    /// compiled module into an executor.
+    pub fn restart(self) -> Self {
+        Self::start(self.limits)
+    }
+
+    /// Check that the compilation is still within its time and memory limits.
+    pub fn check(&self) -> Result<()> {
+        if let Some(limit) = self.limits.max_time {
+            if self.start.elapsed() > limit {
+                return Err(ResourceLimitError::Time { limit }.into());
+            }
+        }
+        if let Some(limit) = self.limits.max_memory {
+            let used = resident_memory().saturating_sub(self.base_memory);
+            if used > limit {
+                return Err(ResourceLimitError::Memory { limit, used }.into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the limits of an optional guard.
+pub(crate) fn check(guard: Option<&ResourceGuard>) -> Result<()> {
+    guard.map_or(Ok(()), ResourceGuard::check)
+}
+
+/// The process' current resident memory, in bytes.
+fn resident_memory() -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        // The second field is the resident set size, in pages.
+        let pages = std::fs::read_to_string("/proc/self/statm")
+            .ok()
+            .and_then(|x| x.split_whitespace().nth(1)?.parse::<u64>().ok());
+        if let Some(pages) = pages {
+            return pages * unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        }
+    }
+
+    // Fall back to the peak resident memory, which never decreases.
+    peak_resident_memory()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext, error::Error, executor::AotNativeExecutor, utils::test::load_cairo,
+    };
+
+    #[test]
+    fn test_limits_program_size() {
+        let (_, program) = load_cairo! {
+            fn run_test(x: felt252) -> felt252 {
+                double(x) + 1
+            }
+
+            fn double(x: felt252) -> felt252 {
+                x * 2
+            }
+        };
+
+        let native_context = NativeContext::new().with_limits(CompilationLimits {
+            max_functions: Some(1),
+            ..Default::default()
+        });
+        let result = native_context.compile(&program, false, Some(Default::default()));
+        assert!(matches!(
+            result,
+            Err(Error::ResourceLimitExceeded(
+                ResourceLimitError::Functions { limit: 1, .. }
+            ))
+        ));
+
+        let native_context = NativeContext::new().with_limits(CompilationLimits {
+            max_statements: Some(1),
+            ..Default::default()
+        });
+        let result = native_context.compile(&program, false, Some(Default::default()));
+        assert!(matches!(
+            result,
+            Err(Error::ResourceLimitExceeded(
+                ResourceLimitError::Statements { limit: 1, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_limits_time() {
+        let (_, program) = load_cairo! {
+            fn run_test(x: felt252) -> felt252 {
+                x * 2
+            }
+        };
+
+        let native_context = NativeContext::new().with_limits(CompilationLimits {
+            max_time: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        let result = native_context.compile(&program, false, Some(Default::default()));
+        assert!(matches!(
+            result,
+            Err(Error::ResourceLimitExceeded(
+                ResourceLimitError::Time { .. }
+            ))
+        ));
+
+        // Generous limits don't get in the way.
+        let native_context = NativeContext::new().with_limits(CompilationLimits {
+            max_time: Some(Duration::from_secs(3600)),
+            max_statements: Some(10_000),
+            max_functions: Some(100),
+            max_memory: Some(u64::MAX),
+        });
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        AotNativeExecutor::from_native_module(module, Default::default()).unwrap();
+    }
+
+    #[test]
+    fn test_limits_time_per_phase() {
+        let guard = ResourceGuard::start(CompilationLimits {
+            max_time: Some(Duration::from_millis(200)),
+            ..Default::default()
+        });
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(guard.check().is_err());
+
+        // The next phase, like loading the module into an executor, gets the whole limit again.
+        assert!(guard.restart().check().is_ok());
+    }
+}
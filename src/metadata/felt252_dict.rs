@@ -16,7 +16,7 @@ use melior::{
     },
     Context,
 };
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
 
 #[derive(Clone, Debug, Default)]
 pub struct Felt252DictOverrides {
@@ -33,6 +33,36 @@ impl Felt252DictOverrides {
         self.drop_overrides.get(type_id).map(String::as_str)
     }
 
+    /// Return the dup and drop override symbols, indexed by their type's id.
+    pub fn symbols(&self) -> (BTreeMap<u64, String>, BTreeMap<u64, String>) {
+        let collect = |overrides: &HashMap<ConcreteTypeId, String>| {
+            overrides
+                .iter()
+                .map(|(type_id, symbol)| (type_id.id, symbol.clone()))
+                .collect()
+        };
+
+        (collect(&self.dup_overrides), collect(&self.drop_overrides))
+    }
+
+    /// Rebuild the overrides from the output of [`symbols`](Self::symbols).
+    pub fn from_symbols(
+        dup_overrides: BTreeMap<u64, String>,
+        drop_overrides: BTreeMap<u64, String>,
+    ) -> Self {
+        let collect = |overrides: BTreeMap<u64, String>| {
+            overrides
+                .into_iter()
+                .map(|(id, symbol)| (ConcreteTypeId::new(id), symbol))
+                .collect()
+        };
+
+        Self {
+            dup_overrides: collect(dup_overrides),
+            drop_overrides: collect(drop_overrides),
+        }
+    }
+
     pub fn build_dup_fn<'ctx>(
         &mut self,
         context: &'ctx Context,
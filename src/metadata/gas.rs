@@ -141,6 +141,50 @@ impl GasMetadata {
             .collect()
     }
 
+    /// Rebuild the metadata required at runtime from the output of
+    /// [`initial_required_gas_for_entry_points`](Self::initial_required_gas_for_entry_points).
+    ///
+    /// Only the function costs are restored, which is enough to run an already compiled program.
+    pub fn from_function_costs(
+        function_costs: &BTreeMap<u64, BTreeMap<u64, u64>>,
+    ) -> NativeResult<Self> {
+        let function_costs = function_costs
+            .iter()
+            .map(|(function_id, costs)| {
+                let costs = costs
+                    .iter()
+                    .map(|(offset, val)| {
+                        let token = match offset {
+                            0 => CostTokenType::Const,
+                            1 => CostTokenType::Pedersen,
+                            2 => CostTokenType::Bitwise,
+                            3 => CostTokenType::EcOp,
+                            4 => CostTokenType::Poseidon,
+                            5 => CostTokenType::AddMod,
+                            6 => CostTokenType::MulMod,
+                            _ => native_panic!("unexpected CostTokenType offset: {offset}"),
+                        };
+
+                        Ok((token, *val as i64))
+                    })
+                    .collect::<NativeResult<_>>()?;
+
+                Ok((FunctionId::new(*function_id), costs))
+            })
+            .collect::<NativeResult<_>>()?;
+
+        Ok(Self {
+            ap_change_info: ApChangeInfo {
+                variable_values: Default::default(),
+                function_ap_change: Default::default(),
+            },
+            gas_info: GasInfo {
+                variable_values: Default::default(),
+                function_costs,
+            },
+        })
+    }
+
     pub fn get_gas_costs_for_statement(&self, idx: StatementIdx) -> Vec<(u64, CostTokenType)> {
         let mut costs = Vec::new();
         for cost_type in CostTokenType::iter_casm_tokens() {
@@ -24,6 +24,12 @@ use std::{
     ffi::{c_int, c_void},
 };
 
+/// The version of the interface between the compiled code and the runtime library.
+///
+/// It must be bumped whenever a runtime function is added, removed or changes its signature, since
+/// libraries compiled against a different version can't be set up with [`setup_runtime`].
+pub const RUNTIME_ABI_VERSION: u32 = 1;
+
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
 enum RuntimeBinding {
     Pedersen,
@@ -1,4 +1,4 @@
-use crate::metadata::MetadataStorage;
+use crate::{metadata::MetadataStorage, statistics::CompilationStats};
 use cairo_lang_sierra::{
     extensions::core::{CoreLibfunc, CoreType},
     program_registry::ProgramRegistry,
@@ -58,6 +58,11 @@ impl<'m> NativeModule<'m> {
         self.metadata.get::<T>()
     }
 
+    /// Retrieve the statistics of the Sierra to MLIR compilation.
+    pub fn compilation_stats(&self) -> Option<&CompilationStats> {
+        self.metadata.get::<CompilationStats>()
+    }
+
     pub const fn metadata(&self) -> &MetadataStorage {
         &self.metadata
     }
@@ -0,0 +1,175 @@
+//! # Compilation options
+//!
+//! Tweaks to the MLIR and LLVM pass pipelines, to trade off code size, execution speed and
+//! compilation time. The optimization level is chosen separately, as an [`OptLevel`].
+//!
+//! The options are set in the [`NativeContext`](crate::context::NativeContext), which applies the
+//! MLIR ones and stores them in the module's metadata so that the AOT executors apply the LLVM ones
+//! (and split the code into codegen units) when generating the code. The JIT executor uses its own
+//! LLVM pipeline, ignoring them.
+
+use crate::OptLevel;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CompilationOptions {
+    /// Optimize for code size instead of speed.
+    pub size_level: SizeLevel,
+    /// Skip the MLIR canonicalizer, which speeds up the compilation of big programs at the cost of
+    /// giving more work to LLVM. The lowering passes are always run.
+    pub skip_canonicalizer: bool,
+    /// Extra MLIR passes, in MLIR's textual pipeline syntax (ex. `cse`, `symbol-dce`), run before
+    /// lowering to LLVM.
+    pub mlir_passes: Vec<String>,
+    /// Extra LLVM passes, in `opt`'s `-passes` syntax (ex. `globaldce`), run after the default
+    /// pipeline.
+    pub llvm_passes: Vec<String>,
+    /// Split the code into (up to) this many objects, which are optimized and emitted in parallel.
+    /// Functions can't be inlined across objects, so more units trade speed for compilation time.
+    /// Both 0 and 1 emit a single object.
+    pub codegen_units: usize,
+    /// Link the shared libraries with the built-in ELF linker instead of the system's `ld`, so that
+    /// no external tool is needed. It's only available on glibc-based Linux for x86-64 and AArch64
+    /// (the libraries it links need glibc and `libgcc_s.so.1`), and objects using features it
+    /// doesn't support (like thread-local storage) fail to link instead of falling back to `ld`.
+    pub builtin_linker: bool,
+}
+
+/// How much to prioritize the code size over its speed.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum SizeLevel {
+    /// Optimize for speed, as determined by the [`OptLevel`].
+    #[default]
+    None,
+    /// Optimize for size, without hurting the speed significantly (`-Os`).
+    Small,
+    /// Optimize for size at any cost (`-Oz`).
+    Smallest,
+}
+
+impl CompilationOptions {
+    /// The MLIR passes to run, in MLIR's textual pipeline syntax.
+    pub fn mlir_pipeline(&self) -> String {
+        let mut passes = Vec::new();
+        if !self.skip_canonicalizer {
+            passes.push("canonicalize");
+        }
+        passes.extend(self.mlir_passes.iter().map(String::as_str));
+        // The SCF dialect must be lowered explicitly, since `convert-to-llvm` doesn't include it.
+        passes.extend(["convert-scf-to-cf", "convert-to-llvm"]);
+
+        format!("builtin.module({})", passes.join(","))
+    }
+
+    /// The LLVM passes to run, in `opt`'s `-passes` syntax.
+    pub fn llvm_pipeline(&self, opt_level: OptLevel) -> String {
+        let default = match self.size_level {
+            // slp-vectorizer pass did cause some issues, but after the change
+            // on function attributes it seems to not trigger them anymore.
+            // https://github.com/llvm/llvm-project/issues/107198
+            SizeLevel::None => format!("default<O{}>", usize::from(opt_level)),
+            SizeLevel::Small => "default<Os>".to_string(),
+            SizeLevel::Smallest => "default<Oz>".to_string(),
+        };
+
+        std::iter::once(default)
+            .chain(self.llvm_passes.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext, error::Error, executor::AotNativeExecutor, utils::test::load_cairo,
+        values::Value,
+    };
+    use starknet_types_core::felt::Felt;
+
+    #[test]
+    fn test_pipelines() {
+        let options = CompilationOptions::default();
+        assert_eq!(
+            options.mlir_pipeline(),
+            "builtin.module(canonicalize,convert-scf-to-cf,convert-to-llvm)"
+        );
+        assert_eq!(options.llvm_pipeline(OptLevel::Default), "default<O2>");
+
+        let options = CompilationOptions {
+            size_level: SizeLevel::Smallest,
+            skip_canonicalizer: true,
+            mlir_passes: vec!["cse".to_string()],
+            llvm_passes: vec!["globaldce".to_string()],
+            codegen_units: 1,
+            builtin_linker: false,
+        };
+        assert_eq!(
+            options.mlir_pipeline(),
+            "builtin.module(cse,convert-scf-to-cf,convert-to-llvm)"
+        );
+        assert_eq!(
+            options.llvm_pipeline(OptLevel::Aggressive),
+            "default<Oz>,globaldce"
+        );
+    }
+
+    #[test]
+    fn test_size_optimized_program() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                fib(10)
+            }
+
+            fn fib(n: felt252) -> felt252 {
+                if n == 0 || n == 1 {
+                    n
+                } else {
+                    fib(n - 1) + fib(n - 2)
+                }
+            }
+        };
+
+        let native_context = NativeContext::new().with_options(CompilationOptions {
+            size_level: SizeLevel::Smallest,
+            mlir_passes: vec!["cse".to_string()],
+            llvm_passes: vec!["globaldce".to_string()],
+            ..Default::default()
+        });
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::Default).unwrap();
+
+        let function_id = &program.funcs.first().expect("should have a function").id;
+        let result = executor
+            .invoke_dynamic(function_id, &[], Some(u64::MAX))
+            .unwrap();
+        assert_eq!(result.return_value, Value::Felt252(Felt::from(55)));
+    }
+
+    #[test]
+    fn test_invalid_llvm_passes() {
+        let (_, program) = load_cairo! {
+            fn run_test() -> felt252 {
+                42
+            }
+        };
+
+        let native_context = NativeContext::new().with_options(CompilationOptions {
+            llvm_passes: vec!["not-a-pass".to_string()],
+            ..Default::default()
+        });
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+
+        assert!(matches!(
+            AotNativeExecutor::from_native_module(module, OptLevel::Default),
+            Err(Error::LLVMCompileError(_))
+        ));
+    }
+}
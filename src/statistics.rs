@@ -0,0 +1,142 @@
+//! # Compilation statistics
+//!
+//! Measurements taken while compiling a program, to track how expensive every program is to
+//! compile. They're stored in the [`NativeModule`](crate::module::NativeModule)'s metadata by
+//! [`NativeContext::compile`](crate::context::NativeContext::compile), and completed by the
+//! executors which generate the code.
+
+use crate::utils::peak_resident_memory;
+use melior::ir::{
+    attribute::StringAttribute, operation::OperationLike, BlockLike, Module, OperationRef,
+    RegionLike,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+/// Statistics about a program's compilation.
+///
+/// The durations of the phases which didn't run (for example, linking when using the JIT) are
+/// zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompilationStats {
+    /// Time spent generating MLIR from the Sierra program.
+    pub sierra_to_mlir: Duration,
+    /// Time spent running the MLIR passes.
+    pub mlir_passes: Duration,
+    /// Time spent translating the MLIR into LLVM IR.
+    pub mlir_to_llvm: Duration,
+    /// Time spent running the LLVM passes.
+    pub llvm_passes: Duration,
+    /// Time spent generating the object.
+    pub object_emission: Duration,
+    /// Time spent linking the objects into a shared library.
+    pub linking: Duration,
+
+    /// The number of MLIR operations in every function, before running the passes.
+    pub function_operations: BTreeMap<String, usize>,
+    /// The size of the generated object, or the sum of them when the code is split into many.
+    pub object_size: usize,
+    /// The process' peak resident memory, in bytes, once the compilation finished.
+    ///
+    /// It's measured for the whole process, therefore it's only meaningful when nothing else is
+    /// running in parallel.
+    pub peak_memory: u64,
+}
+
+impl CompilationStats {
+    /// The total time spent compiling.
+    pub fn total_time(&self) -> Duration {
+        self.sierra_to_mlir
+            + self.mlir_passes
+            + self.mlir_to_llvm
+            + self.llvm_passes
+            + self.object_emission
+            + self.linking
+    }
+
+    /// Count the operations of every function in the module.
+    pub(crate) fn count_operations(&mut self, module: &Module) {
+        let mut op = module.body().first_operation();
+        while let Some(function) = op {
+            let name = function
+                .attribute("sym_name")
+                .ok()
+                .and_then(|x| StringAttribute::try_from(x).ok())
+                .map(|x| x.value().to_string());
+
+            if let Some(name) = name {
+                let count = count_nested_operations(function);
+                // Skip the declarations.
+                if count != 0 {
+                    self.function_operations.insert(name, count);
+                }
+            }
+
+            op = function.next_in_block();
+        }
+    }
+
+    /// Record the process' current peak memory.
+    pub(crate) fn record_peak_memory(&mut self) {
+        self.peak_memory = peak_resident_memory();
+    }
+}
+
+fn count_nested_operations(op: OperationRef) -> usize {
+    let mut count = 0;
+    for region in op.regions() {
+        let mut block = region.first_block();
+        while let Some(current_block) = block {
+            let mut nested = current_block.first_operation();
+            while let Some(current_op) = nested {
+                count += 1 + count_nested_operations(current_op);
+                nested = current_op.next_in_block();
+            }
+            block = current_block.next_in_region();
+        }
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext, executor::AotNativeExecutor, utils::test::load_cairo, OptLevel,
+    };
+
+    #[test]
+    fn test_compilation_stats() {
+        let (_, program) = load_cairo! {
+            fn run_test(x: felt252) -> felt252 {
+                double(x) + 1
+            }
+
+            fn double(x: felt252) -> felt252 {
+                x * 2
+            }
+        };
+
+        let native_context = NativeContext::new();
+        let module = native_context
+            .compile(&program, false, Some(Default::default()))
+            .unwrap();
+
+        let stats = module.compilation_stats().unwrap().clone();
+        assert!(stats.peak_memory > 0);
+        assert!(stats
+            .function_operations
+            .keys()
+            .any(|x| x.contains("double")));
+        assert!(stats.function_operations.values().all(|x| *x > 0));
+        assert_eq!(stats.object_size, 0);
+
+        let executor = AotNativeExecutor::from_native_module(module, OptLevel::Default).unwrap();
+        let stats = executor.compilation_stats().unwrap();
+        assert!(stats.object_size > 0);
+        assert!(!stats.llvm_passes.is_zero());
+        assert!(!stats.object_emission.is_zero());
+        assert!(!stats.linking.is_zero());
+    }
+}
@@ -533,10 +533,11 @@ impl TypeBuilder for CoreTypeConcrete {
 
                 value
             },
-            CoreTypeConcrete::Const(_) => native_panic!("todo: check Const is complex"),
+            CoreTypeConcrete::Const(info) => registry.get_type(&info.inner_ty)?.is_complex(registry)?,
             CoreTypeConcrete::Span(_) => native_panic!("todo: check Span is complex"),
-            CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Secp256Point(_))
-            | CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Sha256StateHandle(_)) => native_panic!("todo: check Sha256StateHandle is complex"),
+            CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Secp256Point(_)) => true,
+            // A pointer to the state.
+            CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Sha256StateHandle(_)) => false,
             CoreTypeConcrete::Coupon(_) => false,
 
             CoreTypeConcrete::Circuit(info) => circuit::is_complex(info),
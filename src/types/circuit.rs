@@ -284,9 +284,10 @@ pub fn layout(
 
             let n_gates = circuit.circuit_info.values.len();
 
-            let u384_layout = get_integer_layout(384);
+            // The gates' values followed by the modulus, all of them as `u384` structs.
+            let u384_struct_layout = layout_repeat(&get_integer_layout(96), 4)?.0;
 
-            let layout = layout_repeat(&u384_layout, n_gates)?.0;
+            let layout = layout_repeat(&u384_struct_layout, n_gates + 1)?.0;
 
             Ok(layout)
         }
@@ -1,11 +1,12 @@
 //! # Various utilities
 
+pub use self::reachability::reachable_subprogram;
 pub(crate) use self::{
     block_ext::{BlockExt, GepIndex},
     program_registry_ext::ProgramRegistryExt,
     range_ext::RangeExt,
 };
-use crate::{metadata::MetadataStorage, OptLevel};
+use crate::{metadata::MetadataStorage, options::CompilationOptions, OptLevel};
 use cairo_lang_compiler::CompilerConfig;
 use cairo_lang_runner::token_gas_cost;
 use cairo_lang_sierra::{
@@ -14,9 +15,7 @@ use cairo_lang_sierra::{
     program::{GenFunction, Program, StatementIdx},
 };
 use melior::{
-    ir::Module,
-    pass::{self, PassManager},
-    Context, Error, ExecutionEngine,
+    ir::Module, pass::PassManager, utility::parse_pass_pipeline, Context, Error, ExecutionEngine,
 };
 use num_bigint::{BigInt, BigUint, Sign};
 use serde::{Deserialize, Serialize};
@@ -34,6 +33,7 @@ mod block_ext;
 pub mod mem_tracing;
 mod program_registry_ext;
 mod range_ext;
+mod reachability;
 
 #[cfg(target_os = "macos")]
 pub const SHARED_LIBRARY_EXT: &str = "dylib";
@@ -227,6 +227,36 @@ pub fn find_function_id<'a>(program: &'a Program, function_name: &str) -> Option
         .map(|func| &func.id)
 }
 
+/// Compute a hash which identifies the program, as a hexadecimal string.
+///
+/// Any change to the program, including its debug names, results in a different hash.
+pub fn program_hash(program: &Program) -> crate::error::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let hash = Sha256::digest(serde_json::to_vec(program)?);
+    Ok(hash.iter().map(|x| format!("{x:02x}")).collect())
+}
+
+/// The process' peak resident memory, in bytes, or zero if it can't be determined.
+pub(crate) fn peak_resident_memory() -> u64 {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return 0;
+    }
+    let max_rss = unsafe { usage.assume_init() }.ru_maxrss as u64;
+
+    // Linux reports kibibytes while macOS reports bytes.
+    if cfg!(target_os = "macos") {
+        max_rss
+    } else {
+        max_rss * 1024
+    }
+}
+
+/// The [`program_hash`] of the program a module was compiled from, stored in its metadata.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ProgramHash(pub String);
+
 /// Parse a numeric string into felt, wrapping negatives around the prime modulo.
 pub fn felt252_str(value: &str) -> Felt {
     let value = value
@@ -283,12 +313,18 @@ pub fn create_engine(
     engine
 }
 
-pub fn run_pass_manager(context: &Context, module: &mut Module) -> Result<(), Error> {
+/// Run the MLIR passes which lower the module to the LLVM dialect, as configured by the `options`.
+pub fn run_pass_manager(
+    context: &Context,
+    module: &mut Module,
+    options: &CompilationOptions,
+) -> Result<(), Error> {
     let pass_manager = PassManager::new(context);
     pass_manager.enable_verifier(true);
-    pass_manager.add_pass(pass::transform::create_canonicalizer());
-    pass_manager.add_pass(pass::conversion::create_scf_to_control_flow()); // needed because to_llvm doesn't include it.
-    pass_manager.add_pass(pass::conversion::create_to_llvm());
+    parse_pass_pipeline(
+        pass_manager.as_operation_pass_manager(),
+        &options.mlir_pipeline(),
+    )?;
     pass_manager.run(module)
 }
 
@@ -562,21 +598,29 @@ pub mod test {
         (module_name.to_string(), sierra_program_with_dbg.program)
     }
 
+    /// Find a function of a test program by its name.
+    #[track_caller]
+    pub fn find_test_function<'a>(
+        program: &'a (String, Program),
+        name: &str,
+    ) -> &'a GenFunction<StatementIdx> {
+        let name = format!("{0}::{0}::{1}", program.0, name);
+        program
+            .1
+            .funcs
+            .iter()
+            .find(|x| x.id.debug_name.as_deref() == Some(&name))
+            .expect("Test program entry point not found.")
+    }
+
     pub fn run_program(
         program: &(String, Program),
         entry_point: &str,
         args: &[Value],
     ) -> ExecutionResult {
-        let entry_point = format!("{0}::{0}::{1}", program.0, entry_point);
+        let entry_point_id = &find_test_function(program, entry_point).id;
         let program = &program.1;
 
-        let entry_point_id = &program
-            .funcs
-            .iter()
-            .find(|x| x.id.debug_name.as_deref() == Some(&entry_point))
-            .expect("Test program entry point not found.")
-            .id;
-
         let context = NativeContext::new();
 
         let module = context
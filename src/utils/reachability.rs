@@ -0,0 +1,265 @@
+//! # Reachability analysis
+//!
+//! Contracts usually contain lots of code which can't be reached from the entry points that will
+//! actually be invoked. Pruning it before compiling saves both compile time and binary size.
+
+use crate::error::Result;
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType},
+        ConcreteLibfunc,
+    },
+    ids::{ConcreteLibfuncId, ConcreteTypeId, FunctionId},
+    program::{
+        BranchInfo, BranchTarget, GenericArg, Invocation, LibfuncDeclaration, Program, Statement,
+        StatementIdx, TypeDeclaration,
+    },
+    program_registry::ProgramRegistry,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Return a copy of `program` which only contains the functions, types, libfuncs and statements
+/// transitively reachable from the `roots`.
+///
+/// Functions, types and libfuncs keep their ids, therefore the roots can be invoked exactly as if
+/// the whole program had been compiled. The statements are renumbered.
+pub fn reachable_subprogram<'a>(
+    program: &Program,
+    roots: impl IntoIterator<Item = &'a FunctionId>,
+) -> Result<Program> {
+    let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(program)?;
+
+    let mut reachability = Reachability {
+        program,
+        registry: &registry,
+        type_declarations: program
+            .type_declarations
+            .iter()
+            .map(|x| (&x.id, x))
+            .collect(),
+        libfunc_declarations: program
+            .libfunc_declarations
+            .iter()
+            .map(|x| (&x.id, x))
+            .collect(),
+        pending_functions: roots.into_iter().cloned().collect(),
+        functions: HashSet::new(),
+        types: HashSet::new(),
+        libfuncs: HashSet::new(),
+        statements: BTreeSet::new(),
+    };
+    reachability.run()?;
+
+    // Since every statement's successors are also reachable, fallthrough branches remain valid after
+    // renumbering the statements in their original order.
+    let statement_mappings = reachability
+        .statements
+        .iter()
+        .enumerate()
+        .map(|(new_idx, old_idx)| (*old_idx, StatementIdx(new_idx)))
+        .collect::<HashMap<_, _>>();
+
+    Ok(Program {
+        type_declarations: program
+            .type_declarations
+            .iter()
+            .filter(|x| reachability.types.contains(&x.id))
+            .cloned()
+            .collect(),
+        libfunc_declarations: program
+            .libfunc_declarations
+            .iter()
+            .filter(|x| reachability.libfuncs.contains(&x.id))
+            .cloned()
+            .collect(),
+        statements: reachability
+            .statements
+            .iter()
+            .map(|idx| match &program.statements[*idx] {
+                Statement::Invocation(invocation) => Statement::Invocation(Invocation {
+                    libfunc_id: invocation.libfunc_id.clone(),
+                    args: invocation.args.clone(),
+                    branches: invocation
+                        .branches
+                        .iter()
+                        .map(|branch| BranchInfo {
+                            target: match &branch.target {
+                                BranchTarget::Fallthrough => BranchTarget::Fallthrough,
+                                BranchTarget::Statement(target) => {
+                                    BranchTarget::Statement(statement_mappings[&target.0])
+                                }
+                            },
+                            results: branch.results.clone(),
+                        })
+                        .collect(),
+                }),
+                Statement::Return(vars) => Statement::Return(vars.clone()),
+            })
+            .collect(),
+        funcs: program
+            .funcs
+            .iter()
+            .filter(|x| reachability.functions.contains(&x.id))
+            .map(|x| {
+                let mut function = x.clone();
+                function.entry_point = statement_mappings[&x.entry_point.0];
+                function
+            })
+            .collect(),
+    })
+}
+
+struct Reachability<'a> {
+    program: &'a Program,
+    registry: &'a ProgramRegistry<CoreType, CoreLibfunc>,
+    type_declarations: HashMap<&'a ConcreteTypeId, &'a TypeDeclaration>,
+    libfunc_declarations: HashMap<&'a ConcreteLibfuncId, &'a LibfuncDeclaration>,
+
+    pending_functions: Vec<FunctionId>,
+    functions: HashSet<FunctionId>,
+    types: HashSet<ConcreteTypeId>,
+    libfuncs: HashSet<ConcreteLibfuncId>,
+    statements: BTreeSet<usize>,
+}
+
+impl Reachability<'_> {
+    fn run(&mut self) -> Result<()> {
+        while let Some(function_id) = self.pending_functions.pop() {
+            if !self.functions.insert(function_id.clone()) {
+                continue;
+            }
+
+            let function = self.registry.get_function(&function_id)?;
+            for type_id in function
+                .signature
+                .param_types
+                .iter()
+                .chain(&function.signature.ret_types)
+            {
+                self.visit_type(type_id)?;
+            }
+
+            let mut pending_statements = vec![function.entry_point];
+            while let Some(statement_idx) = pending_statements.pop() {
+                if !self.statements.insert(statement_idx.0) {
+                    continue;
+                }
+
+                if let Statement::Invocation(invocation) = &self.program.statements[statement_idx.0]
+                {
+                    self.visit_libfunc(&invocation.libfunc_id)?;
+                    pending_statements.extend(
+                        invocation
+                            .branches
+                            .iter()
+                            .map(|branch| statement_idx.next(&branch.target)),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_type(&mut self, type_id: &ConcreteTypeId) -> Result<()> {
+        if !self.types.insert(type_id.clone()) {
+            return Ok(());
+        }
+
+        // Make sure the type exists.
+        self.registry.get_type(type_id)?;
+        let declaration = self.type_declarations[type_id];
+        self.visit_generic_args(&declaration.long_id.generic_args)
+    }
+
+    fn visit_libfunc(&mut self, libfunc_id: &ConcreteLibfuncId) -> Result<()> {
+        if !self.libfuncs.insert(libfunc_id.clone()) {
+            return Ok(());
+        }
+
+        // The libfunc's specialization may depend on types which don't appear in its generic
+        // arguments (for example, `felt252_is_zero` needs `NonZero<felt252>`), so the types in its
+        // signature have to be kept too.
+        let libfunc = self.registry.get_libfunc(libfunc_id)?;
+        for param in libfunc.param_signatures() {
+            self.visit_type(&param.ty)?;
+        }
+        for branch in libfunc.branch_signatures() {
+            for var in &branch.vars {
+                self.visit_type(&var.ty)?;
+            }
+        }
+
+        let declaration = self.libfunc_declarations[libfunc_id];
+        self.visit_generic_args(&declaration.long_id.generic_args)
+    }
+
+    fn visit_generic_args(&mut self, generic_args: &[GenericArg]) -> Result<()> {
+        for generic_arg in generic_args {
+            match generic_arg {
+                GenericArg::Type(type_id) => self.visit_type(type_id)?,
+                GenericArg::Libfunc(libfunc_id) => self.visit_libfunc(libfunc_id)?,
+                GenericArg::UserFunc(function_id) => {
+                    self.pending_functions.push(function_id.clone())
+                }
+                GenericArg::UserType(_) | GenericArg::Value(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext,
+        executor::JitNativeExecutor,
+        utils::{find_function_id, test::load_cairo},
+        values::Value,
+        OptLevel,
+    };
+    use starknet_types_core::felt::Felt;
+
+    #[test]
+    fn test_reachable_subprogram() {
+        let (module_name, program) = load_cairo! {
+            fn used(a: felt252) -> felt252 {
+                helper(a) + 1
+            }
+
+            fn helper(a: felt252) -> felt252 {
+                a * 2
+            }
+
+            fn unused(a: u128, b: u128) -> u128 {
+                a * b
+            }
+        };
+
+        let root = find_function_id(&program, &format!("{module_name}::{module_name}::used"))
+            .unwrap()
+            .clone();
+        let pruned = reachable_subprogram(&program, [&root]).unwrap();
+
+        assert!(pruned.funcs.iter().any(|x| x.id == root));
+        assert!(pruned.funcs.iter().all(|x| !x
+            .id
+            .debug_name
+            .as_deref()
+            .unwrap()
+            .ends_with("::unused")));
+        assert!(pruned.statements.len() < program.statements.len());
+        assert!(pruned.type_declarations.len() < program.type_declarations.len());
+
+        let module = NativeContext::new()
+            .compile_reachable(&program, [&root], false, Some(Default::default()))
+            .unwrap();
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::None).unwrap();
+        let result = executor
+            .invoke_dynamic(&root, &[Value::Felt252(Felt::from(20))], Some(u64::MAX))
+            .unwrap();
+        assert_eq!(result.return_value, Value::Felt252(Felt::from(41)));
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
     native_assert, native_panic,
     runtime::FeltDict,
     starknet::{Secp256k1Point, Secp256r1Point},
-    types::TypeBuilder,
+    types::{circuit, TypeBuilder},
     utils::{
         felt252_bigint, get_integer_layout, layout_repeat, libc_free, libc_malloc, RangeExt, PRIME,
     },
@@ -15,6 +15,7 @@ use crate::{
 use bumpalo::Bump;
 use cairo_lang_sierra::{
     extensions::{
+        circuit::CircuitTypeConcrete,
         core::{CoreLibfunc, CoreType, CoreTypeConcrete},
         starknet::{secp256::Secp256PointTypeConcrete, StarkNetTypeConcrete},
         utils::Range,
@@ -36,6 +37,10 @@ use std::{
     slice,
 };
 
+mod check;
+mod display;
+mod felts;
+
 /// A Value is a value that can be passed to either the JIT engine or a compiled program as an argument or received as a result.
 ///
 /// They map to the cairo/sierra types.
@@ -43,6 +48,9 @@ use std::{
 /// The debug_name field on some variants is `Some` when receiving a [`Value`] as a result.
 ///
 /// A Boxed value or a non-null Nullable value is returned with it's inner value.
+///
+/// The circuit values are structs of `u384`s, which are themselves structs of four 96-bit
+/// [`Value::BoundedInt`]s. Spans have no representation.
 #[derive(Clone, Educe, serde::Serialize, serde::Deserialize)]
 #[educe(Debug, Eq, PartialEq)]
 pub enum Value {
@@ -89,7 +97,7 @@ pub enum Value {
         x: Box<Value>,
         y: Box<Value>,
     },
-    /// Used as return value for Nullables that are null.
+    /// A null Nullable, also returned for uninitialized values.
     Null,
 }
 
@@ -175,6 +183,22 @@ impl Value {
     ) -> Result<NonNull<()>, Error> {
         let ty = registry.get_type(type_id)?;
 
+        // Constants are represented by their inner type.
+        if let CoreTypeConcrete::Const(info) = ty {
+            return self.to_ptr(arena, registry, &info.inner_ty, find_dict_overrides);
+        }
+
+        match Self::resolve_type(ty, registry)? {
+            CoreTypeConcrete::Circuit(info) => {
+                return self.circuit_to_ptr(arena, registry, type_id, info)
+            }
+            // Spans can't be lowered into MLIR, therefore there are no values for them.
+            CoreTypeConcrete::Span(_) => {
+                return Err(Error::UnsupportedValueType(type_id.to_string()))
+            }
+            _ => {}
+        }
+
         Ok(unsafe {
             match self {
                 Self::Felt252(value) => {
@@ -219,7 +243,12 @@ impl Value {
                     ptr
                 }
 
-                Self::Bytes31(_) => native_panic!("todo: allocate type Bytes31"),
+                Self::Bytes31(value) => {
+                    let ptr = arena.alloc_layout(get_integer_layout(248)).cast();
+                    ptr.cast::<[u8; 31]>().as_mut().copy_from_slice(value);
+
+                    ptr
+                }
                 Self::Array(data) => {
                     if let CoreTypeConcrete::Array(info) = Self::resolve_type(ty, registry)? {
                         let elem_ty = registry.get_type(&info.ty)?;
@@ -290,6 +319,36 @@ impl Value {
                         )))?
                     }
                 }
+                Self::Struct { fields, .. }
+                    if matches!(
+                        Self::resolve_type(ty, registry)?,
+                        CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Sha256StateHandle(_))
+                    ) =>
+                {
+                    let state = fields
+                        .iter()
+                        .map(|field| match field {
+                            Self::Uint32(value) => Ok(*value),
+                            _ => Err(Error::UnexpectedValue("u32".to_string())),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let state = <[u32; 8]>::try_from(state)
+                        .map_err(|_| Error::UnexpectedValue("[u32; 8]".to_string()))?;
+
+                    // The handle points to a heap allocated state, which the program frees.
+                    let state_ptr = libc_malloc(size_of::<[u32; 8]>()).cast::<[u32; 8]>();
+                    state_ptr.write(state);
+
+                    let ptr = arena.alloc_layout(Layout::new::<*mut ()>()).cast();
+                    *ptr.cast::<*mut [u32; 8]>().as_mut() = state_ptr;
+
+                    ptr
+                }
+                Self::Struct { fields, .. } if fields.is_empty() && ty.is_zst(registry)? => {
+                    // Other zero-sized types (like `Coupon` or `U128MulGuarantee`) are represented
+                    // as unit structs.
+                    arena.alloc_layout(Layout::new::<()>()).cast()
+                }
                 Self::Struct {
                     fields: members, ..
                 } => {
@@ -539,12 +598,36 @@ impl Value {
 
                     ptr
                 }
-                Self::Secp256K1Point { .. } => native_panic!("todo: allocate type Secp256K1Point"),
-                Self::Secp256R1Point { .. } => native_panic!("todo: allocate type Secp256R1Point"),
+                Self::Secp256K1Point(point) => {
+                    let ptr = arena.alloc_layout(Layout::new::<Secp256k1Point>()).cast();
+                    *ptr.cast::<Secp256k1Point>().as_mut() = *point;
+
+                    ptr
+                }
+                Self::Secp256R1Point(point) => {
+                    let ptr = arena.alloc_layout(Layout::new::<Secp256r1Point>()).cast();
+                    *ptr.cast::<Secp256r1Point>().as_mut() = *point;
+
+                    ptr
+                }
                 Self::Null => {
-                    native_panic!(
-                        "unimplemented: null is meant as return value for nullable for now"
-                    )
+                    if !matches!(
+                        Self::resolve_type(ty, registry)?,
+                        CoreTypeConcrete::Nullable(_) | CoreTypeConcrete::Uninitialized(_)
+                    ) {
+                        Err(Error::UnexpectedValue(format!(
+                            "expected value of type {:?} but got null",
+                            type_id.debug_name
+                        )))?
+                    }
+
+                    // A null nullable is a null pointer, and an uninitialized value has no
+                    // meaningful contents.
+                    let layout = ty.layout(registry)?;
+                    let ptr = arena.alloc_layout(layout);
+                    ptr.as_ptr().write_bytes(0, layout.size());
+
+                    ptr.cast()
                 }
                 Self::IntRange { x, y } => {
                     if let CoreTypeConcrete::IntRange(info) = Self::resolve_type(ty, registry)? {
@@ -711,8 +794,11 @@ impl Value {
                 CoreTypeConcrete::Uint32(_) => Self::Uint32(*ptr.cast::<u32>().as_ref()),
                 CoreTypeConcrete::Uint64(_) => Self::Uint64(*ptr.cast::<u64>().as_ref()),
                 CoreTypeConcrete::Uint128(_) => Self::Uint128(*ptr.cast::<u128>().as_ref()),
-                CoreTypeConcrete::Uint128MulGuarantee(_) => {
-                    native_panic!("todo: implement uint128mulguarantee from_ptr")
+                CoreTypeConcrete::Uint128MulGuarantee(_) | CoreTypeConcrete::Coupon(_) => {
+                    Self::Struct {
+                        fields: Vec::new(),
+                        debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+                    }
                 }
                 CoreTypeConcrete::Sint8(_) => Self::Sint8(*ptr.cast::<i8>().as_ref()),
                 CoreTypeConcrete::Sint16(_) => Self::Sint16(*ptr.cast::<i16>().as_ref()),
@@ -741,9 +827,8 @@ impl Value {
                         value
                     }
                 }
-                CoreTypeConcrete::Uninitialized(_) => {
-                    native_panic!("todo: implement uninit from_ptr or ignore the return value")
-                }
+                // There's nothing meaningful to read from an uninitialized value.
+                CoreTypeConcrete::Uninitialized(_) => Self::Null,
                 CoreTypeConcrete::Enum(info) => {
                     let tag_layout = crate::utils::get_integer_layout(match info.variants.len() {
                         0 | 1 => 0,
@@ -870,6 +955,7 @@ impl Value {
                 | CoreTypeConcrete::Bitwise(_)
                 | CoreTypeConcrete::BuiltinCosts(_)
                 | CoreTypeConcrete::RangeCheck(_)
+                | CoreTypeConcrete::RangeCheck96(_)
                 | CoreTypeConcrete::EcOp(_)
                 | CoreTypeConcrete::GasBuiltin(_)
                 | CoreTypeConcrete::SegmentArena(_) => {
@@ -901,10 +987,21 @@ impl Value {
                         }
                     },
                     StarkNetTypeConcrete::Sha256StateHandle(_) => {
-                        native_panic!("todo: implement Sha256StateHandle from_ptr")
+                        let state_ptr = *ptr.cast::<*mut [u32; 8]>().as_ref();
+                        let state = state_ptr.read();
+
+                        if should_drop {
+                            libc_free(state_ptr.cast());
+                        }
+
+                        Self::Struct {
+                            fields: state.into_iter().map(Self::Uint32).collect(),
+                            debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+                        }
                     }
                 },
-                CoreTypeConcrete::Span(_) => native_panic!("implement span from_ptr"),
+                // Spans can't be lowered into MLIR, therefore they never reach an entry point.
+                CoreTypeConcrete::Span(_) => Err(Error::UnsupportedValueType(type_id.to_string()))?,
                 CoreTypeConcrete::Snapshot(info) => {
                     Self::from_ptr(ptr, &info.ty, registry, should_drop)?
                 }
@@ -913,7 +1010,9 @@ impl Value {
                     Self::Bytes31(data)
                 }
 
-                CoreTypeConcrete::Const(_) => native_panic!("implement const from_ptr"),
+                CoreTypeConcrete::Const(info) => {
+                    Self::from_ptr(ptr, &info.inner_ty, registry, should_drop)?
+                }
                 CoreTypeConcrete::BoundedInt(info) => {
                     let mut data = BigInt::from_biguint(
                         Sign::Plus,
@@ -931,9 +1030,9 @@ impl Value {
                         range: info.range.clone(),
                     }
                 }
-                CoreTypeConcrete::Coupon(_)
-                | CoreTypeConcrete::Circuit(_)
-                | CoreTypeConcrete::RangeCheck96(_) => native_panic!("implement from_ptr"),
+                CoreTypeConcrete::Circuit(info) => {
+                    Self::circuit_from_ptr(ptr, type_id, info, registry)?
+                }
                 CoreTypeConcrete::IntRange(info) => {
                     let member = registry.get_type(&info.ty)?;
                     let member_layout = member.layout(registry)?;
@@ -966,6 +1065,229 @@ impl Value {
             }
         })
     }
+
+    /// Read a circuit value. Their 384-bit integers are read the way Cairo represents a `u384`: a
+    /// struct of four 96-bit limbs.
+    ///
+    /// The circuit's containers are read as structs:
+    ///   - `CircuitData` has every input of the circuit.
+    ///   - `CircuitInputAccumulator` has the inputs added so far.
+    ///   - `CircuitOutputs` has a struct with the circuit's values (its inputs and the gates'
+    ///     results), followed by the modulus.
+    ///
+    /// The compiled code doesn't keep anything in a `CircuitPartialOutputs`, therefore it's read
+    /// as an empty struct like the zero-sized circuit types.
+    unsafe fn circuit_from_ptr(
+        ptr: NonNull<()>,
+        type_id: &ConcreteTypeId,
+        info: &CircuitTypeConcrete,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+    ) -> Result<Self, Error> {
+        let u96_mask = (BigUint::one() << 96u32) - BigUint::one();
+        let u96 = |value: BigUint| Self::BoundedInt {
+            value: BigInt::from(value & &u96_mask).into(),
+            range: Range {
+                lower: 0.into(),
+                upper: BigInt::one() << 96u32,
+            },
+        };
+        let u384 = |ptr: *const u8| {
+            let value = BigUint::from_bytes_le(slice::from_raw_parts(ptr, 48));
+            Self::Struct {
+                fields: (0..4).map(|i| u96(&value >> (96 * i))).collect(),
+                debug_name: None,
+            }
+        };
+        // The outputs are stored as structs of 96-bit integers instead.
+        let u384_struct = |ptr: *const u8| Self::Struct {
+            fields: (0..4)
+                .map(|i| u96(ptr.byte_add(16 * i).cast::<u128>().read_unaligned().into()))
+                .collect(),
+            debug_name: None,
+        };
+        let u384_list = |ptr: *const u8, len: usize| Self::Struct {
+            fields: (0..len)
+                .map(|i| u384(ptr.byte_add(U384_STRIDE * i)))
+                .collect(),
+            debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+        };
+
+        let layout = registry.get_type(type_id)?.layout(registry)?;
+        Ok(match info {
+            CircuitTypeConcrete::U96Guarantee(_) => u96(ptr.cast::<u128>().read().into()),
+            CircuitTypeConcrete::CircuitModulus(_) => u384(ptr.cast().as_ptr()),
+            CircuitTypeConcrete::CircuitData(_) => {
+                u384_list(ptr.cast().as_ptr(), layout.size() / U384_STRIDE)
+            }
+            CircuitTypeConcrete::CircuitInputAccumulator(_) => {
+                let len = ptr.cast::<u64>().read() as usize;
+                u384_list(
+                    ptr.cast::<u8>()
+                        .as_ptr()
+                        .byte_add(accumulator_inputs_offset()?),
+                    len,
+                )
+            }
+            CircuitTypeConcrete::CircuitOutputs(_) => {
+                let len = layout.size() / U384_STRUCT_STRIDE - 1;
+                let ptr = ptr.cast::<u8>().as_ptr();
+
+                Self::Struct {
+                    fields: vec![
+                        Self::Struct {
+                            fields: (0..len)
+                                .map(|i| u384_struct(ptr.byte_add(U384_STRUCT_STRIDE * i)))
+                                .collect(),
+                            debug_name: None,
+                        },
+                        u384_struct(ptr.byte_add(U384_STRUCT_STRIDE * len)),
+                    ],
+                    debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+                }
+            }
+            CircuitTypeConcrete::AddMod(_) | CircuitTypeConcrete::MulMod(_) => {
+                native_panic!("handled before: {:?}", type_id)
+            }
+            CircuitTypeConcrete::CircuitPartialOutputs(_) => Self::Struct {
+                fields: Vec::new(),
+                debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+            },
+            info if circuit::is_zst(info) => Self::Struct {
+                fields: Vec::new(),
+                debug_name: type_id.debug_name.as_ref().map(|x| x.to_string()),
+            },
+            _ => native_panic!("unhandled circuit type: {:?}", type_id),
+        })
+    }
+
+    /// Allocate a circuit value, laid out the way [`Self::circuit_from_ptr`] reads it.
+    fn circuit_to_ptr(
+        &self,
+        arena: &Bump,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+        info: &CircuitTypeConcrete,
+    ) -> Result<NonNull<()>, Error> {
+        let unexpected = || {
+            Error::UnexpectedValue(format!(
+                "expected value of type {:?} but got {self:?}",
+                type_id.debug_name
+            ))
+        };
+        let u96 = |value: &Self| match value {
+            Self::BoundedInt { value, .. } => u128::try_from(value.to_biguint())
+                .ok()
+                .filter(|x| *x >> 96 == 0)
+                .ok_or_else(unexpected),
+            _ => Err(unexpected()),
+        };
+        let limbs = |value: &Self| match value {
+            Self::Struct { fields, .. } if fields.len() == 4 => {
+                let mut limbs = [0u128; 4];
+                for (limb, field) in limbs.iter_mut().zip(fields) {
+                    *limb = u96(field)?;
+                }
+                Ok(limbs)
+            }
+            _ => Err(unexpected()),
+        };
+        let u384_list = |value: &Self| match value {
+            Self::Struct { fields, .. } => fields.iter().map(limbs).collect::<Result<Vec<_>, _>>(),
+            _ => Err(unexpected()),
+        };
+        let write_u384 = |ptr: *mut u8, limbs: [u128; 4]| {
+            let mut value = BigUint::ZERO;
+            for limb in limbs.into_iter().rev() {
+                value = (value << 96u32) + limb;
+            }
+
+            let mut data = value.to_bytes_le();
+            data.resize(48, 0);
+            unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len()) };
+        };
+        let write_u384_struct = |ptr: *mut u8, limbs: [u128; 4]| {
+            for (i, limb) in limbs.into_iter().enumerate() {
+                unsafe { ptr.byte_add(16 * i).cast::<u128>().write_unaligned(limb) };
+            }
+        };
+
+        let layout = registry.get_type(type_id)?.layout(registry)?;
+        let ptr = arena.alloc_layout(layout);
+        unsafe { ptr.as_ptr().write_bytes(0, layout.size()) };
+        let data = ptr.as_ptr();
+
+        match info {
+            CircuitTypeConcrete::U96Guarantee(_) => unsafe {
+                data.cast::<u128>().write(u96(self)?)
+            },
+            CircuitTypeConcrete::CircuitModulus(_) => write_u384(data, limbs(self)?),
+            CircuitTypeConcrete::CircuitData(_) => {
+                let inputs = u384_list(self)?;
+                if inputs.len() != layout.size() / U384_STRIDE {
+                    return Err(unexpected());
+                }
+
+                for (i, input) in inputs.into_iter().enumerate() {
+                    write_u384(unsafe { data.byte_add(U384_STRIDE * i) }, input);
+                }
+            }
+            CircuitTypeConcrete::CircuitInputAccumulator(_) => {
+                // The accumulator holds all the inputs but the last one, after its length.
+                let offset = accumulator_inputs_offset()?;
+                let inputs = u384_list(self)?;
+                if inputs.len() > (layout.size() - offset) / U384_STRIDE {
+                    return Err(unexpected());
+                }
+
+                unsafe { data.cast::<u64>().write(inputs.len() as u64) };
+                for (i, input) in inputs.into_iter().enumerate() {
+                    write_u384(unsafe { data.byte_add(offset + U384_STRIDE * i) }, input);
+                }
+            }
+            CircuitTypeConcrete::CircuitOutputs(_) => {
+                let Self::Struct { fields, .. } = self else {
+                    return Err(unexpected());
+                };
+                let [gates, modulus] = fields.as_slice() else {
+                    return Err(unexpected());
+                };
+
+                let gates = u384_list(gates)?;
+                if gates.len() != layout.size() / U384_STRUCT_STRIDE - 1 {
+                    return Err(unexpected());
+                }
+
+                for (i, gate) in gates.iter().enumerate() {
+                    write_u384_struct(unsafe { data.byte_add(U384_STRUCT_STRIDE * i) }, *gate);
+                }
+                write_u384_struct(
+                    unsafe { data.byte_add(U384_STRUCT_STRIDE * gates.len()) },
+                    limbs(modulus)?,
+                );
+            }
+            CircuitTypeConcrete::AddMod(_) | CircuitTypeConcrete::MulMod(_) => {
+                return Err(Error::UnsupportedValueType(type_id.to_string()))
+            }
+            _ => match self {
+                // Partial outputs and the zero-sized circuit types have no contents.
+                Self::Struct { fields, .. } if fields.is_empty() => {}
+                _ => return Err(unexpected()),
+            },
+        }
+
+        Ok(ptr.cast())
+    }
+}
+
+/// The distance between consecutive 384-bit integers in the circuit values.
+const U384_STRIDE: usize = 48;
+/// The distance between consecutive `u384` structs (four 96-bit integers) in the circuit values.
+const U384_STRUCT_STRIDE: usize = 64;
+
+/// Return the offset of the inputs within a `CircuitInputAccumulator`, which start after their
+/// count.
+fn accumulator_inputs_offset() -> Result<usize, Error> {
+    Ok(get_integer_layout(64).extend(get_integer_layout(384))?.1)
 }
 
 #[cfg(test)]
@@ -1835,6 +2157,74 @@ mod test {
             _ => panic!("Unexpected error type: {:?}", result),
         }
     }
+
+    #[test]
+    fn test_roundtrip_const() {
+        // Constants can't be parameters, but they're represented by their inner type.
+        let program = ProgramParser::new()
+            .parse(
+                "type u32 = u32;
+                type ConstU32 = Const<u32, 5>;",
+            )
+            .unwrap();
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap();
+        let type_id = &program.type_declarations[1].id;
+
+        let ptr = Value::Uint32(5)
+            .to_ptr(&Bump::new(), &registry, type_id, |_| todo!())
+            .unwrap();
+        assert_eq!(
+            Value::from_ptr(ptr, type_id, &registry, true).unwrap(),
+            Value::Uint32(5)
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_uninitialized() {
+        // Uninitialized values can't be parameters, and they have no meaningful contents.
+        let program = ProgramParser::new()
+            .parse(
+                "type felt252 = felt252;
+                type UninitializedFelt252 = Uninitialized<felt252>;",
+            )
+            .unwrap();
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap();
+        let type_id = &program.type_declarations[1].id;
+
+        let ptr = Value::Null
+            .to_ptr(&Bump::new(), &registry, type_id, |_| todo!())
+            .unwrap();
+        assert_eq!(
+            Value::from_ptr(ptr, type_id, &registry, true).unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_span_unsupported() {
+        let program = ProgramParser::new()
+            .parse(
+                "type felt252 = felt252;
+                type SpanFelt252 = Span<felt252>;",
+            )
+            .unwrap();
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program).unwrap();
+        let type_id = &program.type_declarations[1].id;
+
+        let value = Value::Array(vec![Value::Felt252(1.into())]);
+        assert!(matches!(
+            value.check(&registry, type_id),
+            Err(Error::UnsupportedValueType(_))
+        ));
+        assert!(matches!(
+            value.to_ptr(&Bump::new(), &registry, type_id, |_| todo!()),
+            Err(Error::UnsupportedValueType(_))
+        ));
+        assert!(matches!(
+            Value::from_ptr(NonNull::dangling(), type_id, &registry, true),
+            Err(Error::UnsupportedValueType(_))
+        ));
+    }
 }
 
 mod range_serde {
@@ -0,0 +1,390 @@
+//! # Value type-checking
+//!
+//! Validation of a [`Value`] against the Sierra type it's going to be used as, before anything is
+//! allocated for it. Mismatches are reported with their path within the value, the expected type
+//! and the type of what was found instead.
+//!
+//! Sierra doesn't keep the names of the structs' members nor of the enums' variants, therefore
+//! they're identified by their index, qualified by the name of their type when the program has
+//! debug names.
+
+use super::{felts::felt_to_signed, Value};
+use crate::{
+    error::{Error, Result, ValueMismatchError},
+    types::TypeBuilder,
+};
+use cairo_lang_sierra::{
+    extensions::{
+        circuit::CircuitTypeConcrete,
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::{secp256::Secp256PointTypeConcrete, StarkNetTypeConcrete},
+    },
+    ids::ConcreteTypeId,
+    program_registry::ProgramRegistry,
+};
+
+impl Value {
+    /// Check that the value can be used as the given type.
+    ///
+    /// On mismatch it returns an [`Error::ValueMismatch`], whose path is relative to this value.
+    pub fn check(
+        &self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<()> {
+        self.check_at(registry, type_id, &mut String::new())
+    }
+
+    fn check_at(
+        &self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+        path: &mut String,
+    ) -> Result<()> {
+        match (self, registry.get_type(type_id)?) {
+            (
+                value,
+                CoreTypeConcrete::Box(info)
+                | CoreTypeConcrete::NonZero(info)
+                | CoreTypeConcrete::Snapshot(info),
+            ) => value.check_at(registry, &info.ty, path)?,
+            (value, CoreTypeConcrete::Const(info)) => {
+                value.check_at(registry, &info.inner_ty, path)?
+            }
+            // Spans can't be lowered into MLIR, therefore there are no values for them.
+            (_, CoreTypeConcrete::Span(_)) => {
+                return Err(Error::UnsupportedValueType(type_id.to_string()))
+            }
+            (Self::Null, CoreTypeConcrete::Nullable(_) | CoreTypeConcrete::Uninitialized(_)) => {}
+            (value, CoreTypeConcrete::Nullable(info)) => {
+                value.check_at(registry, &info.ty, path)?
+            }
+
+            (
+                Self::Felt252(_),
+                CoreTypeConcrete::Felt252(_)
+                | CoreTypeConcrete::StarkNet(
+                    StarkNetTypeConcrete::ClassHash(_)
+                    | StarkNetTypeConcrete::ContractAddress(_)
+                    | StarkNetTypeConcrete::StorageAddress(_)
+                    | StarkNetTypeConcrete::StorageBaseAddress(_),
+                ),
+            )
+            | (Self::Uint8(_), CoreTypeConcrete::Uint8(_))
+            | (Self::Uint16(_), CoreTypeConcrete::Uint16(_))
+            | (Self::Uint32(_), CoreTypeConcrete::Uint32(_))
+            | (Self::Uint64(_), CoreTypeConcrete::Uint64(_))
+            | (Self::Uint128(_), CoreTypeConcrete::Uint128(_))
+            | (Self::Sint8(_), CoreTypeConcrete::Sint8(_))
+            | (Self::Sint16(_), CoreTypeConcrete::Sint16(_))
+            | (Self::Sint32(_), CoreTypeConcrete::Sint32(_))
+            | (Self::Sint64(_), CoreTypeConcrete::Sint64(_))
+            | (Self::Sint128(_), CoreTypeConcrete::Sint128(_))
+            | (Self::Bytes31(_), CoreTypeConcrete::Bytes31(_))
+            | (Self::EcPoint(..), CoreTypeConcrete::EcPoint(_))
+            | (Self::EcState(..), CoreTypeConcrete::EcState(_))
+            | (
+                Self::Secp256K1Point(_),
+                CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Secp256(
+                    Secp256PointTypeConcrete::K1(_),
+                )),
+            )
+            | (
+                Self::Secp256R1Point(_),
+                CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Secp256(
+                    Secp256PointTypeConcrete::R1(_),
+                )),
+            ) => {}
+            (Self::BoundedInt { value, .. }, CoreTypeConcrete::BoundedInt(info))
+                if info.range.lower <= felt_to_signed(*value)
+                    && felt_to_signed(*value) < info.range.upper => {}
+
+            (Self::Array(items), CoreTypeConcrete::Array(info)) => {
+                for (i, item) in items.iter().enumerate() {
+                    item.check_nested(registry, &info.ty, path, &format!("[{i}]"))?;
+                }
+            }
+            (Self::Struct { fields, .. }, CoreTypeConcrete::Struct(info))
+                if fields.len() == info.members.len() =>
+            {
+                let prefix = type_name(type_id)
+                    .map(|x| format!(".{x}"))
+                    .unwrap_or_default();
+                for (i, (field, member)) in fields.iter().zip(&info.members).enumerate() {
+                    field.check_nested(registry, member, path, &format!("{prefix}.{i}"))?;
+                }
+            }
+            (Self::Enum { tag, value, .. }, CoreTypeConcrete::Enum(info))
+                if *tag < info.variants.len() =>
+            {
+                let prefix = type_name(type_id)
+                    .map(|x| format!(".{x}"))
+                    .unwrap_or_default();
+                value.check_nested(
+                    registry,
+                    &info.variants[*tag],
+                    path,
+                    &format!("{prefix}#{tag}"),
+                )?;
+            }
+            (Self::Felt252Dict { value, .. }, CoreTypeConcrete::Felt252Dict(info)) => {
+                for (key, value) in value {
+                    value.check_nested(registry, &info.ty, path, &format!("[{key}]"))?;
+                }
+            }
+            (Self::IntRange { x, y }, CoreTypeConcrete::IntRange(info)) => {
+                x.check_nested(registry, &info.ty, path, ".0")?;
+                y.check_nested(registry, &info.ty, path, ".1")?;
+            }
+            (
+                Self::Struct { fields, .. },
+                CoreTypeConcrete::StarkNet(StarkNetTypeConcrete::Sha256StateHandle(_)),
+            ) if fields.len() == 8 && fields.iter().all(|x| matches!(x, Self::Uint32(_))) => {}
+            (value, CoreTypeConcrete::Circuit(CircuitTypeConcrete::U96Guarantee(_)))
+                if value.is_u96() => {}
+            (value, CoreTypeConcrete::Circuit(CircuitTypeConcrete::CircuitModulus(_)))
+                if value.is_u384() => {}
+            (
+                Self::Struct { fields, .. },
+                CoreTypeConcrete::Circuit(
+                    CircuitTypeConcrete::CircuitData(_)
+                    | CircuitTypeConcrete::CircuitInputAccumulator(_),
+                ),
+            ) if fields.iter().all(Self::is_u384) => {}
+            (
+                Self::Struct { fields, .. },
+                CoreTypeConcrete::Circuit(CircuitTypeConcrete::CircuitOutputs(_)),
+            ) if matches!(
+                fields.as_slice(),
+                [Self::Struct { fields: gates, .. }, modulus]
+                    if gates.iter().all(Self::is_u384) && modulus.is_u384()
+            ) => {}
+            (Self::Struct { fields, .. }, type_info)
+                if fields.is_empty() && type_info.is_zst(registry)? => {}
+            (
+                Self::Struct { fields, .. },
+                CoreTypeConcrete::Circuit(CircuitTypeConcrete::CircuitPartialOutputs(_)),
+            ) if fields.is_empty() => {}
+
+            _ => {
+                return Err(ValueMismatchError {
+                    path: path.clone(),
+                    expected: type_id.to_string(),
+                    found: self.describe(),
+                }
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a value nested within this one, appending its location to the path while it's being
+    /// checked.
+    fn check_nested(
+        &self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+        path: &mut String,
+        location: &str,
+    ) -> Result<()> {
+        let len = path.len();
+        path.push_str(location);
+        self.check_at(registry, type_id, path)?;
+        path.truncate(len);
+
+        Ok(())
+    }
+
+    /// Whether the value is a 96-bit circuit limb.
+    fn is_u96(&self) -> bool {
+        matches!(self, Self::BoundedInt { value, .. } if value.bits() <= 96)
+    }
+
+    /// Whether the value is a 384-bit circuit integer, which are structs of four limbs.
+    fn is_u384(&self) -> bool {
+        matches!(
+            self,
+            Self::Struct { fields, .. } if fields.len() == 4 && fields.iter().all(Self::is_u96)
+        )
+    }
+
+    /// Describe the value for the diagnostics, without its contents.
+    ///
+    /// Values which can only be of a single Sierra type are described by its name.
+    fn describe(&self) -> String {
+        let of_type = |name: &str| format!("a value of type '{name}'");
+        match self {
+            Self::Felt252(_) => of_type("felt252"),
+            Self::Bytes31(_) => of_type("bytes31"),
+            Self::Uint8(_) => of_type("u8"),
+            Self::Uint16(_) => of_type("u16"),
+            Self::Uint32(_) => of_type("u32"),
+            Self::Uint64(_) => of_type("u64"),
+            Self::Uint128(_) => of_type("u128"),
+            Self::Sint8(_) => of_type("i8"),
+            Self::Sint16(_) => of_type("i16"),
+            Self::Sint32(_) => of_type("i32"),
+            Self::Sint64(_) => of_type("i64"),
+            Self::Sint128(_) => of_type("i128"),
+            Self::EcPoint(..) => of_type("EcPoint"),
+            Self::EcState(..) => of_type("EcState"),
+            Self::Secp256K1Point(_) => of_type("Secp256K1Point"),
+            Self::Secp256R1Point(_) => of_type("Secp256R1Point"),
+            Self::BoundedInt { range, .. } => of_type(&format!(
+                "BoundedInt<{}, {}>",
+                range.lower,
+                &range.upper - 1
+            )),
+            Self::Struct {
+                debug_name: Some(name),
+                ..
+            }
+            | Self::Enum {
+                debug_name: Some(name),
+                ..
+            }
+            | Self::Felt252Dict {
+                debug_name: Some(name),
+                ..
+            } => of_type(name),
+            Self::Struct { fields, .. } => format!("a struct with {} fields", fields.len()),
+            Self::Enum { tag, .. } => format!("an enum with variant {tag}"),
+            Self::Felt252Dict { .. } => "a felt252 dictionary".to_string(),
+            Self::Array(_) => "an array".to_string(),
+            Self::IntRange { .. } => "an int range".to_string(),
+            Self::Null => "null".to_string(),
+        }
+    }
+}
+
+/// The short name of a type from its debug name (ex. `Option` for
+/// `core::option::Option::<core::felt252>`), if it has any.
+fn type_name(type_id: &ConcreteTypeId) -> Option<&str> {
+    let debug_name = type_id.debug_name.as_deref()?;
+    // Tuples (and other unnamed types) aren't paths.
+    if debug_name.starts_with('(') {
+        return None;
+    }
+
+    let path = debug_name.split('<').next()?.trim_end_matches("::");
+    path.rsplit("::").next().filter(|x| !x.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        context::NativeContext,
+        executor::JitNativeExecutor,
+        utils::test::{find_test_function, jit_enum, jit_struct, load_cairo},
+        OptLevel,
+    };
+
+    fn mismatch(result: Result<()>) -> ValueMismatchError {
+        match result {
+            Err(Error::ValueMismatch(error)) => error,
+            x => panic!("expected a value mismatch, got {x:?}"),
+        }
+    }
+
+    #[test]
+    fn check_values() {
+        let program = load_cairo! {
+            fn run_test(
+                _a: u256, _b: Option<(u8, Array<u32>)>, _c: Nullable<felt252>, _d: NonZero<u64>,
+            ) {}
+        };
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program.1).unwrap();
+        let param_types = &find_test_function(&program, "run_test")
+            .signature
+            .param_types;
+
+        let u256 = jit_struct!(Value::Uint128(1), Value::Uint128(2));
+        let option = jit_enum!(0, jit_struct!(Value::Uint8(3), Value::from(vec![4u32, 5])));
+        u256.check(&registry, &param_types[0]).unwrap();
+        option.check(&registry, &param_types[1]).unwrap();
+        Value::Null.check(&registry, &param_types[2]).unwrap();
+        Value::Felt252(6.into())
+            .check(&registry, &param_types[2])
+            .unwrap();
+        Value::Uint64(7).check(&registry, &param_types[3]).unwrap();
+
+        let error = mismatch(jit_struct!(Value::Uint128(1)).check(&registry, &param_types[0]));
+        assert_eq!(error.path, "");
+        assert_eq!(error.expected, "core::integer::u256");
+        assert_eq!(error.found, "a struct with 1 fields");
+
+        let error = mismatch(
+            jit_enum!(
+                0,
+                jit_struct!(
+                    Value::Uint8(3),
+                    Value::Array(vec![Value::Uint32(4), Value::Felt252(5.into())])
+                )
+            )
+            .check(&registry, &param_types[1]),
+        );
+        assert_eq!(error.path, ".Option#0.1[1]");
+        assert_eq!(error.expected, "u32");
+        assert_eq!(error.found, "a value of type 'felt252'");
+
+        let error = mismatch(jit_enum!(2, jit_struct!()).check(&registry, &param_types[1]));
+        assert_eq!(error.path, "");
+        assert_eq!(error.found, "an enum with variant 2");
+
+        let error = mismatch(Value::Null.check(&registry, &param_types[3]));
+        assert_eq!(error.expected, "u64");
+        assert_eq!(error.found, "null");
+    }
+
+    #[test]
+    fn invoke_with_invalid_argument() {
+        let program = load_cairo! {
+            fn run_test(a: felt252, b: Array<u8>) -> felt252 {
+                a + b.len().into()
+            }
+        };
+        let function_id = &find_test_function(&program, "run_test").id;
+
+        let module = NativeContext::new()
+            .compile(&program.1, false, Some(Default::default()))
+            .unwrap();
+        let executor = JitNativeExecutor::from_native_module(module, OptLevel::None).unwrap();
+
+        let error = executor
+            .invoke_dynamic(
+                function_id,
+                &[
+                    Value::Felt252(1.into()),
+                    Value::from(vec![Value::Uint8(2), Value::Uint32(3)]),
+                ],
+                Some(u64::MAX),
+            )
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "argument 1[1]: expected a value of type 'u8', found a value of type 'u32'"
+        );
+
+        assert!(matches!(
+            executor.invoke_dynamic(function_id, &[Value::Felt252(1.into())], Some(u64::MAX)),
+            Err(Error::MissingParameter(_))
+        ));
+        assert!(matches!(
+            executor.invoke_dynamic(
+                function_id,
+                &[
+                    Value::Felt252(1.into()),
+                    Value::from(Vec::<u8>::new()),
+                    Value::Felt252(2.into()),
+                ],
+                Some(u64::MAX),
+            ),
+            Err(Error::TooManyArguments {
+                expected: 2,
+                found: 3
+            })
+        ));
+    }
+}
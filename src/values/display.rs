@@ -0,0 +1,265 @@
+//! # Value formatting
+//!
+//! Formats [`Value`]s the way Cairo's `Debug` trait would, using the debug names of the returned
+//! values to recognize the corelib types:
+//!   - `u256` is a single number and `ByteArray` a quoted string.
+//!   - `bool`, `Option` and `Result` (including `PanicResult`) use their variants' names.
+//!   - Tuples and fixed-size arrays are printed as such.
+//!
+//! Sierra doesn't keep the names of the members and variants of user types, therefore they're
+//! printed as their index: `Point { 0: 1, 1: 2 }` or `Direction#1(())`. Values without a debug
+//! name (as when built by hand) are printed as tuples and `#1(())` respectively.
+
+use super::{felts::felt_to_signed, Value};
+use crate::starknet::U256;
+use num_bigint::BigUint;
+use starknet_types_core::felt::Felt;
+use std::fmt::{self, Display, Formatter};
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Felt252(value) => write!(f, "{value}"),
+            Self::Bytes31(value) => write!(f, "{}", Felt::from_bytes_le_slice(value)),
+            Self::Uint8(value) => write!(f, "{value}"),
+            Self::Uint16(value) => write!(f, "{value}"),
+            Self::Uint32(value) => write!(f, "{value}"),
+            Self::Uint64(value) => write!(f, "{value}"),
+            Self::Uint128(value) => write!(f, "{value}"),
+            Self::Sint8(value) => write!(f, "{value}"),
+            Self::Sint16(value) => write!(f, "{value}"),
+            Self::Sint32(value) => write!(f, "{value}"),
+            Self::Sint64(value) => write!(f, "{value}"),
+            Self::Sint128(value) => write!(f, "{value}"),
+            Self::BoundedInt { value, .. } => write!(f, "{}", felt_to_signed(*value)),
+            Self::Array(items) => write_list(f, "[", items, "]"),
+            Self::Struct { fields, debug_name } => fmt_struct(f, fields, debug_name.as_deref()),
+            Self::Enum {
+                tag,
+                value,
+                debug_name,
+            } => fmt_enum(f, *tag, value, debug_name.as_deref()),
+            Self::Felt252Dict { value, .. } => {
+                let mut entries = value.iter().collect::<Vec<_>>();
+                entries.sort_by_key(|(key, _)| *key);
+
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.into_iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+            Self::EcPoint(x, y) => write!(f, "EcPoint({x}, {y})"),
+            Self::EcState(x, y, x0, y0) => write!(f, "EcState({x}, {y}, {x0}, {y0})"),
+            Self::Secp256K1Point(point) => write!(
+                f,
+                "Secp256k1Point {{ x: {}, y: {}, is_infinity: {} }}",
+                u256_to_biguint(&point.x),
+                u256_to_biguint(&point.y),
+                point.is_infinity,
+            ),
+            Self::Secp256R1Point(point) => write!(
+                f,
+                "Secp256r1Point {{ x: {}, y: {}, is_infinity: {} }}",
+                u256_to_biguint(&point.x),
+                u256_to_biguint(&point.y),
+                point.is_infinity,
+            ),
+            Self::IntRange { x, y } => write!(f, "{x}..{y}"),
+            Self::Null => write!(f, "null"),
+        }
+    }
+}
+
+fn fmt_struct(f: &mut Formatter<'_>, fields: &[Value], debug_name: Option<&str>) -> fmt::Result {
+    let Some(debug_name) = debug_name else {
+        return write_tuple(f, fields);
+    };
+
+    match (type_path(debug_name), fields) {
+        ("core::integer::u256", [Value::Uint128(low), Value::Uint128(high)]) => {
+            write!(
+                f,
+                "{}",
+                u256_to_biguint(&U256 {
+                    lo: *low,
+                    hi: *high
+                })
+            )
+        }
+        ("core::byte_array::ByteArray", [data, pending_word, pending_word_len]) => {
+            match decode_byte_array(data, pending_word, pending_word_len) {
+                Some(value) => write!(f, "{value:?}"),
+                None => write_members(f, "ByteArray", fields),
+            }
+        }
+        ("Tuple", _) => write_tuple(f, fields),
+        (path, _) if path.starts_with('[') => write_list(f, "[", fields, "]"),
+        (path, _) => write_members(f, type_name(path), fields),
+    }
+}
+
+fn fmt_enum(
+    f: &mut Formatter<'_>,
+    tag: usize,
+    value: &Value,
+    debug_name: Option<&str>,
+) -> fmt::Result {
+    let path = debug_name.map(type_path);
+    let variant = match (path, tag) {
+        (Some("core::bool"), 0) => return write!(f, "false"),
+        (Some("core::bool"), 1) => return write!(f, "true"),
+        (Some("core::option::Option"), 0) => "Some",
+        (Some("core::option::Option"), 1) => return write!(f, "None"),
+        (Some("core::result::Result" | "core::panics::PanicResult"), 0) => "Ok",
+        (Some("core::result::Result" | "core::panics::PanicResult"), 1) => "Err",
+        (Some(path), _) => return write!(f, "{}#{tag}({value})", type_name(path)),
+        (None, _) => return write!(f, "#{tag}({value})"),
+    };
+
+    write!(f, "{variant}({value})")
+}
+
+/// Return the path of the type a debug name refers to, without its generic arguments nor the
+/// snapshot and Sierra wrappers (`@` and `Struct<ut@...>`).
+fn type_path(debug_name: &str) -> &str {
+    let path = debug_name.trim_start_matches('@');
+    let path = ["Struct<ut@", "Enum<ut@"]
+        .into_iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .unwrap_or(path);
+
+    if path.starts_with('[') {
+        return path;
+    }
+
+    let end = path.find(['<', ',', '>']).unwrap_or(path.len());
+    path[..end].trim_end_matches("::")
+}
+
+/// Return the last segment of a type's path.
+fn type_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+fn write_list(f: &mut Formatter<'_>, open: &str, items: &[Value], close: &str) -> fmt::Result {
+    write!(f, "{open}")?;
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    write!(f, "{close}")
+}
+
+fn write_tuple(f: &mut Formatter<'_>, fields: &[Value]) -> fmt::Result {
+    match fields {
+        [field] => write!(f, "({field},)"),
+        _ => write_list(f, "(", fields, ")"),
+    }
+}
+
+fn write_members(f: &mut Formatter<'_>, name: &str, fields: &[Value]) -> fmt::Result {
+    if fields.is_empty() {
+        return write!(f, "{name}");
+    }
+
+    write!(f, "{name} {{ ")?;
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{i}: {field}")?;
+    }
+    write!(f, " }}")
+}
+
+fn u256_to_biguint(value: &U256) -> BigUint {
+    (BigUint::from(value.hi) << 128u32) + value.lo
+}
+
+/// Decode the contents of a `ByteArray`, unless its members are malformed.
+fn decode_byte_array(
+    data: &Value,
+    pending_word: &Value,
+    pending_word_len: &Value,
+) -> Option<String> {
+    let (Value::Array(data), Value::Felt252(pending_word), Value::Uint32(pending_word_len)) =
+        (data, pending_word, pending_word_len)
+    else {
+        return None;
+    };
+    let pending_word_len = (*pending_word_len as usize).min(31);
+
+    let mut bytes = Vec::new();
+    for word in data {
+        let Value::Bytes31(word) = word else {
+            return None;
+        };
+
+        // Words are big-endian, while `bytes31` values are stored in little-endian.
+        bytes.extend(word.iter().rev());
+    }
+    bytes.extend_from_slice(&pending_word.to_bytes_be()[32 - pending_word_len..]);
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{jit_enum, jit_struct, load_cairo, run_program};
+
+    #[test]
+    fn display_values() {
+        assert_eq!(Value::Felt252(5.into()).to_string(), "5");
+        assert_eq!(Value::Sint8(-3).to_string(), "-3");
+        assert_eq!(Value::from(vec![1u8, 2]).to_string(), "[1, 2]");
+        assert_eq!(jit_struct!(Value::Uint8(1)).to_string(), "(1,)");
+        assert_eq!(jit_struct!().to_string(), "()");
+        assert_eq!(jit_enum!(1, jit_struct!()).to_string(), "#1(())");
+        assert_eq!(Value::Null.to_string(), "null");
+    }
+
+    #[test]
+    fn display_returned_values() {
+        let program = load_cairo! {
+            #[derive(Drop)]
+            struct Point {
+                x: u8,
+                y: Option<u256>,
+            }
+
+            #[derive(Drop)]
+            enum Shape {
+                Circle: u8,
+                Square: (bool, bool),
+            }
+
+            fn run_test(x: u8) -> (Point, Shape, ByteArray, Result<u8, felt252>, [u16; 2]) {
+                (
+                    Point { x: x + 1, y: Option::Some(0x100000000000000000000000000000001) },
+                    Shape::Square((true, false)),
+                    "this string is longer than a single word",
+                    Result::Err(7),
+                    [3, 4],
+                )
+            }
+        };
+
+        // The function can panic, so the tuple is wrapped in a `PanicResult`.
+        let result = run_program(&program, "run_test", &[Value::Uint8(0)]);
+        assert_eq!(
+            result.return_value.to_string(),
+            format!(
+                "Ok(((Point {{ 0: 1, 1: Some({}) }}, Shape#1((true, false)), \
+                \"this string is longer than a single word\", Err(7), [3, 4]),))",
+                (BigUint::from(1u8) << 128u32) + 1u8,
+            ),
+        );
+    }
+}
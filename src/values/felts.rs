@@ -0,0 +1,416 @@
+//! # Felt serialization
+//!
+//! Conversions between [`Value`]s and their serialization as a list of felts, following the
+//! conventions of Cairo's `Serde` trait:
+//!   - Integers, felt-like types and `bytes31` are a single felt.
+//!   - Structs (including tuples, fixed-size arrays, `u256` and `ByteArray`) are their members in
+//!     order.
+//!   - Enums (including `bool`, `Option` and `Result`) are the variant index followed by the
+//!     payload.
+//!   - Arrays are their length followed by their elements.
+//!
+//! It's the format used by contract calldata, therefore any function can be called with the same
+//! arguments a contract entry point would receive.
+
+use super::Value;
+use crate::{
+    error::{Error, Result},
+    types::TypeBuilder,
+    utils::{HALF_PRIME, PRIME},
+};
+use cairo_lang_sierra::{
+    extensions::{
+        core::{CoreLibfunc, CoreType, CoreTypeConcrete},
+        starknet::StarkNetTypeConcrete,
+    },
+    ids::{ConcreteTypeId, FunctionId},
+    program_registry::ProgramRegistry,
+};
+use num_bigint::BigInt;
+use starknet_types_core::felt::Felt;
+
+impl Value {
+    /// Deserialize a value of the given type, consuming its felts from the iterator.
+    pub fn from_felts(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+        felts: &mut impl Iterator<Item = Felt>,
+    ) -> Result<Self> {
+        let mut next_felt = || {
+            felts
+                .next()
+                .ok_or_else(|| Error::make_missing_parameter(type_id))
+        };
+        let debug_name = || type_id.debug_name.as_ref().map(|x| x.to_string());
+
+        Ok(match registry.get_type(type_id)? {
+            CoreTypeConcrete::Felt252(_)
+            | CoreTypeConcrete::StarkNet(
+                StarkNetTypeConcrete::ClassHash(_)
+                | StarkNetTypeConcrete::ContractAddress(_)
+                | StarkNetTypeConcrete::StorageAddress(_)
+                | StarkNetTypeConcrete::StorageBaseAddress(_),
+            ) => Self::Felt252(next_felt()?),
+            CoreTypeConcrete::Uint8(_) => Self::Uint8(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Uint16(_) => Self::Uint16(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Uint32(_) => Self::Uint32(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Uint64(_) => Self::Uint64(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Uint128(_) => Self::Uint128(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Sint8(_) => Self::Sint8(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Sint16(_) => Self::Sint16(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Sint32(_) => Self::Sint32(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Sint64(_) => Self::Sint64(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Sint128(_) => Self::Sint128(felt_to_int(next_felt()?, type_id)?),
+            CoreTypeConcrete::Bytes31(_) => {
+                let bytes = next_felt()?.to_bytes_le();
+                if bytes[31] != 0 {
+                    return Err(Error::UnexpectedValue(type_id.to_string()));
+                }
+
+                Self::Bytes31(
+                    bytes[..31]
+                        .try_into()
+                        .map_err(|_| Error::IntegerConversion)?,
+                )
+            }
+            CoreTypeConcrete::BoundedInt(info) => {
+                let value = next_felt()?;
+                let signed_value = felt_to_signed(value);
+                if signed_value < info.range.lower || signed_value >= info.range.upper {
+                    return Err(Error::UnexpectedValue(type_id.to_string()));
+                }
+
+                Self::BoundedInt {
+                    value,
+                    range: info.range.clone(),
+                }
+            }
+            CoreTypeConcrete::Array(info) => {
+                let len: u32 = felt_to_int(next_felt()?, type_id)?;
+
+                Self::Array(
+                    (0..len)
+                        .map(|_| Self::from_felts(registry, &info.ty, felts))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            CoreTypeConcrete::Struct(info) => Self::Struct {
+                fields: info
+                    .members
+                    .iter()
+                    .map(|member| Self::from_felts(registry, member, felts))
+                    .collect::<Result<_>>()?,
+                debug_name: debug_name(),
+            },
+            CoreTypeConcrete::Enum(info) => {
+                let tag: usize = felt_to_int(next_felt()?, type_id)?;
+                let variant = info
+                    .variants
+                    .get(tag)
+                    .ok_or_else(|| Error::UnexpectedValue(type_id.to_string()))?;
+
+                Self::Enum {
+                    tag,
+                    value: Box::new(Self::from_felts(registry, variant, felts)?),
+                    debug_name: debug_name(),
+                }
+            }
+            CoreTypeConcrete::Box(info)
+            | CoreTypeConcrete::NonZero(info)
+            | CoreTypeConcrete::Snapshot(info) => Self::from_felts(registry, &info.ty, felts)?,
+            CoreTypeConcrete::Const(info) => Self::from_felts(registry, &info.inner_ty, felts)?,
+            _ => return Err(Error::UnsupportedValueType(type_id.to_string())),
+        })
+    }
+
+    /// Deserialize the arguments of a function, as they would be passed to
+    /// [`invoke_dynamic`](crate::executor::JitNativeExecutor::invoke_dynamic).
+    ///
+    /// The builtins and zero-sized parameters aren't part of the serialization, and every felt must
+    /// be consumed.
+    pub fn args_from_felts(
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        function_id: &FunctionId,
+        felts: &[Felt],
+    ) -> Result<Vec<Self>> {
+        let mut felts = felts.iter().copied();
+
+        let mut args = Vec::new();
+        for type_id in &registry.get_function(function_id)?.signature.param_types {
+            let type_info = registry.get_type(type_id)?;
+            if type_info.is_builtin() || type_info.is_zst(registry)? {
+                continue;
+            }
+
+            args.push(Self::from_felts(registry, type_id, &mut felts)?);
+        }
+
+        match felts.count() {
+            0 => Ok(args),
+            n => Err(Error::SignatureMismatch(format!(
+                "{n} felts were left after deserializing the arguments"
+            ))),
+        }
+    }
+
+    /// Serialize the value as the given type.
+    pub fn to_felts(
+        &self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+    ) -> Result<Vec<Felt>> {
+        let mut felts = Vec::new();
+        self.write_felts(registry, type_id, &mut felts)?;
+
+        Ok(felts)
+    }
+
+    fn write_felts(
+        &self,
+        registry: &ProgramRegistry<CoreType, CoreLibfunc>,
+        type_id: &ConcreteTypeId,
+        felts: &mut Vec<Felt>,
+    ) -> Result<()> {
+        match (self, registry.get_type(type_id)?) {
+            (
+                Self::Felt252(value),
+                CoreTypeConcrete::Felt252(_)
+                | CoreTypeConcrete::StarkNet(
+                    StarkNetTypeConcrete::ClassHash(_)
+                    | StarkNetTypeConcrete::ContractAddress(_)
+                    | StarkNetTypeConcrete::StorageAddress(_)
+                    | StarkNetTypeConcrete::StorageBaseAddress(_),
+                ),
+            ) => felts.push(*value),
+            (Self::Uint8(value), CoreTypeConcrete::Uint8(_)) => felts.push((*value).into()),
+            (Self::Uint16(value), CoreTypeConcrete::Uint16(_)) => felts.push((*value).into()),
+            (Self::Uint32(value), CoreTypeConcrete::Uint32(_)) => felts.push((*value).into()),
+            (Self::Uint64(value), CoreTypeConcrete::Uint64(_)) => felts.push((*value).into()),
+            (Self::Uint128(value), CoreTypeConcrete::Uint128(_)) => felts.push((*value).into()),
+            (Self::Sint8(value), CoreTypeConcrete::Sint8(_)) => felts.push((*value).into()),
+            (Self::Sint16(value), CoreTypeConcrete::Sint16(_)) => felts.push((*value).into()),
+            (Self::Sint32(value), CoreTypeConcrete::Sint32(_)) => felts.push((*value).into()),
+            (Self::Sint64(value), CoreTypeConcrete::Sint64(_)) => felts.push((*value).into()),
+            (Self::Sint128(value), CoreTypeConcrete::Sint128(_)) => felts.push((*value).into()),
+            (Self::Bytes31(value), CoreTypeConcrete::Bytes31(_)) => {
+                felts.push(Felt::from_bytes_le_slice(value))
+            }
+            (Self::BoundedInt { value, .. }, CoreTypeConcrete::BoundedInt(_)) => felts.push(*value),
+            (Self::Array(items), CoreTypeConcrete::Array(info)) => {
+                felts.push(items.len().into());
+                for item in items {
+                    item.write_felts(registry, &info.ty, felts)?;
+                }
+            }
+            (Self::Struct { fields, .. }, CoreTypeConcrete::Struct(info))
+                if fields.len() == info.members.len() =>
+            {
+                for (field, member) in fields.iter().zip(&info.members) {
+                    field.write_felts(registry, member, felts)?;
+                }
+            }
+            (Self::Enum { tag, value, .. }, CoreTypeConcrete::Enum(info))
+                if *tag < info.variants.len() =>
+            {
+                felts.push((*tag).into());
+                value.write_felts(registry, &info.variants[*tag], felts)?;
+            }
+            (
+                value,
+                CoreTypeConcrete::Box(info)
+                | CoreTypeConcrete::NonZero(info)
+                | CoreTypeConcrete::Snapshot(info),
+            ) => value.write_felts(registry, &info.ty, felts)?,
+            (value, CoreTypeConcrete::Const(info)) => {
+                value.write_felts(registry, &info.inner_ty, felts)?
+            }
+            (_, type_info) if !is_serializable(type_info) => {
+                return Err(Error::UnsupportedValueType(type_id.to_string()))
+            }
+            _ => return Err(Error::UnexpectedValue(type_id.to_string())),
+        }
+
+        Ok(())
+    }
+}
+
+/// Return whether the type has a felt serialization.
+fn is_serializable(type_info: &CoreTypeConcrete) -> bool {
+    matches!(
+        type_info,
+        CoreTypeConcrete::Felt252(_)
+            | CoreTypeConcrete::StarkNet(
+                StarkNetTypeConcrete::ClassHash(_)
+                    | StarkNetTypeConcrete::ContractAddress(_)
+                    | StarkNetTypeConcrete::StorageAddress(_)
+                    | StarkNetTypeConcrete::StorageBaseAddress(_)
+            )
+            | CoreTypeConcrete::Uint8(_)
+            | CoreTypeConcrete::Uint16(_)
+            | CoreTypeConcrete::Uint32(_)
+            | CoreTypeConcrete::Uint64(_)
+            | CoreTypeConcrete::Uint128(_)
+            | CoreTypeConcrete::Sint8(_)
+            | CoreTypeConcrete::Sint16(_)
+            | CoreTypeConcrete::Sint32(_)
+            | CoreTypeConcrete::Sint64(_)
+            | CoreTypeConcrete::Sint128(_)
+            | CoreTypeConcrete::Bytes31(_)
+            | CoreTypeConcrete::BoundedInt(_)
+            | CoreTypeConcrete::Array(_)
+            | CoreTypeConcrete::Struct(_)
+            | CoreTypeConcrete::Enum(_)
+    )
+}
+
+/// Convert a felt into its signed representation, where the values above half the prime are
+/// negative.
+pub(super) fn felt_to_signed(value: Felt) -> BigInt {
+    let value = value.to_biguint();
+    if value > *HALF_PRIME {
+        BigInt::from(value) - BigInt::from(PRIME.clone())
+    } else {
+        value.into()
+    }
+}
+
+/// Convert a felt into an integer, failing if it's out of the integer's range.
+fn felt_to_int<T: TryFrom<BigInt>>(value: Felt, type_id: &ConcreteTypeId) -> Result<T> {
+    T::try_from(felt_to_signed(value)).map_err(|_| Error::UnexpectedValue(type_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test::{find_test_function, jit_enum, jit_struct, load_cairo, run_program};
+
+    #[test]
+    fn roundtrip() {
+        let program = load_cairo! {
+            fn run_test(
+                _a: u256, _b: ByteArray, _c: Option<i8>, _d: Array<u16>, _e: (bool, felt252),
+            ) {}
+        };
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program.1).unwrap();
+        let param_types = &find_test_function(&program, "run_test")
+            .signature
+            .param_types;
+
+        let felts = [
+            // u256 { low: 5, high: 1 }
+            vec![5.into(), 1.into()],
+            // "hello" as a ByteArray without full words.
+            vec![0.into(), Felt::from_hex_unchecked("0x68656c6c6f"), 5.into()],
+            // Option::Some(-3)
+            vec![0.into(), Felt::from(-3)],
+            // array![7, 8]
+            vec![2.into(), 7.into(), 8.into()],
+            // (true, 9)
+            vec![1.into(), 9.into()],
+        ];
+
+        let values = felts
+            .iter()
+            .zip(param_types)
+            .map(|(felts, type_id)| {
+                Value::from_felts(&registry, type_id, &mut felts.iter().copied()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(values[0], jit_struct!(Value::Uint128(5), Value::Uint128(1)));
+        assert_eq!(values[2], jit_enum!(0, Value::Sint8(-3)));
+        assert_eq!(values[3], Value::from(vec![7u16, 8]));
+        assert_eq!(
+            values[4],
+            jit_struct!(jit_enum!(1, jit_struct!()), Value::Felt252(9.into()))
+        );
+
+        for ((value, type_id), felts) in values.iter().zip(param_types).zip(&felts) {
+            assert_eq!(&value.to_felts(&registry, type_id).unwrap(), felts);
+        }
+    }
+
+    #[test]
+    fn out_of_range() {
+        let program = load_cairo! {
+            fn run_test(_a: u8, _b: bytes31, _c: Option<felt252>) {}
+        };
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program.1).unwrap();
+        let param_types = &find_test_function(&program, "run_test")
+            .signature
+            .param_types;
+
+        let from_felts = |type_id, felts: &[Felt]| {
+            Value::from_felts(&registry, type_id, &mut felts.iter().copied())
+        };
+
+        assert!(matches!(
+            from_felts(&param_types[0], &[256.into()]),
+            Err(Error::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            from_felts(&param_types[0], &[Felt::from(-1)]),
+            Err(Error::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            from_felts(&param_types[1], &[Felt::MAX]),
+            Err(Error::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            from_felts(&param_types[2], &[2.into()]),
+            Err(Error::UnexpectedValue(_))
+        ));
+        assert!(matches!(
+            from_felts(&param_types[2], &[0.into()]),
+            Err(Error::MissingParameter(_))
+        ));
+    }
+
+    #[test]
+    fn invoke_with_felts() {
+        let program = load_cairo! {
+            fn run_test(a: u256, b: Span<u32>) -> u256 {
+                let mut sum = a;
+                for x in b {
+                    sum += (*x).into();
+                };
+                sum
+            }
+        };
+        let registry = ProgramRegistry::<CoreType, CoreLibfunc>::new(&program.1).unwrap();
+        let function = find_test_function(&program, "run_test");
+
+        let args = Value::args_from_felts(
+            &registry,
+            &function.id,
+            &[
+                u128::MAX.into(),
+                0.into(),
+                3.into(),
+                1.into(),
+                2.into(),
+                3.into(),
+            ],
+        )
+        .unwrap();
+        let result = run_program(&program, "run_test", &args);
+
+        let return_type = function.signature.ret_types.last().unwrap();
+        assert_eq!(
+            result
+                .return_value
+                .to_felts(&registry, return_type)
+                .unwrap(),
+            [0.into(), 5.into(), 1.into()],
+        );
+
+        assert!(matches!(
+            Value::args_from_felts(
+                &registry,
+                &function.id,
+                &[1.into(), 0.into(), 0.into(), 1.into()]
+            ),
+            Err(Error::SignatureMismatch(_))
+        ));
+    }
+}
@@ -1,5 +1,10 @@
 use crate::common::load_cairo;
-use cairo_native::context::NativeContext;
+use cairo_lang_sierra::program_registry::ProgramRegistry;
+use cairo_native::{
+    context::NativeContext, executor::AotNativeExecutor, options::CompilationOptions,
+    utils::find_function_id, Value,
+};
+use libloading::Library;
 use std::error::Error;
 use tempfile::NamedTempFile;
 
@@ -16,10 +21,62 @@ pub fn compile_library() -> Result<(), Box<dyn Error>> {
 
     let module = context.compile(&program.1, false, Some(Default::default()))?;
 
-    let object = cairo_native::module_to_object(module.module(), Default::default())?;
+    let object = cairo_native::module_to_object(module.module(), Default::default(), None)?;
 
     let file = NamedTempFile::new()?.into_temp_path();
     cairo_native::object_to_shared_lib(&object, &file)?;
 
     Ok(())
 }
+
+#[test]
+pub fn compile_library_parallel() -> Result<(), Box<dyn Error>> {
+    let context = NativeContext::new();
+
+    let (module_name, program, _) = load_cairo! {
+        fn run_test(lhs: felt252, rhs: felt252) -> felt252 {
+            double(lhs) + square(rhs)
+        }
+
+        #[inline(never)]
+        fn double(value: felt252) -> felt252 {
+            value + value
+        }
+
+        #[inline(never)]
+        fn square(value: felt252) -> felt252 {
+            value * value
+        }
+    };
+
+    let mut module = context.compile(&program, false, Some(Default::default()))?;
+
+    let objects = cairo_native::module_to_objects(
+        module.module(),
+        Default::default(),
+        None,
+        &CompilationOptions {
+            codegen_units: 4,
+            ..Default::default()
+        },
+    )?;
+    assert!(objects.len() > 1);
+
+    let shared_lib = cairo_native::objects_to_ephemeral_shared_lib(&objects, &Default::default())?;
+    let executor = AotNativeExecutor::new(
+        unsafe { Library::new(shared_lib.path())? },
+        ProgramRegistry::new(&program)?,
+        module.remove_metadata().unwrap(),
+        module.remove_metadata().unwrap_or_default(),
+    );
+
+    let entry_point = format!("{0}::{0}::run_test", module_name);
+    let result = executor.invoke_dynamic(
+        find_function_id(&program, &entry_point).unwrap(),
+        &[Value::Felt252(3.into()), Value::Felt252(5.into())],
+        Some(u64::MAX),
+    )?;
+    assert_eq!(result.return_value, Value::Felt252(31.into()));
+
+    Ok(())
+}
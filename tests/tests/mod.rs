@@ -13,3 +13,4 @@ pub mod result;
 pub mod starknet;
 pub mod trampoline;
 pub mod uint;
+pub mod values;
@@ -0,0 +1,462 @@
+use crate::common::{load_cairo, run_native_program};
+use cairo_lang_runner::SierraCasmRunner;
+use cairo_lang_sierra::{extensions::utils::Range, program::Program, ProgramParser};
+use cairo_native::{
+    context::NativeContext,
+    execution_result::ExecutionResult,
+    executor::JitNativeExecutor,
+    starknet::{DummySyscallHandler, Secp256k1Point, Secp256r1Point},
+    starknet_stub::StubSyscallHandler,
+    OptLevel, Value,
+};
+use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use starknet_types_core::felt::Felt;
+
+lazy_static! {
+    static ref BYTES31: (String, Program, SierraCasmRunner) = load_cairo! {
+        fn identity(x: bytes31) -> bytes31 {
+            x
+        }
+
+        fn identity_array(x: Array<bytes31>) -> Array<bytes31> {
+            x
+        }
+
+        fn to_felt(x: bytes31) -> felt252 {
+            x.into()
+        }
+    };
+    static ref SECP256: (String, Program, SierraCasmRunner) = load_cairo! {
+        use starknet::{
+            secp256k1::Secp256k1Point, secp256r1::Secp256r1Point, secp256_trait::Secp256PointTrait,
+            SyscallResultTrait,
+        };
+
+        fn identity_k1(x: Secp256k1Point) -> Secp256k1Point {
+            x
+        }
+
+        fn identity_k1_array(x: Array<Secp256k1Point>) -> Array<Secp256k1Point> {
+            x
+        }
+
+        fn identity_r1(x: Secp256r1Point) -> Secp256r1Point {
+            x
+        }
+
+        fn add_k1(p: Secp256k1Point, q: Secp256k1Point) -> Secp256k1Point {
+            p.add(q).unwrap_syscall()
+        }
+    };
+    static ref SHA256: (String, Program, SierraCasmRunner) = load_cairo! {
+        use core::sha256::{Sha256StateHandle, sha256_state_handle_init, sha256_state_handle_digest};
+
+        fn identity(x: Sha256StateHandle) -> Sha256StateHandle {
+            x
+        }
+
+        fn init(x: [u32; 8]) -> Sha256StateHandle {
+            sha256_state_handle_init(BoxTrait::new(x))
+        }
+
+        fn digest(x: Sha256StateHandle) -> [u32; 8] {
+            sha256_state_handle_digest(x).unbox()
+        }
+    };
+    static ref CIRCUIT_MODULUS: (String, Program, SierraCasmRunner) = load_cairo! {
+        use core::circuit::CircuitModulus;
+
+        fn run_test() -> CircuitModulus {
+            TryInto::<_, CircuitModulus>::try_into([7, 0, 1, 0]).unwrap()
+        }
+    };
+    static ref SINT64_WITH_BUILTIN: (String, Program, SierraCasmRunner) = load_cairo! {
+        fn run_test(x: felt252) -> i64 {
+            match x.try_into() {
+                Option::Some(x) => x,
+                Option::None => 0,
+            }
+        }
+    };
+    // The types Cairo code can't name (like the circuit's containers) are used from Sierra.
+    static ref SIERRA_VALUES: Program = ProgramParser::new()
+        .parse(
+            r#"
+                type u128 = u128;
+                type U128MulGuarantee = U128MulGuarantee;
+                type Unit = Struct<ut@Tuple>;
+                type Coupon = Coupon<user@unit>;
+                type RangeCheck96 = RangeCheck96;
+                type u96 = BoundedInt<0, 79228162514264337593543950335>;
+                type U96Guarantee = U96Guarantee;
+                type u384 = Struct<ut@core::circuit::u384, u96, u96, u96, u96>;
+                type U96Limbs = Struct<ut@Tuple, U96Guarantee, U96Guarantee, U96Guarantee, U96Guarantee>;
+                type CircuitInput<0> = CircuitInput<0>;
+                type CircuitInput<1> = CircuitInput<1>;
+                type AddModGate = AddModGate<CircuitInput<0>, CircuitInput<1>>;
+                type Gates = Struct<ut@Tuple, AddModGate>;
+                type Circuit = Circuit<Gates>;
+                type CircuitInputAccumulator = CircuitInputAccumulator<Circuit>;
+                type CircuitData = CircuitData<Circuit>;
+                type CircuitOutputs = CircuitOutputs<Circuit>;
+                type AddInputResult = Enum<ut@AddInputResult, CircuitData, CircuitInputAccumulator>;
+
+                libfunc u128_guarantee_mul = u128_guarantee_mul;
+                libfunc drop<u128> = drop<u128>;
+                libfunc struct_construct<Unit> = struct_construct<Unit>;
+                libfunc into_u96_guarantee<u96> = into_u96_guarantee<u96>;
+                libfunc init_circuit_data<Circuit> = init_circuit_data<Circuit>;
+                libfunc struct_deconstruct<u384> = struct_deconstruct<u384>;
+                libfunc struct_construct<U96Limbs> = struct_construct<U96Limbs>;
+                libfunc add_circuit_input<Circuit> = add_circuit_input<Circuit>;
+                libfunc enum_init<AddInputResult, 0> = enum_init<AddInputResult, 0>;
+                libfunc enum_init<AddInputResult, 1> = enum_init<AddInputResult, 1>;
+
+                u128_guarantee_mul([0], [1]) -> ([2], [3], [4]);
+                drop<u128>([2]) -> ();
+                drop<u128>([3]) -> ();
+                return([4]);
+
+                return([0]);
+
+                return([0]);
+
+                struct_construct<Unit>() -> ([0]);
+                return([0]);
+
+                into_u96_guarantee<u96>([0]) -> ([1]);
+                return([1]);
+
+                return([0]);
+
+                init_circuit_data<Circuit>([0]) -> ([1], [2]);
+                return([1], [2]);
+
+                struct_deconstruct<u384>([1]) -> ([2], [3], [4], [5]);
+                into_u96_guarantee<u96>([2]) -> ([6]);
+                into_u96_guarantee<u96>([3]) -> ([7]);
+                into_u96_guarantee<u96>([4]) -> ([8]);
+                into_u96_guarantee<u96>([5]) -> ([9]);
+                struct_construct<U96Limbs>([6], [7], [8], [9]) -> ([10]);
+                add_circuit_input<Circuit>([0], [10]) { fallthrough([11]) 22([12]) };
+                enum_init<AddInputResult, 0>([11]) -> ([13]);
+                return([13]);
+                enum_init<AddInputResult, 1>([12]) -> ([13]);
+                return([13]);
+
+                return([0]);
+
+                return([0]);
+
+                mul_guarantee@0([0]: u128, [1]: u128) -> (U128MulGuarantee);
+                identity_mul_guarantee@4([0]: U128MulGuarantee) -> (U128MulGuarantee);
+                identity_coupon@5([0]: Coupon) -> (Coupon);
+                unit@6() -> (Unit);
+                u96_guarantee@8([0]: u96) -> (U96Guarantee);
+                identity_u96_guarantee@10([0]: U96Guarantee) -> (U96Guarantee);
+                init_circuit@11([0]: RangeCheck96) -> (RangeCheck96, CircuitInputAccumulator);
+                add_input@13([0]: CircuitInputAccumulator, [1]: u384) -> (AddInputResult);
+                identity_data@24([0]: CircuitData) -> (CircuitData);
+                identity_outputs@25([0]: CircuitOutputs) -> (CircuitOutputs);
+            "#,
+        )
+        .unwrap();
+}
+
+fn run(program: &(String, Program, SierraCasmRunner), entry_point: &str, args: &[Value]) -> Value {
+    run_native_program(
+        program,
+        entry_point,
+        args,
+        Some(u64::MAX),
+        Option::<DummySyscallHandler>::None,
+    )
+    .return_value
+}
+
+fn run_sierra(entry_point: &str, args: &[Value]) -> ExecutionResult {
+    let function_id = &SIERRA_VALUES
+        .funcs
+        .iter()
+        .find(|x| x.id.debug_name.as_deref() == Some(entry_point))
+        .expect("Test program entry point not found.")
+        .id;
+
+    let module = NativeContext::new()
+        .compile(&SIERRA_VALUES, false, None)
+        .unwrap();
+    let executor = JitNativeExecutor::from_native_module(module, OptLevel::None).unwrap();
+    executor.invoke_dynamic(function_id, args, None).unwrap()
+}
+
+fn panic_ok(value: Value) -> Value {
+    Value::Enum {
+        tag: 0,
+        value: Box::new(Value::Struct {
+            fields: vec![value],
+            debug_name: None,
+        }),
+        debug_name: None,
+    }
+}
+
+fn u96(value: u128) -> Value {
+    Value::BoundedInt {
+        value: value.into(),
+        range: Range {
+            lower: BigInt::ZERO,
+            upper: BigInt::from(1) << 96,
+        },
+    }
+}
+
+#[test]
+fn bytes31_roundtrip() {
+    let mut bytes = [0; 31];
+    bytes[0] = 0x42;
+    bytes[30] = 0xFF;
+
+    assert_eq!(
+        run(&BYTES31, "identity", &[Value::Bytes31(bytes)]),
+        Value::Bytes31(bytes),
+    );
+    assert_eq!(
+        run(
+            &BYTES31,
+            "identity_array",
+            &[Value::Array(vec![
+                Value::Bytes31(bytes),
+                Value::Bytes31([0; 31]),
+            ])]
+        ),
+        Value::Array(vec![Value::Bytes31(bytes), Value::Bytes31([0; 31])]),
+    );
+    assert_eq!(
+        run(&BYTES31, "to_felt", &[Value::Bytes31(bytes)]),
+        Value::Felt252(Felt::from_bytes_le_slice(&bytes)),
+    );
+}
+
+#[test]
+fn secp256_points_roundtrip() {
+    let k1 = Secp256k1Point::new(1, 2, 3, 4, false);
+    let r1 = Secp256r1Point::new(5, 6, 7, 8, true);
+
+    assert_eq!(
+        run(&SECP256, "identity_k1", &[Value::Secp256K1Point(k1)]),
+        Value::Secp256K1Point(k1),
+    );
+    assert_eq!(
+        run(
+            &SECP256,
+            "identity_k1_array",
+            &[Value::Array(vec![
+                Value::Secp256K1Point(k1),
+                Value::Secp256K1Point(Secp256k1Point::default()),
+            ])]
+        ),
+        Value::Array(vec![
+            Value::Secp256K1Point(k1),
+            Value::Secp256K1Point(Secp256k1Point::default()),
+        ]),
+    );
+    assert_eq!(
+        run(&SECP256, "identity_r1", &[Value::Secp256R1Point(r1)]),
+        Value::Secp256R1Point(r1),
+    );
+}
+
+#[test]
+fn secp256k1_add_generator() {
+    let generator = Secp256k1Point::new(
+        0x029BFCDB2DCE28D959F2815B16F81798,
+        0x79BE667EF9DCBBAC55A06295CE870B07,
+        0xFD17B448A68554199C47D08FFB10D4B8,
+        0x483ADA7726A3C4655DA4FBFC0E1108A8,
+        false,
+    );
+    let double_generator = Secp256k1Point::new(
+        0x5C778E4B8CEF3CA7ABAC09B95C709EE5,
+        0xC6047F9441ED7D6D3045406E95C07CD8,
+        0xF7F632653266D0E1236431A950CFE52A,
+        0x1AE168FEA63DC339A3C58419466CEAEE,
+        false,
+    );
+
+    let result = run_native_program(
+        &SECP256,
+        "add_k1",
+        &[
+            Value::Secp256K1Point(generator),
+            Value::Secp256K1Point(generator),
+        ],
+        Some(u64::MAX),
+        Some(StubSyscallHandler::default()),
+    );
+    assert_eq!(
+        result.return_value,
+        panic_ok(Value::Secp256K1Point(double_generator)),
+    );
+}
+
+#[test]
+fn sha256_state_roundtrip() {
+    let state = Value::Struct {
+        fields: [1u32, 2, 3, 4, 5, 6, 7, 8].map(Value::from).to_vec(),
+        debug_name: None,
+    };
+
+    assert_eq!(run(&SHA256, "identity", &[state.clone()]), state);
+    assert_eq!(run(&SHA256, "init", &[state.clone()]), state);
+    assert_eq!(run(&SHA256, "digest", &[state.clone()]), state);
+}
+
+#[test]
+fn circuit_modulus_result() {
+    assert_eq!(
+        run(&CIRCUIT_MODULUS, "run_test", &[]),
+        panic_ok(Value::Struct {
+            fields: vec![u96(7), u96(0), u96(1), u96(0)],
+            debug_name: None,
+        }),
+    );
+}
+
+fn u384(limbs: [u128; 4]) -> Value {
+    Value::Struct {
+        fields: limbs.map(u96).to_vec(),
+        debug_name: None,
+    }
+}
+
+fn empty() -> Value {
+    Value::Struct {
+        fields: Vec::new(),
+        debug_name: None,
+    }
+}
+
+#[test]
+fn uint128_mul_guarantee_result() {
+    assert_eq!(
+        run_sierra("mul_guarantee", &[Value::Uint128(3), Value::Uint128(5)]).return_value,
+        empty(),
+    );
+
+    // Zero-sized arguments aren't passed.
+    assert_eq!(
+        run_sierra("identity_mul_guarantee", &[]).return_value,
+        empty()
+    );
+}
+
+#[test]
+fn coupon_roundtrip() {
+    assert_eq!(run_sierra("identity_coupon", &[]).return_value, empty());
+}
+
+#[test]
+fn u96_guarantee_roundtrip() {
+    let value = (1 << 96) - 2;
+
+    assert_eq!(
+        run_sierra("u96_guarantee", &[u96(value)]).return_value,
+        u96(value),
+    );
+    assert_eq!(
+        run_sierra("identity_u96_guarantee", &[u96(value)]).return_value,
+        u96(value),
+    );
+}
+
+#[test]
+fn range_check96_result() {
+    // Two inputs and a gate: the constant one, the inputs and the gate's result are range checked
+    // as four limbs each.
+    let result = run_sierra("init_circuit", &[]);
+    assert_eq!(result.builtin_stats.range_check_96, 24);
+    assert_eq!(result.return_value, empty());
+}
+
+#[test]
+fn circuit_data_roundtrip() {
+    let a = u384([1, 2, 3, 4]);
+    let b = u384([(1 << 96) - 1, 0, 5, 0]);
+
+    let accumulator = run_sierra("add_input", &[empty(), a.clone()]).return_value;
+    assert_eq!(
+        accumulator,
+        Value::Enum {
+            tag: 1,
+            value: Box::new(Value::Struct {
+                fields: vec![a.clone()],
+                debug_name: None,
+            }),
+            debug_name: None,
+        },
+    );
+    let Value::Enum {
+        value: accumulator, ..
+    } = accumulator
+    else {
+        unreachable!()
+    };
+
+    let data = Value::Struct {
+        fields: vec![a, b.clone()],
+        debug_name: None,
+    };
+    assert_eq!(
+        run_sierra("add_input", &[*accumulator, b]).return_value,
+        Value::Enum {
+            tag: 0,
+            value: Box::new(data.clone()),
+            debug_name: None,
+        },
+    );
+    assert_eq!(
+        run_sierra("identity_data", &[data.clone()]).return_value,
+        data
+    );
+}
+
+#[test]
+fn circuit_outputs_roundtrip() {
+    // The values of the inputs and the gate, followed by the modulus.
+    let outputs = Value::Struct {
+        fields: vec![
+            Value::Struct {
+                fields: vec![u384([1, 0, 0, 0]), u384([2, 0, 0, 0]), u384([3, 0, 0, 0])],
+                debug_name: None,
+            },
+            u384([7, 0, 1, 0]),
+        ],
+        debug_name: None,
+    };
+
+    assert_eq!(
+        run_sierra("identity_outputs", &[outputs.clone()]).return_value,
+        outputs,
+    );
+}
+
+#[test]
+fn sint64_result_with_builtin() {
+    assert_eq!(
+        run(
+            &SINT64_WITH_BUILTIN,
+            "run_test",
+            &[Value::Felt252(Felt::from(-5))]
+        ),
+        Value::Sint64(-5),
+    );
+    assert_eq!(
+        run(
+            &SINT64_WITH_BUILTIN,
+            "run_test",
+            &[Value::Felt252(Felt::from(1u128 << 64))]
+        ),
+        Value::Sint64(0),
+    );
+}